@@ -0,0 +1,26 @@
+//! Ramps every channel from 0 to full scale and back, to check the outputs with a scope.
+//!
+//! Usage: `cargo run --example sweep --features linux -- /dev/spidev0.0 <sync gpio line>`
+
+use std::{env, process, thread, time::Duration};
+
+use ad5328::{Ad5328, Ad5328Config, Channel};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = env::args().skip(1);
+    let (Some(spi_path), Some(line)) = (args.next(), args.next()) else {
+        eprintln!("usage: sweep <spidev path> <sync gpio line>");
+        process::exit(2);
+    };
+    let mut dac = Ad5328::open(spi_path, line.parse()?, Ad5328Config::default())?;
+
+    let ramp = (0..=4095).step_by(16).chain((0..=4095).rev().step_by(16));
+    loop {
+        for value in ramp.clone() {
+            for channel in 0..8 {
+                dac.set_channel(Channel::from(channel), value)?;
+            }
+            thread::sleep(Duration::from_micros(100));
+        }
+    }
+}
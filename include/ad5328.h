@@ -0,0 +1,54 @@
+#ifndef AD5328_H
+#define AD5328_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdbool.h>
+#include <stdint.h>
+
+/**
+ * Channel index is not in 0..=7
+ */
+#define AD5328_ERR_CHANNEL -1
+
+/**
+ * Channel value is over 4095
+ */
+#define AD5328_ERR_VALUE -2
+
+/**
+ * LDAC mode is not in 0..=2
+ */
+#define AD5328_ERR_LDAC_MODE -3
+
+/**
+ * Data write of `value` to `channel` (0 for A, ..., 7 for H)
+ */
+int32_t ad5328_encode_set_channel(uint8_t channel, uint16_t value);
+
+/**
+ * Control word with the GAIN, BUF and VDD bits of channels A...D and E...H
+ */
+uint16_t ad5328_encode_config(bool gain_ad,
+                              bool gain_eh,
+                              bool buf_ad,
+                              bool buf_eh,
+                              bool vdd_ad,
+                              bool vdd_eh);
+
+/**
+ * LDAC mode word, 0 for LDAC low, 1 for LDAC high and 2 for a single update
+ */
+int32_t ad5328_encode_ldac(uint8_t mode);
+
+/**
+ * Power-down of the channels whose bit is set, bit 0 for channel A
+ */
+uint16_t ad5328_encode_power_down(uint8_t channels);
+
+/**
+ * Reset of all DAC data, including the control data if `full_reset` is set
+ */
+uint16_t ad5328_encode_reset(bool full_reset);
+
+#endif  /* AD5328_H */
@@ -0,0 +1,624 @@
+//! Async driver for embedded-hal-async buses, enabled with the `async` feature.
+
+use core::convert::Infallible;
+
+use embedded_hal_async::{delay::DelayNs, spi::SpiDevice};
+
+use crate::{command, Ad5328Config, Channel, Error, StreamError};
+
+/// Async counterpart of [`Ad5328`](crate::Ad5328), talking to the DAC through an embedded-hal-async `SpiDevice`
+/// whose chip select is wired to SYNC. Frames are encoded on the stack, so the futures are `Send` whenever `SPI` is.
+pub struct Ad5328Async<SPI> {
+    spi: SPI,
+    /// Value last written per channel, `None` while unknown
+    values: [Option<u16>; 8],
+    powered_down: [bool; 8],
+    config: Ad5328Config,
+}
+
+impl<SPI> Ad5328Async<SPI>
+where
+    SPI: SpiDevice<u8>,
+{
+    async fn write(&mut self, cmd: u16) -> Result<(), Error<SPI::Error, Infallible>> {
+        #[cfg(feature = "log")]
+        log::trace!("ad5328: {}", command::Describe(cmd));
+        let frame = command::to_bytes(cmd);
+        self.spi.write(&frame).await.map_err(Error::Spi)
+    }
+
+    /// Initialize a new Ad5328Async instance, while configuring it for the first time
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal-async `SpiDevice` whose chip select is wired to SYNC
+    /// * `config` - The Ad5328 device configuration struct
+    pub async fn init(
+        spi: SPI,
+        config: Ad5328Config,
+    ) -> Result<Self, Error<SPI::Error, Infallible>> {
+        let mut ad5328 = Self {
+            spi,
+            values: [None; 8],
+            powered_down: [false; 8],
+            config: Ad5328Config::POWER_ON,
+        };
+        ad5328.configure(config).await?;
+        Ok(ad5328)
+    }
+
+    /// (Re-)configure the already initialized Ad5328
+    pub async fn configure(
+        &mut self,
+        config: Ad5328Config,
+    ) -> Result<(), Error<SPI::Error, Infallible>> {
+        let cmds = config.as_commands();
+        #[cfg(feature = "log")]
+        log::debug!(
+            "ad5328: configure {}, {}",
+            command::Describe(cmds[0]),
+            command::Describe(cmds[1])
+        );
+        for cmd in cmds {
+            self.write(cmd).await?;
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    /// Reset all DAC data. A full reset will also reset all control data
+    pub async fn reset(&mut self, full_reset: bool) -> Result<(), Error<SPI::Error, Infallible>> {
+        let cmd = command::reset(full_reset);
+        #[cfg(feature = "log")]
+        log::debug!("ad5328: {}", command::Describe(cmd));
+        self.write(cmd).await?;
+        self.values = [None; 8];
+        if full_reset {
+            self.powered_down = [false; 8];
+            self.config = Ad5328Config::POWER_ON;
+        }
+        Ok(())
+    }
+
+    /// Power down the channels that are set to true in their respective position
+    /// Channel A -> 0, ..., Channel H -> 7
+    pub async fn power_down(
+        &mut self,
+        channels: [bool; 8],
+    ) -> Result<(), Error<SPI::Error, Infallible>> {
+        let cmd = command::power_down(channels);
+        #[cfg(feature = "log")]
+        log::debug!("ad5328: {}", command::Describe(cmd));
+        self.write(cmd).await?;
+        self.powered_down = channels;
+        Ok(())
+    }
+
+    /// Set the value for a DAC channel. Max value is 4095
+    pub async fn set_channel(
+        &mut self,
+        channel: Channel,
+        value: u16,
+    ) -> Result<(), Error<SPI::Error, Infallible>> {
+        let cmd = command::set_channel(channel, value).ok_or(Error::OobMax(command::MAX_VALUE))?;
+        self.write(cmd).await?;
+        self.values[usize::from(u8::from(channel))] = Some(value);
+        Ok(())
+    }
+
+    /// Stream samples to a DAC channel, one frame per sample, pausing `sample_period_ns` after each frame.
+    /// All samples are validated before the first frame is written.
+    ///
+    /// Dropping the future between frames is safe: the DAC keeps the last sample written
+    /// and the driver can be used right away.
+    pub async fn stream_channel<D: DelayNs>(
+        &mut self,
+        channel: Channel,
+        samples: &[u16],
+        sample_period_ns: u32,
+        delay: &mut D,
+    ) -> Result<(), StreamError<SPI::Error, Infallible>> {
+        if let Some(index) = samples.iter().position(|&s| s > command::MAX_VALUE) {
+            return Err(StreamError {
+                index,
+                error: Error::OobMax(command::MAX_VALUE),
+            });
+        }
+        for (index, &sample) in samples.iter().enumerate() {
+            self.write(command::set_channel_unchecked(channel, sample))
+                .await
+                .map_err(|error| StreamError { index, error })?;
+            self.values[usize::from(u8::from(channel))] = Some(sample);
+            delay.delay_ns(sample_period_ns).await;
+        }
+        Ok(())
+    }
+
+    /// Fade `channel` linearly from its last value written through this driver to `target` over `duration_ms`,
+    /// in evenly spaced steps at least 1 ms apart, waiting before each step and yielding to other tasks meanwhile.
+    /// There are no more steps than codes to go, and the last step writes exactly `target` at the end of the
+    /// duration. A channel not written
+    /// since [`init`](Self::init) or a [`reset`](Self::reset) jumps to `target` right away.
+    ///
+    /// Dropping the future between frames is safe: the channel keeps the last step written, which is also the
+    /// start of the next fade
+    pub async fn fade_to<D: DelayNs>(
+        &mut self,
+        channel: Channel,
+        target: u16,
+        duration_ms: u32,
+        delay: &mut D,
+    ) -> Result<(), Error<SPI::Error, Infallible>> {
+        if target > command::MAX_VALUE {
+            return Err(Error::OobMax(command::MAX_VALUE));
+        }
+        let Some(from) = self.values[usize::from(u8::from(channel))] else {
+            return self.set_channel(channel, target).await;
+        };
+        let steps = duration_ms.min(u32::from(from.abs_diff(target))).max(1) as u16;
+        let step_us = u64::from(duration_ms) * 1000 / u64::from(steps);
+        // at most `duration_ms`, so the milliseconds fit where the microseconds may not
+        let (ms, us) = ((step_us / 1000) as u32, (step_us % 1000) as u32);
+        for step in 1..=steps {
+            delay.delay_ms(ms).await;
+            if us > 0 {
+                delay.delay_us(us).await;
+            }
+            let value = crate::ramp::ramp_value(from, target, step, steps);
+            self.set_channel(channel, value).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<SPI> Ad5328Async<SPI>
+where
+    SPI: SpiDevice<u8>,
+{
+    /// Sink of channel updates, e.g. to `forward` a stream of samples into.
+    ///
+    /// `start_send` stages an item and `poll_flush` writes its frame, `poll_ready` is pending until the staged
+    /// frame is written. A value over 4095 fails the flush with [`Error::OobMax`]. Dropping the sink while a frame
+    /// is in flight drops the `SpiDevice` transaction, which leaves releasing SYNC to the `SpiDevice` implementation.
+    /// The sink is not `Unpin`, pin it with `core::pin::pin!` to use the `SinkExt` methods taking `&mut self`
+    pub fn sink(
+        &mut self,
+    ) -> impl futures_util::Sink<(Channel, u16), Error = Error<SPI::Error, Infallible>> + '_ {
+        futures_util::sink::unfold(self, |dac, (channel, value)| async move {
+            dac.set_channel(channel, value).await?;
+            Ok(dac)
+        })
+    }
+}
+
+/// Conversions to and from the blocking driver on an `SpiDevice` implementing both the blocking and async traits,
+/// e.g. to write a safe state before an executor is running. The bus is not touched and nothing is reconfigured,
+/// the configuration, power-down state and codes last written carry across
+#[cfg(feature = "eh1")]
+impl<SPI> Ad5328Async<SPI> {
+    /// Continue with the blocking driver, with the configuration last written, so e.g. the gain applies to
+    /// [`set_channel_millivolts`](crate::Ad5328::set_channel_millivolts), and the channels powered down
+    pub fn into_blocking(self) -> crate::Ad5328<SPI, crate::eh1::DeviceCs> {
+        let mut dac = crate::Ad5328::new(self.spi, crate::eh1::DeviceCs);
+        dac.config = self.config;
+        dac.powered_down = self.powered_down;
+        dac.data = self.values;
+        dac
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<SPI> crate::Ad5328<SPI, crate::eh1::DeviceCs> {
+    /// Continue with the async driver, see [`Ad5328Async::into_blocking`]. The codes last written to the channels
+    /// are where [`fade_to`](Ad5328Async::fade_to) starts from, after the value pipeline and on the physical
+    /// channels of a [channel map](crate::Ad5328::set_channel_map)
+    pub fn into_async(self) -> Ad5328Async<SPI> {
+        let powered_down = self.physical_mask(self.powered_down);
+        let mut values = [None; 8];
+        for n in 0..8 {
+            let channel = Channel::from(n);
+            let physical = usize::from(u8::from(self.physical_channel(channel)));
+            values[physical] = self.last_code(channel);
+        }
+        Ad5328Async {
+            spi: self.spi,
+            values,
+            powered_down,
+            config: self.config,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh1::spi::{Mock, Transaction};
+    use futures::{executor::block_on, FutureExt};
+
+    #[derive(Default)]
+    struct RecordingDelay(Vec<u32>);
+
+    impl DelayNs for RecordingDelay {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.0.push(ns);
+        }
+    }
+
+    struct NeverDelay;
+
+    impl DelayNs for NeverDelay {
+        async fn delay_ns(&mut self, _ns: u32) {
+            core::future::pending().await
+        }
+    }
+
+    fn frame(bytes: [u8; 2]) -> [Transaction<u8>; 3] {
+        [
+            Transaction::transaction_start(),
+            Transaction::write_vec(bytes.to_vec()),
+            Transaction::transaction_end(),
+        ]
+    }
+
+    fn assert_send<T: Send>(t: T) -> T {
+        t
+    }
+
+    #[test]
+    fn writes_match_blocking_encoding() {
+        let expectations: Vec<_> = [
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            [0x21, 0x23],
+            [0xc0, 0x81],
+            [0xe0, 0x00],
+        ]
+        .into_iter()
+        .flat_map(frame)
+        .collect();
+        let mut spi = Mock::new(&expectations);
+        block_on(async {
+            let mut dac = assert_send(Ad5328Async::init(spi.clone(), Ad5328Config::default()))
+                .await
+                .unwrap();
+            assert_send(dac.set_channel(Channel::C, 0x123))
+                .await
+                .unwrap();
+            assert!(matches!(
+                dac.set_channel(Channel::C, 4096).await,
+                Err(Error::OobMax(4095))
+            ));
+            dac.power_down([true, false, false, false, false, false, false, true])
+                .await
+                .unwrap();
+            dac.reset(false).await.unwrap();
+        });
+        spi.done();
+    }
+
+    #[test]
+    fn stream_channel_paces_frames() {
+        let expectations: Vec<_> = [
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            [0x30, 0x00],
+            [0x37, 0xff],
+            [0x3f, 0xff],
+        ]
+        .into_iter()
+        .flat_map(frame)
+        .collect();
+        let mut spi = Mock::new(&expectations);
+        let mut delay = RecordingDelay::default();
+        block_on(async {
+            let mut dac = Ad5328Async::init(spi.clone(), Ad5328Config::default())
+                .await
+                .unwrap();
+            dac.stream_channel(Channel::D, &[0, 0x7ff, 0xfff], 20_833, &mut delay)
+                .await
+                .unwrap();
+        });
+        assert_eq!(delay.0, [20_833; 3]);
+        spi.done();
+    }
+
+    #[test]
+    fn stream_channel_rejects_samples_up_front() {
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01]]
+            .into_iter()
+            .flat_map(frame)
+            .collect();
+        let mut spi = Mock::new(&expectations);
+        block_on(async {
+            let mut dac = Ad5328Async::init(spi.clone(), Ad5328Config::default())
+                .await
+                .unwrap();
+            let err = dac
+                .stream_channel(
+                    Channel::D,
+                    &[0, 1, 4096, 2],
+                    1000,
+                    &mut RecordingDelay::default(),
+                )
+                .await
+                .unwrap_err();
+            assert_eq!(err.index, 2);
+            assert!(matches!(err.error, Error::OobMax(4095)));
+        });
+        spi.done();
+    }
+
+    #[test]
+    fn dropped_stream_leaves_driver_usable() {
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01], [0x00, 0x0a], [0x10, 0x01]]
+            .into_iter()
+            .flat_map(frame)
+            .collect();
+        let mut spi = Mock::new(&expectations);
+        block_on(async {
+            let mut dac = Ad5328Async::init(spi.clone(), Ad5328Config::default())
+                .await
+                .unwrap();
+            let mut delay = NeverDelay;
+            let stream = dac.stream_channel(Channel::A, &[0x00a, 0x00b, 0x00c], 1000, &mut delay);
+            assert!(stream.now_or_never().is_none());
+            dac.set_channel(Channel::B, 1).await.unwrap();
+        });
+        spi.done();
+    }
+
+    /// Completes `self.0` delays, then never again
+    struct PendAfter(usize);
+
+    impl DelayNs for PendAfter {
+        async fn delay_ns(&mut self, _ns: u32) {
+            if self.0 == 0 {
+                core::future::pending().await
+            }
+            self.0 -= 1;
+        }
+    }
+
+    #[test]
+    fn fade_to_steps_about_every_millisecond() {
+        let expectations: Vec<_> = [
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            // unknown start, a jump
+            [0x00, 0x00],
+            [0x00, 0x19],
+            [0x00, 0x32],
+            [0x00, 0x4b],
+            [0x00, 0x64],
+            // two codes to go in 10 ms
+            [0x00, 0x63],
+            [0x00, 0x62],
+        ]
+        .into_iter()
+        .flat_map(frame)
+        .collect();
+        let mut spi = Mock::new(&expectations);
+        let mut delay = RecordingDelay::default();
+        block_on(async {
+            let mut dac = Ad5328Async::init(spi.clone(), Ad5328Config::default())
+                .await
+                .unwrap();
+            dac.fade_to(Channel::A, 0, 100, &mut delay).await.unwrap();
+            assert!(delay.0.is_empty());
+            dac.fade_to(Channel::A, 100, 4, &mut delay).await.unwrap();
+            assert_eq!(delay.0, [1_000_000; 4]);
+            delay.0.clear();
+            dac.fade_to(Channel::A, 98, 10, &mut delay).await.unwrap();
+            assert_eq!(delay.0, [5_000_000; 2]);
+            assert!(matches!(
+                dac.fade_to(Channel::A, 4096, 10, &mut delay).await,
+                Err(Error::OobMax(4095))
+            ));
+        });
+        spi.done();
+    }
+
+    #[test]
+    fn fades_wait_their_whole_duration() {
+        let expectations: Vec<_> = [
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            [0x00, 0x00],
+            // three codes in 10 ms
+            [0x00, 0x01],
+            [0x00, 0x02],
+            [0x00, 0x03],
+            // two codes in three hours
+            [0x00, 0x02],
+            [0x00, 0x01],
+        ]
+        .into_iter()
+        .flat_map(frame)
+        .collect();
+        let mut spi = Mock::new(&expectations);
+        let mut delay = RecordingDelay::default();
+        let waited_ns = |delay: &mut RecordingDelay| delay.0.drain(..).map(u64::from).sum::<u64>();
+        block_on(async {
+            let mut dac = Ad5328Async::init(spi.clone(), Ad5328Config::default())
+                .await
+                .unwrap();
+            dac.set_channel(Channel::A, 0).await.unwrap();
+            dac.fade_to(Channel::A, 3, 10, &mut delay).await.unwrap();
+            assert_eq!(delay.0, [3_000_000, 333_000].repeat(3));
+            assert_eq!(waited_ns(&mut delay), 3 * 3_333_000);
+            let hours = 3 * 60 * 60 * 1000;
+            dac.fade_to(Channel::A, 1, hours, &mut delay).await.unwrap();
+            assert_eq!(waited_ns(&mut delay), u64::from(hours) * 1_000_000);
+        });
+        spi.done();
+    }
+
+    #[test]
+    fn dropped_fade_continues_from_the_last_step() {
+        let expectations: Vec<_> = [
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            [0x00, 0x62],
+            [0x00, 0x31],
+            [0x00, 0x00],
+        ]
+        .into_iter()
+        .flat_map(frame)
+        .collect();
+        let mut spi = Mock::new(&expectations);
+        block_on(async {
+            let mut dac = Ad5328Async::init(spi.clone(), Ad5328Config::default())
+                .await
+                .unwrap();
+            dac.set_channel(Channel::A, 98).await.unwrap();
+            let mut delay = PendAfter(1);
+            let fade = dac.fade_to(Channel::A, 0, 2, &mut delay);
+            assert!(fade.now_or_never().is_none());
+            dac.fade_to(Channel::A, 0, 1, &mut RecordingDelay::default())
+                .await
+                .unwrap();
+        });
+        spi.done();
+    }
+
+    #[cfg(feature = "eh1")]
+    #[test]
+    fn drivers_convert_without_touching_the_bus() {
+        let config = Ad5328Config {
+            gain: (crate::GAIN::Gain02Vref, crate::GAIN::Gain0Vref),
+            ..Default::default()
+        };
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01], [0x00, 0x00]]
+            .into_iter()
+            .chain(config.as_commands().map(command::to_bytes))
+            .chain([
+                [0xc0, 0x80],
+                [0x10, 0x01],
+                // 1000 mV at twice the 2500 mV reference
+                [0x23, 0x33],
+            ])
+            .flat_map(frame)
+            .collect();
+        let mut spi = Mock::new(&expectations);
+        let mut dac = crate::Ad5328::init_device(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::A, 0).unwrap();
+        let mut dac = dac.into_async();
+        block_on(dac.configure(config)).unwrap();
+        block_on(dac.power_down([false, false, false, false, false, false, false, true])).unwrap();
+        block_on(dac.set_channel(Channel::B, 1)).unwrap();
+        let mut dac = dac.into_blocking();
+        dac.set_channel_millivolts(Channel::C, 1000, 2500).unwrap();
+        assert_eq!(dac.config.as_commands(), config.as_commands());
+        assert_eq!(
+            dac.powered_down,
+            [false, false, false, false, false, false, false, true]
+        );
+        let dac = dac.into_async();
+        assert_eq!(dac.config.as_commands(), config.as_commands());
+        assert!(dac.powered_down[7]);
+        spi.done();
+    }
+
+    #[cfg(feature = "eh1")]
+    #[test]
+    fn fades_start_from_codes_written_before_a_conversion() {
+        let expectations: Vec<_> = [
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            [0x00, 0x64],
+            [0x00, 0x65],
+            [0x00, 0x66],
+            [0x10, 0x07],
+        ]
+        .into_iter()
+        .flat_map(frame)
+        .collect();
+        let mut spi = Mock::new(&expectations);
+        let mut dac = crate::Ad5328::init_device(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::A, 100).unwrap();
+        let mut dac = dac.into_async();
+        let mut delay = RecordingDelay::default();
+        block_on(dac.fade_to(Channel::A, 102, 2, &mut delay)).unwrap();
+        assert_eq!(delay.0, [1_000_000; 2]);
+        block_on(dac.set_channel(Channel::B, 7)).unwrap();
+        let dac = dac.into_blocking();
+        assert_eq!(dac.last_code(Channel::A), Some(102));
+        assert_eq!(dac.last_code(Channel::B), Some(7));
+        assert_eq!(dac.last_code(Channel::C), None);
+        spi.done();
+    }
+
+    #[cfg(feature = "eh1")]
+    #[test]
+    fn codes_are_unknown_after_a_reset() {
+        let expectations: Vec<_> = [
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            [0x00, 0x05],
+            [0xe0, 0x00],
+            [0x00, 0x00],
+        ]
+        .into_iter()
+        .flat_map(frame)
+        .collect();
+        let mut spi = Mock::new(&expectations);
+        let mut dac = block_on(Ad5328Async::init(spi.clone(), Ad5328Config::default())).unwrap();
+        block_on(dac.set_channel(Channel::A, 5)).unwrap();
+        block_on(dac.reset(false)).unwrap();
+        let mut dac = dac.into_blocking().with_dedup(true);
+        assert_eq!(dac.last_code(Channel::A), None);
+        dac.set_channel(Channel::A, 0).unwrap();
+        spi.done();
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn sink_writes_forwarded_items_in_order() {
+        use futures::{stream, StreamExt};
+
+        let items: Vec<_> = (0..100u16)
+            .map(|n| (Channel::from((n % 8) as u8), n * 41))
+            .collect();
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01]]
+            .into_iter()
+            .chain(items.iter().map(|&(channel, value)| {
+                (u16::from(u8::from(channel)) << 12 | value).to_be_bytes()
+            }))
+            .flat_map(frame)
+            .collect();
+        let mut spi = Mock::new(&expectations);
+        block_on(async {
+            let mut dac = Ad5328Async::init(spi.clone(), Ad5328Config::default())
+                .await
+                .unwrap();
+            stream::iter(items.iter().copied().map(Ok))
+                .forward(dac.sink())
+                .await
+                .unwrap();
+        });
+        spi.done();
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn sink_reports_out_of_range_values() {
+        use futures::SinkExt;
+
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01]]
+            .into_iter()
+            .flat_map(frame)
+            .collect();
+        let mut spi = Mock::new(&expectations);
+        block_on(async {
+            let mut dac = Ad5328Async::init(spi.clone(), Ad5328Config::default())
+                .await
+                .unwrap();
+            let mut sink = core::pin::pin!(dac.sink());
+            assert!(matches!(
+                sink.send((Channel::A, 4096)).await,
+                Err(Error::OobMax(4095))
+            ));
+        });
+        spi.done();
+    }
+}
@@ -0,0 +1,303 @@
+//! Several AD5328s on one SPI bus, each with its own SYNC line.
+
+use embedded_hal::blocking::spi::Write;
+
+use crate::{chip_select::ChipSelect, command, Ad5328Config, Channel, Error};
+
+/// Error of a bank operation, together with the index of the device it occurred on
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BankError<S, P> {
+    /// Index of the failing device. For [`Error::Address`] this is the device the output would have been on
+    pub device: usize,
+    /// The underlying error
+    pub error: Error<S, P>,
+}
+
+impl<S, P> core::fmt::Display for BankError<S, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} on device {}", self.error, self.device)
+    }
+}
+
+impl<S, P> core::error::Error for BankError<S, P>
+where
+    S: core::error::Error + 'static,
+    P: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        core::error::Error::source(&self.error)
+    }
+}
+
+/// Driver for `N` AD5328s sharing one SPI bus, with the SYNC lines driven through a [`ChipSelect`].
+/// With one enable pin per device that is a [`GpioChipSelect`](crate::chip_select::GpioChipSelect).
+///
+/// Outputs are addressed as one flat range: output 0..=7 are channels A..H of device 0,
+/// output 8..=15 channels A..H of device 1 and so on.
+pub struct Ad5328Bank<SPI, CS, const N: usize> {
+    spi: SPI,
+    cs: CS,
+}
+
+impl<SPI, CS, S, P, const N: usize> Ad5328Bank<SPI, CS, N>
+where
+    SPI: Write<u8, Error = S>,
+    CS: ChipSelect<Error = P>,
+{
+    fn write(&mut self, device: usize, cmd: u16) -> Result<(), BankError<S, P>> {
+        self.write_frame(device, &command::to_bytes(cmd))
+            .map_err(|error| BankError { device, error })
+    }
+
+    fn write_frame(&mut self, device: usize, frame: &[u8; 2]) -> Result<(), Error<S, P>> {
+        if device >= self.cs.devices() {
+            return Err(Error::Address);
+        }
+        self.cs.assert(device).map_err(Error::Pin)?;
+        if let Err(e) = self.spi.write(frame) {
+            // the SPI error is the one worth reporting
+            let _ = self.cs.deassert();
+            return Err(Error::Spi(e));
+        }
+        self.cs.deassert().map_err(Error::Pin)?;
+        Ok(())
+    }
+
+    fn write_all(&mut self, cmd: impl Fn(usize) -> u16) -> Result<(), BankError<S, P>> {
+        for device in 0..N {
+            self.write(device, cmd(device))?;
+        }
+        Ok(())
+    }
+
+    /// Initialize a new Ad5328Bank instance, while configuring all devices for the first time
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal compatible SPI instance
+    /// * `cs` - Chip select for the `N` devices
+    /// * `config` - The Ad5328 device configuration struct, applied to every device
+    ///
+    /// A chip select for fewer than `N` devices fails with an [`Error::Address`] for the first device it
+    /// can't select, before anything is written
+    pub fn init(spi: SPI, cs: CS, config: Ad5328Config) -> Result<Self, BankError<S, P>> {
+        if cs.devices() < N {
+            return Err(BankError {
+                device: cs.devices(),
+                error: Error::Address,
+            });
+        }
+        let mut bank = Self { spi, cs };
+        bank.configure(config)?;
+        Ok(bank)
+    }
+
+    /// (Re-)configure all devices, one after another
+    pub fn configure(&mut self, config: Ad5328Config) -> Result<(), BankError<S, P>> {
+        for cmd in config.as_commands() {
+            self.write_all(|_| cmd)?;
+        }
+        Ok(())
+    }
+
+    /// Reset all DAC data of every device. A full reset will also reset all control data
+    pub fn reset(&mut self, full_reset: bool) -> Result<(), BankError<S, P>> {
+        self.write_all(|_| command::reset(full_reset))
+    }
+
+    /// Power down the channels that are set to true, per device.
+    /// Channel A -> 0, ..., Channel H -> 7
+    pub fn power_down(&mut self, channels: [[bool; 8]; N]) -> Result<(), BankError<S, P>> {
+        self.write_all(|device| command::power_down(channels[device]))
+    }
+
+    /// Set the value for an output of the bank. Max value is 4095, outputs past the last device are an [`Error::Address`]
+    pub fn set_output(&mut self, index: u8, value: u16) -> Result<(), BankError<S, P>> {
+        let device = usize::from(index / 8);
+        if device >= N {
+            return Err(BankError {
+                device,
+                error: Error::Address,
+            });
+        }
+        let cmd = command::set_channel(Channel::from(index % 8), value).ok_or(BankError {
+            device,
+            error: Error::OobMax(command::MAX_VALUE),
+        })?;
+        self.write(device, cmd)
+    }
+
+    /// Release the SPI instance and chip select
+    pub fn release(self) -> (SPI, CS) {
+        (self.spi, self.cs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip_select::GpioChipSelect;
+    use crate::testing::{Event, Log, LogSpi};
+    use embedded_hal_mock::eh0::{pin, spi};
+
+    fn syncs(n: usize) -> pin::Mock {
+        let toggle = [
+            pin::Transaction::set(pin::State::Low),
+            pin::Transaction::set(pin::State::High),
+        ];
+        pin::Mock::new(toggle.iter().cycle().take(n * 2))
+    }
+
+    #[test]
+    fn outputs_map_to_device_and_channel() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x00, 0x01]),
+            spi::Transaction::write(vec![0x10, 0x02]),
+            spi::Transaction::write(vec![0x7f, 0xff]),
+        ]);
+        let mut pins = [syncs(3), syncs(3), syncs(3)];
+        let mut bank = Ad5328Bank::<_, _, 3>::init(
+            spi.clone(),
+            GpioChipSelect::new(pins.clone()),
+            Ad5328Config::default(),
+        )
+        .unwrap();
+        bank.set_output(0, 1).unwrap();
+        bank.set_output(9, 2).unwrap();
+        bank.set_output(23, 4095).unwrap();
+        spi.done();
+        pins.iter_mut().for_each(pin::Mock::done);
+    }
+
+    #[test]
+    fn errors_carry_the_device_index() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+        ]);
+        let mut pins = [syncs(2), syncs(2)];
+        let mut bank = Ad5328Bank::<_, _, 2>::init(
+            spi.clone(),
+            GpioChipSelect::new(pins.clone()),
+            Ad5328Config::default(),
+        )
+        .unwrap();
+        let err = bank.set_output(16, 0).unwrap_err();
+        assert!(matches!(
+            err,
+            BankError {
+                device: 2,
+                error: Error::Address
+            }
+        ));
+        let err = bank.set_output(12, 4096).unwrap_err();
+        assert!(matches!(
+            err,
+            BankError {
+                device: 1,
+                error: Error::OobMax(4095)
+            }
+        ));
+        spi.done();
+        pins.iter_mut().for_each(pin::Mock::done);
+    }
+
+    #[test]
+    fn banks_larger_than_their_chip_select_fail_to_init() {
+        let mut spi = spi::Mock::new(&[]);
+        let mut pins = [syncs(0), syncs(0), syncs(0)];
+        let result = Ad5328Bank::<_, _, 4>::init(
+            spi.clone(),
+            GpioChipSelect::new(pins.clone()),
+            Ad5328Config::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(BankError {
+                device: 3,
+                error: Error::Address
+            })
+        ));
+        spi.done();
+        pins.iter_mut().for_each(pin::Mock::done);
+    }
+
+    /// Stands in for a 3-to-8 decoder behind an I²C expander that stops answering after `budget` calls
+    struct Decoder {
+        log: Log,
+        budget: usize,
+    }
+
+    impl ChipSelect for Decoder {
+        type Error = ();
+
+        fn devices(&self) -> usize {
+            8
+        }
+
+        fn assert(&mut self, device: usize) -> Result<(), ()> {
+            self.budget = self.budget.checked_sub(1).ok_or(())?;
+            self.log.push(Event::Assert(device));
+            Ok(())
+        }
+
+        fn deassert(&mut self) -> Result<(), ()> {
+            self.budget = self.budget.checked_sub(1).ok_or(())?;
+            self.log.push(Event::Deassert);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn address_is_held_around_the_frame() {
+        let log = Log::default();
+        let decoder = Decoder {
+            log: log.clone(),
+            budget: 2 * 3 * 2 + 2 + 2 + 1,
+        };
+        let mut bank: Ad5328Bank<_, _, 3> =
+            Ad5328Bank::init(LogSpi::new(&log), decoder, Ad5328Config::default()).unwrap();
+        log.events().clear();
+
+        bank.set_output(13, 0x123).unwrap();
+        assert_eq!(
+            *log.events(),
+            [
+                Event::Assert(1),
+                Event::Bytes(vec![0x51, 0x23]),
+                Event::Deassert
+            ]
+        );
+        log.events().clear();
+
+        // a failed frame leaves no device selected
+        bank.spi.failing = true;
+        let err = bank.set_output(13, 0x123).unwrap_err();
+        assert!(matches!(
+            err,
+            BankError {
+                device: 1,
+                error: Error::Spi(())
+            }
+        ));
+        assert_eq!(*log.events(), [Event::Assert(1), Event::Deassert]);
+        bank.spi.failing = false;
+
+        let err = bank.reset(false).unwrap_err();
+        assert!(matches!(
+            err,
+            BankError {
+                device: 0,
+                error: Error::Pin(())
+            }
+        ));
+    }
+}
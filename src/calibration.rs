@@ -0,0 +1,180 @@
+//! Versioned binary encoding of the per-channel [`Calibration`]s, e.g. for an EEPROM page.
+//!
+//! Version 1 is [`CalibrationSet::ENCODED_LEN`] bytes: the version byte, then for channel A to H the offset as
+//! `i16` and the gain in ppm as `i32`, both little-endian, then a CRC-8 (polynomial 0x07, initial value 0) over
+//! all bytes before it. Lookup tables and V/oct corrections are not part of it.
+
+use crate::{scale::Calibration, Channel, ChannelMap};
+
+/// Errors while encoding or decoding a [`CalibrationSet`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CalibrationError {
+    /// The output buffer can't hold the encoded set
+    BufferTooSmall,
+    /// The input ends before the encoded set does
+    Truncated,
+    /// The input is of a version this crate doesn't know
+    UnknownVersion(u8),
+    /// The checksum doesn't match, the input is corrupt
+    Checksum,
+}
+
+impl core::fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CalibrationError::BufferTooSmall => f.write_str("buffer too small"),
+            CalibrationError::Truncated => f.write_str("truncated calibration data"),
+            CalibrationError::UnknownVersion(version) => {
+                write!(f, "unknown calibration data version {}", version)
+            }
+            CalibrationError::Checksum => f.write_str("calibration data checksum mismatch"),
+        }
+    }
+}
+
+impl core::error::Error for CalibrationError {}
+
+/// The calibration of all eight channels, as installed with [`Ad53xx::set_calibrations`](crate::Ad53xx::set_calibrations)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CalibrationSet {
+    /// Calibration per channel
+    pub channels: ChannelMap<Calibration>,
+}
+
+impl CalibrationSet {
+    /// Current version of the encoding
+    pub const VERSION: u8 = 1;
+
+    /// Length of the encoded set
+    pub const ENCODED_LEN: usize = 1 + 8 * 6 + 1;
+
+    /// Encode the set at the start of `buf`, returning the number of bytes written
+    pub fn to_bytes(&self, buf: &mut [u8]) -> Result<usize, CalibrationError> {
+        let buf = buf
+            .get_mut(..Self::ENCODED_LEN)
+            .ok_or(CalibrationError::BufferTooSmall)?;
+        buf[0] = Self::VERSION;
+        for (out, calibration) in buf[1..].chunks_exact_mut(6).zip(&self.channels.0) {
+            out[..2].copy_from_slice(&calibration.offset.to_le_bytes());
+            out[2..].copy_from_slice(&calibration.gain_ppm.to_le_bytes());
+        }
+        buf[Self::ENCODED_LEN - 1] = crc8(&buf[..Self::ENCODED_LEN - 1]);
+        Ok(Self::ENCODED_LEN)
+    }
+
+    /// Decode a set from the start of `bytes`, any bytes after it are ignored
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CalibrationError> {
+        match bytes.first() {
+            None => return Err(CalibrationError::Truncated),
+            Some(&Self::VERSION) => {}
+            Some(&version) => return Err(CalibrationError::UnknownVersion(version)),
+        }
+        let bytes = bytes
+            .get(..Self::ENCODED_LEN)
+            .ok_or(CalibrationError::Truncated)?;
+        if crc8(&bytes[..Self::ENCODED_LEN - 1]) != bytes[Self::ENCODED_LEN - 1] {
+            return Err(CalibrationError::Checksum);
+        }
+        let mut set = Self::default();
+        for (n, data) in bytes[1..Self::ENCODED_LEN - 1].chunks_exact(6).enumerate() {
+            set.channels[Channel::from(n as u8)] = Calibration {
+                offset: i16::from_le_bytes([data[0], data[1]]),
+                gain_ppm: i32::from_le_bytes([data[2], data[3], data[4], data[5]]),
+            };
+        }
+        Ok(set)
+    }
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set() -> CalibrationSet {
+        let calibration = |offset, gain_ppm| Calibration { offset, gain_ppm };
+        CalibrationSet {
+            channels: ChannelMap([
+                calibration(0, 0),
+                calibration(-3, 125),
+                calibration(12, -4000),
+                calibration(0, 0),
+                calibration(1, 1),
+                calibration(i16::MIN, i32::MAX),
+                calibration(i16::MAX, i32::MIN),
+                calibration(100, 0),
+            ]),
+        }
+    }
+
+    /// Version 1 of [`set`], which must keep decoding as long as the crate reads version 1
+    const V1: &str = "01000000000000fdff7d0000000c0060f0ffff0000000000000100010000000080ffffff7fff7f0000008064000000000070";
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn version_1_is_frozen() {
+        let mut buf = [0xff; 64];
+        let len = set().to_bytes(&mut buf).unwrap();
+        assert_eq!(len, CalibrationSet::ENCODED_LEN);
+        assert_eq!(hex(&buf[..len]), V1);
+        assert_eq!(CalibrationSet::from_bytes(&unhex(V1)), Ok(set()));
+    }
+
+    #[test]
+    fn round_trip_ignores_the_rest_of_the_page() {
+        let mut page = [0xff; 64];
+        set().to_bytes(&mut page).unwrap();
+        assert_eq!(CalibrationSet::from_bytes(&page), Ok(set()));
+        assert_eq!(
+            CalibrationSet::default().to_bytes(&mut [0; 49]),
+            Err(CalibrationError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn truncated_and_corrupt_input_is_rejected() {
+        let v1 = unhex(V1);
+        assert_eq!(
+            CalibrationSet::from_bytes(&[]),
+            Err(CalibrationError::Truncated)
+        );
+        assert_eq!(
+            CalibrationSet::from_bytes(&v1[..v1.len() - 1]),
+            Err(CalibrationError::Truncated)
+        );
+        let mut corrupt = v1.clone();
+        corrupt[10] ^= 0x04;
+        assert_eq!(
+            CalibrationSet::from_bytes(&corrupt),
+            Err(CalibrationError::Checksum)
+        );
+        // an erased EEPROM page
+        assert_eq!(
+            CalibrationSet::from_bytes(&[0xff; 64]),
+            Err(CalibrationError::UnknownVersion(0xff))
+        );
+    }
+}
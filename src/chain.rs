@@ -0,0 +1,159 @@
+//! Several AD5328s daisy-chained on one SYNC line.
+
+use embedded_hal::{blocking::spi::Write, digital::v2::OutputPin};
+
+use crate::{command, interface::framed, Ad5328Config, Channel, Error};
+
+/// Driver for `N` daisy-chained AD5328s sharing SPI and SYNC, with DCEN tied high on every device.
+///
+/// Device 0 is the one whose DIN is wired to the MCU. Every operation shifts one 16-bit word per device
+/// within a single SYNC assertion, so all devices latch on the same rising edge. The words are sent
+/// farthest device first, as each one is shifted through its nearer neighbors.
+pub struct Ad5328Chain<SPI, EN, const N: usize> {
+    spi: SPI,
+    enable: EN,
+}
+
+impl<SPI, EN, S, P, const N: usize> Ad5328Chain<SPI, EN, N>
+where
+    SPI: Write<u8, Error = S>,
+    EN: OutputPin<Error = P>,
+{
+    fn write(&mut self, cmds: [u16; N]) -> Result<(), Error<S, P>> {
+        let spi = &mut self.spi;
+        framed(&mut self.enable, || {
+            cmds.iter()
+                .rev()
+                .try_for_each(|&cmd| spi.write(&command::to_bytes(cmd)))
+        })
+    }
+
+    /// Initialize a new Ad5328Chain instance, while configuring every device for the first time
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal compatible SPI instance
+    /// * `enable` - embedded-hal compatible GPIO pin wired to the shared SYNC line
+    /// * `configs` - The Ad5328 device configuration struct for each device in the chain
+    pub fn init(spi: SPI, enable: EN, configs: [Ad5328Config; N]) -> Result<Self, Error<S, P>> {
+        let mut chain = Self { spi, enable };
+        chain.configure(configs)?;
+        Ok(chain)
+    }
+
+    /// (Re-)configure every device in the chain
+    pub fn configure(&mut self, configs: [Ad5328Config; N]) -> Result<(), Error<S, P>> {
+        let cmds = configs.map(|config| config.as_commands());
+        self.write(cmds.map(|[gain_buf_vdd, _]| gain_buf_vdd))?;
+        self.write(cmds.map(|[_, ldac]| ldac))
+    }
+
+    /// Reset all DAC data of every device. A full reset will also reset all control data
+    pub fn reset(&mut self, full_reset: bool) -> Result<(), Error<S, P>> {
+        self.write([command::reset(full_reset); N])
+    }
+
+    /// Power down the channels that are set to true, per device.
+    /// Channel A -> 0, ..., Channel H -> 7
+    pub fn power_down(&mut self, channels: [[bool; 8]; N]) -> Result<(), Error<S, P>> {
+        self.write(channels.map(command::power_down))
+    }
+
+    /// Set one channel value per device. Max value is 4095, all values are checked before anything is written
+    pub fn set_channel_all(&mut self, values: &[(Channel, u16); N]) -> Result<(), Error<S, P>> {
+        let mut cmds = [0; N];
+        for (cmd, &(channel, value)) in cmds.iter_mut().zip(values) {
+            *cmd = command::set_channel(channel, value).ok_or(Error::OobMax(command::MAX_VALUE))?;
+        }
+        self.write(cmds)
+    }
+
+    /// Release the SPI instance and enable pin
+    pub fn release(self) -> (SPI, EN) {
+        (self.spi, self.enable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh0::{pin, spi};
+
+    fn syncs(n: usize) -> pin::Mock {
+        let toggle = [
+            pin::Transaction::set(pin::State::Low),
+            pin::Transaction::set(pin::State::High),
+        ];
+        pin::Mock::new(toggle.iter().cycle().take(n * 2))
+    }
+
+    fn writes(bytes: &[[u8; 2]]) -> Vec<spi::Transaction> {
+        bytes
+            .iter()
+            .map(|b| spi::Transaction::write(b.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn farthest_device_word_goes_first() {
+        let mut spi = spi::Mock::new(&writes(&[
+            [0x80, 0x0c],
+            [0x80, 0x0c],
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            [0xa0, 0x01],
+            [0xa0, 0x01],
+            [0x7f, 0xff],
+            [0x10, 0x02],
+            [0x00, 0x01],
+        ]));
+        let mut enable = syncs(3);
+        let mut chain: Ad5328Chain<_, _, 3> =
+            Ad5328Chain::init(spi.clone(), enable.clone(), [Ad5328Config::default(); 3]).unwrap();
+        chain
+            .set_channel_all(&[(Channel::A, 1), (Channel::B, 2), (Channel::H, 4095)])
+            .unwrap();
+        spi.done();
+        enable.done();
+    }
+
+    #[test]
+    fn out_of_bounds_value_writes_nothing() {
+        let mut spi = spi::Mock::new(&writes(&[
+            [0x80, 0x0c],
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            [0xa0, 0x01],
+            [0xc0, 0x80],
+            [0xc0, 0x01],
+        ]));
+        let mut enable = syncs(3);
+        let mut chain: Ad5328Chain<_, _, 2> =
+            Ad5328Chain::init(spi.clone(), enable.clone(), [Ad5328Config::default(); 2]).unwrap();
+        assert!(matches!(
+            chain.set_channel_all(&[(Channel::A, 1), (Channel::B, 4096)]),
+            Err(Error::OobMax(4095))
+        ));
+        let mut first = [false; 8];
+        first[0] = true;
+        let mut second = [false; 8];
+        second[7] = true;
+        chain.power_down([first, second]).unwrap();
+        spi.done();
+        enable.done();
+    }
+
+    #[test]
+    fn sync_is_released_when_the_chain_fails() {
+        use crate::testing::{Log, LogSpi};
+
+        let spi = LogSpi {
+            log: Log::default(),
+            failing: true,
+        };
+        let mut enable = syncs(1);
+        let result: Result<Ad5328Chain<_, _, 2>, _> =
+            Ad5328Chain::init(spi, enable.clone(), [Ad5328Config::default(); 2]);
+        assert!(matches!(result, Err(Error::Spi(()))));
+        enable.done();
+    }
+}
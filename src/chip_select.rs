@@ -0,0 +1,87 @@
+//! Selection of one device out of several, for SYNC lines that aren't one GPIO per device.
+
+use embedded_hal::digital::v2::OutputPin;
+
+/// Drives the SYNC lines of several devices, e.g. through a 3-to-8 decoder or an I²C GPIO expander.
+///
+/// At most one device is asserted at a time, [`deassert`](ChipSelect::deassert) releases whichever was asserted last.
+pub trait ChipSelect {
+    /// Error of asserting or releasing a device, reported as [`Error::Pin`](crate::Error::Pin)
+    type Error;
+
+    /// Number of devices that can be selected, `0..devices()` are the valid arguments of
+    /// [`assert`](ChipSelect::assert)
+    fn devices(&self) -> usize;
+
+    /// Pull the SYNC line of `device` low
+    fn assert(&mut self, device: usize) -> Result<(), Self::Error>;
+
+    /// Release the currently asserted device
+    fn deassert(&mut self) -> Result<(), Self::Error>;
+}
+
+/// One enable pin per device, the default wiring
+pub struct GpioChipSelect<EN, const N: usize> {
+    pins: [EN; N],
+    selected: Option<usize>,
+}
+
+impl<EN, const N: usize> GpioChipSelect<EN, N> {
+    /// Use `pins[n]` as the enable pin of device `n`
+    pub fn new(pins: [EN; N]) -> Self {
+        Self {
+            pins,
+            selected: None,
+        }
+    }
+
+    /// Give back the enable pins
+    pub fn release(self) -> [EN; N] {
+        self.pins
+    }
+}
+
+impl<EN, P, const N: usize> ChipSelect for GpioChipSelect<EN, N>
+where
+    EN: OutputPin<Error = P>,
+{
+    type Error = P;
+
+    fn devices(&self) -> usize {
+        N
+    }
+
+    /// Panics if `device` is not below `N`
+    fn assert(&mut self, device: usize) -> Result<(), P> {
+        self.pins[device].set_low()?;
+        self.selected = Some(device);
+        Ok(())
+    }
+
+    fn deassert(&mut self) -> Result<(), P> {
+        if let Some(device) = self.selected {
+            self.pins[device].set_high()?;
+            self.selected = None;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh0::pin::{Mock, State, Transaction};
+
+    #[test]
+    fn deassert_releases_the_asserted_pin() {
+        let mut first = Mock::new(&[]);
+        let mut second = Mock::new(&[Transaction::set(State::Low), Transaction::set(State::High)]);
+        let mut cs = GpioChipSelect::new([first.clone(), second.clone()]);
+        cs.deassert().unwrap();
+        cs.assert(1).unwrap();
+        cs.deassert().unwrap();
+        cs.deassert().unwrap();
+        first.done();
+        second.done();
+    }
+}
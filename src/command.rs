@@ -0,0 +1,188 @@
+//! Command word encoding shared by the blocking and async drivers.
+
+use crate::Channel;
+
+/// Largest code accepted by the 12-bit DAC registers
+pub(crate) const MAX_VALUE: u16 = 4095;
+
+/// Data write to the input register of `channel`, or `None` if `value` is out of bounds
+pub(crate) fn set_channel(channel: Channel, value: u16) -> Option<u16> {
+    if value > MAX_VALUE {
+        return None;
+    }
+    Some(set_channel_unchecked(channel, value))
+}
+
+/// Data write of a `bits` wide code, left-aligned in the data bits, or `None` if `code` doesn't fit
+pub(crate) fn set_code(channel: Channel, code: u16, bits: u32) -> Option<u16> {
+    if code >= 1 << bits {
+        return None;
+    }
+    Some(set_channel_unchecked(channel, code << (12 - bits)))
+}
+
+/// Data write to the input register of `channel` for a value already known to be in bounds
+pub(crate) fn set_channel_unchecked(channel: Channel, value: u16) -> u16 {
+    channel.as_u16() | value
+}
+
+/// Reset of all DAC data, optionally including the control data
+pub(crate) fn reset(full_reset: bool) -> u16 {
+    if full_reset {
+        0xf000
+    } else {
+        0xe000
+    }
+}
+
+/// Power-down of the channels set to true, Channel A -> 0, ..., Channel H -> 7
+pub(crate) fn power_down(channels: [bool; 8]) -> u16 {
+    let mut cmd = 0xc000;
+    for (n, &power_down) in channels.iter().enumerate() {
+        cmd |= u16::from(power_down) << n;
+    }
+    cmd
+}
+
+/// Split a command into the two bytes clocked out MSB first
+pub(crate) fn to_bytes(cmd: u16) -> [u8; 2] {
+    cmd.to_be_bytes()
+}
+
+/// A command word split into its fields. Group pairs are (A...D, E...H)
+#[cfg(any(feature = "log", feature = "sim"))]
+pub(crate) enum Decoded {
+    SetChannel {
+        channel: Channel,
+        value: u16,
+    },
+    Control {
+        gain: (bool, bool),
+        buf: (bool, bool),
+        vdd: (bool, bool),
+    },
+    /// LDAC mode bits, 0b11 is reserved
+    Ldac(u16),
+    /// Power-down mask, bit n for channel n
+    PowerDown(u8),
+    Reset {
+        full: bool,
+    },
+}
+
+#[cfg(any(feature = "log", feature = "sim"))]
+pub(crate) fn decode(cmd: u16) -> Decoded {
+    let bit = |n: u16| (cmd >> n) & 1 == 1;
+    match cmd >> 13 {
+        0..=3 => Decoded::SetChannel {
+            channel: Channel::from((cmd >> 12) as u8),
+            value: cmd & MAX_VALUE,
+        },
+        4 => Decoded::Control {
+            gain: (bit(4), bit(5)),
+            buf: (bit(2), bit(3)),
+            vdd: (bit(0), bit(1)),
+        },
+        5 => Decoded::Ldac(cmd & 0b11),
+        6 => Decoded::PowerDown(cmd as u8),
+        _ => Decoded::Reset { full: bit(12) },
+    }
+}
+
+/// Human readable decoding of a command word, used for logging
+#[cfg(feature = "log")]
+pub(crate) struct Describe(pub(crate) u16);
+
+#[cfg(feature = "log")]
+impl core::fmt::Display for Describe {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let pair = |(a, e): (bool, bool)| (u8::from(a), u8::from(e));
+        match decode(self.0) {
+            Decoded::SetChannel { channel, value } => write!(
+                f,
+                "set_channel channel={} value={}",
+                char::from(b'A' + u8::from(channel)),
+                value
+            )?,
+            Decoded::Control { gain, buf, vdd } => write!(
+                f,
+                "control gain={:?} buf={:?} vdd={:?}",
+                pair(gain),
+                pair(buf),
+                pair(vdd)
+            )?,
+            Decoded::Ldac(mode) => {
+                let mode = match mode {
+                    0 => "low",
+                    1 => "high",
+                    2 => "single update",
+                    _ => "reserved",
+                };
+                write!(f, "ldac mode={}", mode)?
+            }
+            Decoded::PowerDown(channels) => write!(f, "power_down channels={:#010b}", channels)?,
+            Decoded::Reset { full } => write!(f, "reset full={}", full)?,
+        }
+        write!(f, " word={:#06x}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_left_aligned_per_resolution() {
+        // AD5308: full scale in DB11...DB4
+        assert_eq!(set_code(Channel::A, 0xff, 8), Some(0x0ff0));
+        assert_eq!(set_code(Channel::B, 0x01, 8), Some(0x1010));
+        assert_eq!(set_code(Channel::H, 0x80, 8), Some(0x7800));
+        assert_eq!(set_code(Channel::A, 0x100, 8), None);
+        // AD5318: full scale in DB11...DB2
+        assert_eq!(set_code(Channel::A, 0x3ff, 10), Some(0x0ffc));
+        assert_eq!(set_code(Channel::C, 0x001, 10), Some(0x2004));
+        assert_eq!(set_code(Channel::H, 0x200, 10), Some(0x7800));
+        assert_eq!(set_code(Channel::A, 0x400, 10), None);
+        // AD5328: full scale in DB11...DB0
+        assert_eq!(set_code(Channel::A, 0xfff, 12), Some(0x0fff));
+        assert_eq!(set_code(Channel::D, 0x001, 12), Some(0x3001));
+        assert_eq!(set_code(Channel::A, 0x1000, 12), None);
+        for value in [0, 1, 0x7ff, MAX_VALUE, MAX_VALUE + 1] {
+            assert_eq!(
+                set_code(Channel::E, value, 12),
+                set_channel(Channel::E, value)
+            );
+        }
+    }
+}
+
+#[cfg(all(test, feature = "log"))]
+mod describe_tests {
+    use super::*;
+    use crate::Ad5328Config;
+
+    fn describe(cmd: u16) -> String {
+        Describe(cmd).to_string()
+    }
+
+    #[test]
+    fn describes_every_command_kind() {
+        assert_eq!(
+            describe(set_channel_unchecked(Channel::C, 0x123)),
+            "set_channel channel=C value=291 word=0x2123"
+        );
+        let [control, ldac] = Ad5328Config::default().as_commands();
+        assert_eq!(
+            describe(control),
+            "control gain=(0, 0) buf=(1, 1) vdd=(0, 0) word=0x800c"
+        );
+        assert_eq!(describe(ldac), "ldac mode=high word=0xa001");
+        let mut channels = [false; 8];
+        channels[1] = true;
+        assert_eq!(
+            describe(power_down(channels)),
+            "power_down channels=0b00000010 word=0xc002"
+        );
+        assert_eq!(describe(reset(true)), "reset full=true word=0xf000");
+    }
+}
@@ -0,0 +1,154 @@
+//! Dithering between adjacent codes for more than the part's resolution behind a slow RC filter.
+//!
+//! Each dithered channel has a 16-bit target, the code in fixed point with `16 - bits` fraction bits,
+//! e.g. `value / 16` for the AD5328. A first-order sigma-delta accumulates the fraction every tick and
+//! steps up to the next code whenever it overflows, so the average code over `2^(16 - bits)` ticks is the
+//! target, with the toggling spread over the period rather than bunched up as with PWM.
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, Transport};
+
+/// Sigma-delta state of a dithered channel
+#[derive(Clone, Copy)]
+pub(crate) struct Dither {
+    value: u16,
+    error: u16,
+    /// Code last written for the channel, `None` before the first tick
+    code: Option<u16>,
+}
+
+impl Dither {
+    /// Code of the next tick, the code just above the target only if the accumulated fraction overflows.
+    /// At the top of the range, where there is no code above, it stays at `max`
+    fn next(&mut self, bits: u32, max: u16) -> u16 {
+        let shift = 16 - bits;
+        let one = 1 << shift;
+        let code = self.value >> shift;
+        self.error += self.value & (one - 1);
+        if self.error < one {
+            return code;
+        }
+        self.error -= one;
+        (code + 1).min(max)
+    }
+}
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Dither `channel` around the 16-bit target `value`, the code with `16 - bits` fraction bits, e.g.
+    /// `value / 16` for the AD5328, writing its first code right away. Each [`dither_tick`](Self::dither_tick)
+    /// then writes the next code of a first-order sigma-delta, so the average over `2^(16 - bits)` ticks is the
+    /// target. The codes are only ever the ones just below and above the target, never past the full-scale
+    /// code, and pass through [`set_channel`](Self::set_channel). Other writes to the channel are overwritten by
+    /// the next tick until [`stop_dither`](Self::stop_dither)
+    pub fn set_channel_dithered(
+        &mut self,
+        channel: Channel,
+        value: u16,
+    ) -> Result<(), Error<S, P>> {
+        let error = self.pipeline.dither[channel].map_or(0, |dither| dither.error);
+        self.pipeline.dither[channel] = Some(Dither {
+            value,
+            error,
+            code: None,
+        });
+        self.dither_channel(channel)
+    }
+
+    /// Step every dithered channel, A first, writing a frame only for channels whose code changes
+    pub fn dither_tick(&mut self) -> Result<(), Error<S, P>> {
+        for n in 0..8 {
+            self.dither_channel(Channel::from(n))?;
+        }
+        Ok(())
+    }
+
+    /// Stop dithering `channel`, it keeps its last code
+    pub fn stop_dither(&mut self, channel: Channel) {
+        self.pipeline.dither[channel] = None;
+    }
+
+    fn dither_channel(&mut self, channel: Channel) -> Result<(), Error<S, P>> {
+        let Some(mut dither) = self.pipeline.dither[channel] else {
+            return Ok(());
+        };
+        let code = dither.next(self.resolution.bits(), self.resolution.max_code());
+        if dither.code != Some(code) {
+            self.set_channel(channel, code)?;
+            dither.code = Some(code);
+        }
+        self.pipeline.dither[channel] = Some(dither);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::Frames;
+    use crate::{resolution::Resolution, Ad5308, Ad5328, Ad5328Config, Ad53xx, Channel, NoEnable};
+
+    /// Average code of channel A over `ticks` ticks of dithering to `value`, and the largest code written
+    fn dither<R: Resolution>(
+        mut dac: Ad53xx<Frames, NoEnable, R>,
+        value: u16,
+        ticks: usize,
+    ) -> (f64, u16) {
+        let shift = 12 - dac.resolution.bits();
+        dac.set_channel_dithered(Channel::A, value).unwrap();
+        let (mut sum, mut max) = (0, 0);
+        for _ in 0..ticks {
+            let code = (dac.spi.frames.last().unwrap() & 0x0fff) >> shift;
+            sum += u64::from(code);
+            max = max.max(code);
+            dac.dither_tick().unwrap();
+        }
+        (sum as f64 / ticks as f64, max)
+    }
+
+    fn ad5328() -> Ad5328<Frames, NoEnable> {
+        Ad5328::init_without_enable(Frames::default(), Ad5328Config::default()).unwrap()
+    }
+
+    #[test]
+    fn average_code_hits_the_16_bit_target() {
+        for value in [0x0000, 0x0001, 0x7ff8, 0x1235, 0xabcd, 0xfff0] {
+            let (average, _) = dither(ad5328(), value, 16 * 64);
+            let target = f64::from(value) / 16.0;
+            assert!(
+                (average - target).abs() < 1.0 / 64.0,
+                "{value:#06x} averages {average}, not {target}"
+            );
+        }
+    }
+
+    #[test]
+    fn dithered_codes_stay_in_bounds() {
+        let (average, max) = dither(ad5328(), 0xffff, 1000);
+        assert_eq!(max, 4095);
+        assert_eq!(average, 4095.0);
+
+        let ad5308 = Ad5308::init_without_enable(Frames::default(), Ad5328Config::default());
+        let (average, max) = dither(ad5308.unwrap(), 0xff80, 1000);
+        assert_eq!(max, 255);
+        assert_eq!(average, 255.0);
+        let ad5308 = Ad5308::init_without_enable(Frames::default(), Ad5328Config::default());
+        assert_eq!(dither(ad5308.unwrap(), 0x4080, 1000).0, 64.5);
+    }
+
+    #[test]
+    fn only_changed_codes_are_written() {
+        let mut dac =
+            Ad5308::init_without_enable(Frames::default(), Ad5328Config::default()).unwrap();
+        dac.set_channel_dithered(Channel::B, 0x0140).unwrap();
+        for _ in 0..4 {
+            dac.dither_tick().unwrap();
+        }
+        dac.stop_dither(Channel::B);
+        dac.dither_tick().unwrap();
+        // 1.25: the fraction overflows every fourth tick
+        let (spi, _) = dac.release();
+        assert_eq!(spi.frames, [0x1010, 0x1020, 0x1010]);
+    }
+}
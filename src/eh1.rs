@@ -0,0 +1,775 @@
+//! Support for embedded-hal 1.0 buses, enabled with the `eh1` feature.
+
+use core::{cell::RefCell, convert::Infallible};
+
+use embedded_hal_1::{
+    delay::DelayNs,
+    digital::{self, OutputPin},
+    spi::{self, ErrorKind, SpiBus, SpiDevice},
+};
+
+use crate::{Ad5328Config, Ad53xx, Error, LdacPulse, NoLdac, Transport};
+
+/// Stands in for the enable pin when SYNC is driven by the chip select of an `SpiDevice`.
+/// Every frame is sent as its own SPI transaction, so the device asserts and releases SYNC around it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeviceCs;
+
+impl<SPI> Transport<DeviceCs> for SPI
+where
+    SPI: SpiDevice<u8>,
+{
+    type SpiError = SPI::Error;
+    type PinError = Infallible;
+
+    fn write_frame(
+        &mut self,
+        _enable: &mut DeviceCs,
+        frame: &[u8; 2],
+    ) -> Result<(), Error<SPI::Error, Infallible>> {
+        self.write(frame).map_err(Error::Spi)
+    }
+}
+
+impl<SPI, R> Ad53xx<SPI, DeviceCs, R>
+where
+    SPI: SpiDevice<u8>,
+    R: Default,
+{
+    /// Initialize a new Ad5328 instance on an embedded-hal 1.0 `SpiDevice`, while configuring it for the first time
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal 1.0 `SpiDevice` whose chip select is wired to SYNC
+    /// * `config` - The Ad5328 device configuration struct
+    pub fn init_device(
+        spi: SPI,
+        config: Ad5328Config,
+    ) -> Result<Self, Error<SPI::Error, Infallible>> {
+        Self::init(spi, DeviceCs, config)
+    }
+}
+
+/// embedded-hal 1.0 GPIO driving SYNC when the DAC sits on a bare `SpiBus`.
+/// The pin is asserted for each frame and only released once the bus has been flushed, or once it failed.
+#[derive(Debug)]
+pub struct SyncPin<P>(pub P);
+
+impl<BUS, P> Transport<SyncPin<P>> for BUS
+where
+    BUS: SpiBus<u8>,
+    P: OutputPin,
+{
+    type SpiError = BUS::Error;
+    type PinError = P::Error;
+
+    fn write_frame(
+        &mut self,
+        enable: &mut SyncPin<P>,
+        frame: &[u8; 2],
+    ) -> Result<(), Error<BUS::Error, P::Error>> {
+        enable.0.set_low().map_err(Error::Pin)?;
+        if let Err(e) = self.write(frame).and_then(|()| self.flush()) {
+            // the SPI error is the one worth reporting
+            let _ = enable.0.set_high();
+            return Err(Error::Spi(e));
+        }
+        enable.0.set_high().map_err(Error::Pin)?;
+        Ok(())
+    }
+}
+
+impl<BUS, P, R> Ad53xx<BUS, SyncPin<P>, R>
+where
+    BUS: SpiBus<u8>,
+    P: OutputPin,
+    R: Default,
+{
+    /// Initialize a new Ad5328 instance on an embedded-hal 1.0 `SpiBus`, while configuring it for the first time
+    /// # Arguments
+    ///
+    /// * `bus` - embedded-hal 1.0 `SpiBus`
+    /// * `sync_pin` - embedded-hal 1.0 GPIO pin wired to SYNC
+    /// * `config` - The Ad5328 device configuration struct
+    pub fn new_on_bus(
+        bus: BUS,
+        sync_pin: P,
+        config: Ad5328Config,
+    ) -> Result<Self, Error<BUS::Error, P::Error>> {
+        Self::init(bus, SyncPin(sync_pin), config)
+    }
+}
+
+/// Enable pin that keeps SYNC high for at least `sync_high_ns` after every frame, for slow SYNC lines
+/// that need a minimum high time between back-to-back frames.
+///
+/// The pause follows every release of SYNC, so it also separates the frames of multi-frame operations such as
+/// [`configure`](crate::Ad5328::configure). Wrap the pin before [`init`](crate::Ad5328::init) for the initial configuration to be
+/// covered too. This implements both the embedded-hal 0.2 and 1.0 `OutputPin`, so with an `SpiBus` it can be used
+/// as `SyncPin(FrameDelay::new(..))`.
+pub struct FrameDelay<EN, D> {
+    pin: EN,
+    delay: D,
+    sync_high_ns: u32,
+}
+
+impl<EN, D> FrameDelay<EN, D> {
+    /// Pause for `sync_high_ns` using `delay` after `pin` has been set high
+    pub fn new(pin: EN, delay: D, sync_high_ns: u32) -> Self {
+        Self {
+            pin,
+            delay,
+            sync_high_ns,
+        }
+    }
+
+    /// Give back the pin and delay provider
+    pub fn release(self) -> (EN, D) {
+        (self.pin, self.delay)
+    }
+}
+
+impl<EN, D> embedded_hal::digital::v2::OutputPin for FrameDelay<EN, D>
+where
+    EN: embedded_hal::digital::v2::OutputPin,
+    D: DelayNs,
+{
+    type Error = EN::Error;
+
+    fn set_low(&mut self) -> Result<(), EN::Error> {
+        self.pin.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), EN::Error> {
+        self.pin.set_high()?;
+        self.delay.delay_ns(self.sync_high_ns);
+        Ok(())
+    }
+}
+
+impl<EN: digital::ErrorType, D> digital::ErrorType for FrameDelay<EN, D> {
+    type Error = EN::Error;
+}
+
+impl<EN, D> OutputPin for FrameDelay<EN, D>
+where
+    EN: OutputPin,
+    D: DelayNs,
+{
+    fn set_low(&mut self) -> Result<(), EN::Error> {
+        self.pin.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), EN::Error> {
+        self.pin.set_high()?;
+        self.delay.delay_ns(self.sync_high_ns);
+        Ok(())
+    }
+}
+
+impl<SPI, EN, R, L> Ad53xx<SPI, EN, R, L> {
+    /// Keep SYNC high for at least `sync_high_ns` after every following frame, see [`FrameDelay`]
+    pub fn with_frame_delay<D: DelayNs>(
+        self,
+        delay: D,
+        sync_high_ns: u32,
+    ) -> Ad53xx<SPI, FrameDelay<EN, D>, R, L> {
+        Ad53xx {
+            spi: self.spi,
+            enable: FrameDelay::new(self.enable, delay, sync_high_ns),
+            cmd_buf: self.cmd_buf,
+            data: self.data,
+            powered_down: self.powered_down,
+            config: self.config,
+            staged: self.staged,
+            resolution: self.resolution,
+            ldac_pin: self.ldac_pin,
+            pipeline: self.pipeline,
+        }
+    }
+}
+
+/// Minimum LDAC pulse width low, in nanoseconds
+pub const LDAC_PULSE_NS: u32 = 20;
+
+/// embedded-hal 1.0 GPIO wired to LDAC, pulsed low for [`LDAC_PULSE_NS`] to latch all outputs at once.
+/// The pin is expected to idle high, and is set high again even if setting it low fails. Its error converts
+/// into the error of the enable pin, e.g. as both are `Infallible` or the same HAL error
+pub struct LdacPin<PIN, D> {
+    pin: PIN,
+    delay: D,
+}
+
+impl<PIN, D, P> LdacPulse<P> for LdacPin<PIN, D>
+where
+    PIN: OutputPin,
+    PIN::Error: Into<P>,
+    D: DelayNs,
+{
+    fn pulse(&mut self) -> Option<Result<(), P>> {
+        Some(pulse(&mut self.pin, &mut self.delay).map_err(Into::into))
+    }
+}
+
+/// Pulse `pin` low, setting it high again even if setting it low failed
+fn pulse<PIN: OutputPin, D: DelayNs>(pin: &mut PIN, delay: &mut D) -> Result<(), PIN::Error> {
+    let low = pin.set_low();
+    if low.is_ok() {
+        delay.delay_ns(LDAC_PULSE_NS);
+    }
+    let high = pin.set_high();
+    low.and(high)
+}
+
+/// One LDAC line shared by several DACs, so all their outputs update on the same edge.
+/// Each driver references it through [`with_ldac_bus`](crate::Ad5328::with_ldac_bus), or just stages its
+/// channels and leaves the pulse to [`latch_all`](Self::latch_all).
+///
+/// A [`commit`](crate::Ad5328::commit) of one driver latches the others on the line too, and `latch_all` all of
+/// them, so the drivers that did not pulse the line are told with [`mark_latched`](crate::Ad5328::mark_latched)
+/// to clear their [`staged`](crate::Ad5328::staged) channels.
+///
+/// ```
+/// # use ad5328::{eh1::LdacBus, Ad5328, Ad5328Config, Channel};
+/// # use embedded_hal_mock::{eh0::spi, eh1::{delay::NoopDelay, pin}};
+/// # let mock = |value: u8| spi::Mock::new(&[
+/// #     spi::Transaction::write(vec![0x80, 0x0c]),
+/// #     spi::Transaction::write(vec![0xa0, 0x01]),
+/// #     spi::Transaction::write(vec![0x00, value]),
+/// # ]);
+/// # let (spi_1, spi_2) = (mock(1), mock(2));
+/// # let pin = pin::Mock::new(&[
+/// #     pin::Transaction::set(pin::State::Low),
+/// #     pin::Transaction::set(pin::State::High),
+/// # ]);
+/// # let (mut spi_1_check, mut spi_2_check, mut pin_check) = (spi_1.clone(), spi_2.clone(), pin.clone());
+/// let ldac = LdacBus::new(pin, NoopDelay::new());
+/// let mut dac_1 = Ad5328::init_without_enable(spi_1, Ad5328Config::default()).unwrap();
+/// let mut dac_2 = Ad5328::init_without_enable(spi_2, Ad5328Config::default()).unwrap();
+/// dac_1.stage_channel(Channel::A, 1).unwrap();
+/// dac_2.stage_channel(Channel::A, 2).unwrap();
+/// ldac.latch_all().unwrap();
+/// dac_1.mark_latched();
+/// dac_2.mark_latched();
+/// # spi_1_check.done();
+/// # spi_2_check.done();
+/// # pin_check.done();
+/// ```
+pub struct LdacBus<PIN, D> {
+    line: RefCell<(PIN, D)>,
+}
+
+impl<PIN, D> LdacBus<PIN, D> {
+    /// Own `pin`, which should already be high, and `delay` for the pulse width
+    pub fn new(pin: PIN, delay: D) -> Self {
+        Self {
+            line: RefCell::new((pin, delay)),
+        }
+    }
+
+    /// Give back the pin and delay provider
+    pub fn release(self) -> (PIN, D) {
+        self.line.into_inner()
+    }
+}
+
+impl<PIN: OutputPin, D: DelayNs> LdacBus<PIN, D> {
+    /// Pulse LDAC, transferring the input registers of every DAC on the line to their DAC registers
+    pub fn latch_all(&self) -> Result<(), PIN::Error> {
+        let (pin, delay) = &mut *self.line.borrow_mut();
+        pulse(pin, delay)
+    }
+}
+
+impl<PIN, D, P> LdacPulse<P> for &LdacBus<PIN, D>
+where
+    PIN: OutputPin,
+    PIN::Error: Into<P>,
+    D: DelayNs,
+{
+    fn pulse(&mut self) -> Option<Result<(), P>> {
+        Some(self.latch_all().map_err(Into::into))
+    }
+}
+
+impl<SPI, EN, R> Ad53xx<SPI, EN, R> {
+    /// Pulse `pin` on LDAC instead of writing the LDAC command in [`commit`](crate::Ad5328::commit) and
+    /// [`set_channels_synced`](crate::Ad5328::set_channels_synced), using `delay` for the pulse width.
+    /// The pin should already be high, the LDAC mode bits are left to the configuration.
+    /// A pin that cannot be driven is reported as [`Error::LdacPin`] with its error
+    pub fn with_ldac_pin<PIN: OutputPin, D: DelayNs>(
+        self,
+        pin: PIN,
+        delay: D,
+    ) -> Ad53xx<SPI, EN, R, LdacPin<PIN, D>> {
+        self.with_ldac(LdacPin { pin, delay })
+    }
+
+    /// Pulse the shared LDAC line of `bus` instead of writing the LDAC command, see [`with_ldac_pin`](Self::with_ldac_pin)
+    pub fn with_ldac_bus<PIN: OutputPin, D: DelayNs>(
+        self,
+        bus: &LdacBus<PIN, D>,
+    ) -> Ad53xx<SPI, EN, R, &LdacBus<PIN, D>> {
+        self.with_ldac(bus)
+    }
+}
+
+impl<SPI, EN, R, PIN, D> Ad53xx<SPI, EN, R, LdacPin<PIN, D>> {
+    /// Take back the LDAC pin and delay, continuing with the LDAC command
+    pub fn without_ldac_pin(self) -> (Ad53xx<SPI, EN, R>, PIN, D) {
+        let LdacPin { pin, delay } = self.ldac_pin;
+        let dac = Ad53xx {
+            spi: self.spi,
+            enable: self.enable,
+            cmd_buf: self.cmd_buf,
+            data: self.data,
+            powered_down: self.powered_down,
+            config: self.config,
+            staged: self.staged,
+            resolution: self.resolution,
+            ldac_pin: NoLdac,
+            pipeline: self.pipeline,
+        };
+        (dac, pin, delay)
+    }
+}
+
+impl<SPI, EN, R, PIN, D> Ad53xx<SPI, EN, R, &LdacBus<PIN, D>> {
+    /// Stop using the shared LDAC line, continuing with the LDAC command
+    pub fn without_ldac_bus(self) -> Ad53xx<SPI, EN, R> {
+        self.with_ldac(NoLdac)
+    }
+}
+
+impl<SPI, EN, R, PIN, D, S, P> Ad53xx<SPI, EN, R, LdacPin<PIN, D>>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    PIN: OutputPin,
+    PIN::Error: Into<P>,
+    D: DelayNs,
+{
+    /// Pulse the LDAC pin, transferring all input registers to the DAC registers.
+    /// With the LDAC mode bits at [`LDAC::LdacLow`](crate::LDAC::LdacLow) the outputs follow the input registers anyway
+    pub fn pulse_ldac(&mut self) -> Result<(), Error<S, P>> {
+        self.latch()
+    }
+}
+
+impl<P: digital::ErrorType> digital::ErrorType for crate::ActiveHigh<P> {
+    type Error = P::Error;
+}
+
+/// With an `SpiBus`, an inverted SYNC pin is used as `SyncPin(ActiveHigh(pin))`
+impl<P: OutputPin> OutputPin for crate::ActiveHigh<P> {
+    fn set_low(&mut self) -> Result<(), P::Error> {
+        self.0.set_high()
+    }
+
+    fn set_high(&mut self) -> Result<(), P::Error> {
+        self.0.set_low()
+    }
+}
+
+/// Lets generic embedded-hal 1.0 code classify driver errors: SPI errors keep their own kind,
+/// a failing enable pin is a chip select fault and everything detected by the driver itself is `Other`
+impl<S, P> spi::Error for Error<S, P>
+where
+    S: spi::Error,
+    P: core::fmt::Debug,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Spi(e) => e.kind(),
+            Error::Pin(_) => ErrorKind::ChipSelectFault,
+            Error::Conn
+            | Error::Address
+            | Error::Port
+            | Error::Oob
+            | Error::OobMax(_)
+            | Error::Ldac
+            | Error::LdacPin(_)
+            | Error::Lut
+            | Error::Limited(_)
+            | Error::ChannelMap
+            | Error::Mirror
+            | Error::QueueFull => ErrorKind::Other,
+        }
+    }
+}
+
+/// The driver error as PWM error, for per-channel outputs used through `SetDutyCycle`
+impl<S, P> embedded_hal_1::pwm::Error for Error<S, P>
+where
+    S: core::fmt::Debug,
+    P: core::fmt::Debug,
+{
+    fn kind(&self) -> embedded_hal_1::pwm::ErrorKind {
+        embedded_hal_1::pwm::ErrorKind::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Event, Log, LogPin};
+    use crate::Ad5328;
+    use crate::Channel;
+    use embedded_hal_mock::eh1::{
+        pin,
+        spi::{Mock, Transaction},
+    };
+
+    fn frame(bytes: [u8; 2]) -> [Transaction<u8>; 3] {
+        [
+            Transaction::transaction_start(),
+            Transaction::write_vec(bytes.to_vec()),
+            Transaction::transaction_end(),
+        ]
+    }
+
+    #[test]
+    fn set_channel_writes_one_transaction_per_frame() {
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01], [0x21, 0x23]]
+            .into_iter()
+            .flat_map(frame)
+            .collect();
+        let mut spi = Mock::new(&expectations);
+        let mut dac = Ad5328::init_device(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::C, 0x123).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn reset_and_power_down_use_the_device() {
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01], [0xf0, 0x00], [0xc0, 0x81]]
+            .into_iter()
+            .flat_map(frame)
+            .collect();
+        let mut spi = Mock::new(&expectations);
+        let mut dac = Ad5328::init_device(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.reset(true).unwrap();
+        dac.power_down([true, false, false, false, false, false, false, true])
+            .unwrap();
+        spi.done();
+    }
+
+    fn bus_frame(bytes: [u8; 2]) -> [Transaction<u8>; 2] {
+        [Transaction::write_vec(bytes.to_vec()), Transaction::flush()]
+    }
+
+    fn sync_toggles(frames: usize) -> pin::Mock {
+        let toggle = [
+            pin::Transaction::set(pin::State::Low),
+            pin::Transaction::set(pin::State::High),
+        ];
+        pin::Mock::new(toggle.iter().cycle().take(frames * 2))
+    }
+
+    #[test]
+    fn bus_toggles_sync_once_per_frame() {
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01], [0x21, 0x23], [0xe0, 0x00]]
+            .into_iter()
+            .flat_map(bus_frame)
+            .collect();
+        let mut bus = Mock::new(&expectations);
+        let mut sync = sync_toggles(4);
+        let mut dac =
+            Ad5328::new_on_bus(bus.clone(), sync.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::C, 0x123).unwrap();
+        dac.reset(false).unwrap();
+        bus.done();
+        sync.done();
+    }
+
+    #[test]
+    fn bus_configure_frames_are_separately_synced() {
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01], [0x80, 0x3f], [0xa0, 0x00]]
+            .into_iter()
+            .flat_map(bus_frame)
+            .collect();
+        let mut bus = Mock::new(&expectations);
+        let mut sync = sync_toggles(4);
+        let mut dac =
+            Ad5328::new_on_bus(bus.clone(), sync.clone(), Ad5328Config::default()).unwrap();
+        dac.configure(Ad5328Config {
+            gain: (crate::GAIN::Gain02Vref, crate::GAIN::Gain02Vref),
+            vdd: (crate::VDD::VddAsRef, crate::VDD::VddAsRef),
+            ldac: crate::LDAC::LdacLow,
+            ..Default::default()
+        })
+        .unwrap();
+        bus.done();
+        sync.done();
+    }
+
+    /// Bus failing every write or, if `flushes` is set, every flush
+    struct BrokenBus {
+        flushes: bool,
+    }
+
+    impl spi::ErrorType for BrokenBus {
+        type Error = ErrorKind;
+    }
+
+    impl SpiBus<u8> for BrokenBus {
+        fn read(&mut self, _: &mut [u8]) -> Result<(), ErrorKind> {
+            Err(ErrorKind::Other)
+        }
+
+        fn write(&mut self, _: &[u8]) -> Result<(), ErrorKind> {
+            if self.flushes {
+                Ok(())
+            } else {
+                Err(ErrorKind::Overrun)
+            }
+        }
+
+        fn transfer(&mut self, _: &mut [u8], _: &[u8]) -> Result<(), ErrorKind> {
+            Err(ErrorKind::Other)
+        }
+
+        fn transfer_in_place(&mut self, _: &mut [u8]) -> Result<(), ErrorKind> {
+            Err(ErrorKind::Other)
+        }
+
+        fn flush(&mut self) -> Result<(), ErrorKind> {
+            if self.flushes {
+                Err(ErrorKind::ModeFault)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn sync_pin_is_released_when_the_bus_fails() {
+        use embedded_hal_mock::eh1::MockError;
+
+        let levels = |levels: [pin::State; 2]| levels.map(pin::Transaction::set);
+
+        let mut sync = pin::Mock::new(&levels([pin::State::Low, pin::State::High]));
+        let result = Ad5328::new_on_bus(
+            BrokenBus { flushes: false },
+            sync.clone(),
+            Ad5328Config::default(),
+        );
+        assert!(matches!(result, Err(Error::Spi(ErrorKind::Overrun))));
+        sync.done();
+
+        let mut sync = pin::Mock::new(&levels([pin::State::High, pin::State::Low]));
+        let result = Ad5328::new_on_bus(
+            BrokenBus { flushes: true },
+            crate::ActiveHigh(sync.clone()),
+            Ad5328Config::default(),
+        );
+        assert!(matches!(result, Err(Error::Spi(ErrorKind::ModeFault))));
+        sync.done();
+
+        // the bus error is reported over a pin that fails to release SYNC as well
+        let mut sync = pin::Mock::new(&[
+            pin::Transaction::set(pin::State::Low),
+            pin::Transaction::set(pin::State::High)
+                .with_error(MockError::Io(std::io::ErrorKind::Other)),
+        ]);
+        let result = Ad5328::new_on_bus(
+            BrokenBus { flushes: false },
+            sync.clone(),
+            Ad5328Config::default(),
+        );
+        assert!(matches!(result, Err(Error::Spi(ErrorKind::Overrun))));
+        sync.done();
+    }
+
+    #[test]
+    fn error_kinds_follow_the_variant() {
+        use spi::Error as _;
+
+        type E = Error<ErrorKind, ()>;
+        assert_eq!(E::Spi(ErrorKind::Overrun).kind(), ErrorKind::Overrun);
+        assert_eq!(E::Spi(ErrorKind::ModeFault).kind(), ErrorKind::ModeFault);
+        assert_eq!(E::Pin(()).kind(), ErrorKind::ChipSelectFault);
+        assert_eq!(E::Conn.kind(), ErrorKind::Other);
+        assert_eq!(E::Address.kind(), ErrorKind::Other);
+        assert_eq!(E::Port.kind(), ErrorKind::Other);
+        assert_eq!(E::Oob.kind(), ErrorKind::Other);
+        assert_eq!(E::OobMax(4095).kind(), ErrorKind::Other);
+        assert_eq!(E::Ldac.kind(), ErrorKind::Other);
+        assert_eq!(E::LdacPin(()).kind(), ErrorKind::Other);
+        assert_eq!(E::Lut.kind(), ErrorKind::Other);
+        assert_eq!(E::Limited(100).kind(), ErrorKind::Other);
+        assert_eq!(E::ChannelMap.kind(), ErrorKind::Other);
+        assert_eq!(E::Mirror.kind(), ErrorKind::Other);
+        assert_eq!(E::QueueFull.kind(), ErrorKind::Other);
+    }
+
+    struct LogDelay(Log);
+
+    impl DelayNs for LogDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.push(Event::Delay(ns));
+        }
+    }
+
+    #[test]
+    fn frame_delay_separates_configure_frames() {
+        let mut spi = embedded_hal_mock::eh0::spi::Mock::new(&[
+            embedded_hal_mock::eh0::spi::Transaction::write(vec![0x80, 0x0c]),
+            embedded_hal_mock::eh0::spi::Transaction::write(vec![0xa0, 0x01]),
+        ]);
+        let log = Log::default();
+        let enable = FrameDelay::new(LogPin(log.clone()), LogDelay(log.clone()), 40);
+        Ad5328::init(spi.clone(), enable, Ad5328Config::default()).unwrap();
+        assert_eq!(
+            *log.events(),
+            [
+                Event::Sync(false),
+                Event::Sync(true),
+                Event::Delay(40),
+                Event::Sync(false),
+                Event::Sync(true),
+                Event::Delay(40),
+            ]
+        );
+        spi.done();
+    }
+
+    /// LDAC pin that logs its levels and fails to go low with the error it is `stuck` with, if any
+    struct LogLdac<E> {
+        log: Log,
+        stuck: Option<E>,
+    }
+
+    impl<E: digital::Error> digital::ErrorType for LogLdac<E> {
+        type Error = E;
+    }
+
+    impl<E: digital::Error + Clone> OutputPin for LogLdac<E> {
+        fn set_low(&mut self) -> Result<(), E> {
+            if let Some(error) = &self.stuck {
+                return Err(error.clone());
+            }
+            self.log.push(Event::Ldac(false));
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), E> {
+            self.log.push(Event::Ldac(true));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn ldac_pin_replaces_the_ldac_command() {
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01], [0x00, 0x10], [0x10, 0x20]]
+            .into_iter()
+            .flat_map(bus_frame)
+            .collect();
+        let mut bus = Mock::new(&expectations);
+        let mut sync = sync_toggles(4);
+        let log = Log::default();
+        let ldac = LogLdac::<embedded_hal_mock::eh1::MockError> {
+            log: log.clone(),
+            stuck: None,
+        };
+        let mut dac = Ad5328::new_on_bus(bus.clone(), sync.clone(), Ad5328Config::default())
+            .unwrap()
+            .with_ldac_pin(ldac, LogDelay(log.clone()));
+        dac.set_channels_synced(&[(Channel::A, 0x10), (Channel::B, 0x20)])
+            .unwrap();
+        dac.pulse_ldac().unwrap();
+        let pulse = [
+            Event::Ldac(false),
+            Event::Delay(LDAC_PULSE_NS),
+            Event::Ldac(true),
+        ];
+        assert_eq!(*log.events(), [pulse.clone(), pulse].concat());
+        bus.done();
+        sync.done();
+    }
+
+    #[test]
+    fn ldac_pin_is_left_high_when_the_pulse_fails() {
+        use embedded_hal_mock::eh1::MockError;
+
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01], [0x30, 0x40]]
+            .into_iter()
+            .flat_map(bus_frame)
+            .collect();
+        let mut bus = Mock::new(&expectations);
+        let mut sync = sync_toggles(3);
+        let log = Log::default();
+        let stuck = MockError::Io(std::io::ErrorKind::TimedOut);
+        let ldac = LogLdac {
+            log: log.clone(),
+            stuck: Some(stuck.clone()),
+        };
+        let mut dac = Ad5328::new_on_bus(bus.clone(), sync.clone(), Ad5328Config::default())
+            .unwrap()
+            .with_ldac_pin(ldac, LogDelay(log.clone()));
+        assert!(matches!(
+            dac.stage_channel(Channel::D, 0x40)
+                .and_then(|_| dac.commit()),
+            Err(Error::LdacPin(error)) if error == stuck
+        ));
+        assert_eq!(*log.events(), [Event::Ldac(true)]);
+        assert!(dac.staged()[3]);
+        bus.done();
+        sync.done();
+    }
+
+    #[test]
+    fn ldac_bus_latches_every_dac_on_the_line() {
+        use embedded_hal_mock::eh0::spi;
+
+        let mock = |frames: &[[u8; 2]]| {
+            let expectations: Vec<_> = frames
+                .iter()
+                .map(|frame| spi::Transaction::write(frame.to_vec()))
+                .collect();
+            spi::Mock::new(&expectations)
+        };
+        let mut spi_1 = mock(&[[0x80, 0x0c], [0xa0, 0x01], [0x01, 0x00]]);
+        let mut spi_2 = mock(&[
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            [0x72, 0x00],
+            // reapplied
+            [0x80, 0x0c],
+            [0xa0, 0x01],
+            [0xc0, 0x00],
+            [0x72, 0x00],
+        ]);
+        let log = Log::default();
+        let bus = LdacBus::new(
+            LogLdac::<Infallible> {
+                log: log.clone(),
+                stuck: None,
+            },
+            LogDelay(log.clone()),
+        );
+        let mut dac_1 = Ad5328::init_without_enable(spi_1.clone(), Ad5328Config::default())
+            .unwrap()
+            .with_ldac_bus(&bus);
+        let mut dac_2 = Ad5328::init_without_enable(spi_2.clone(), Ad5328Config::default())
+            .unwrap()
+            .with_ldac_bus(&bus);
+        dac_1.stage_channel(Channel::A, 0x100).unwrap();
+        dac_2.stage_channel(Channel::H, 0x200).unwrap();
+        bus.latch_all().unwrap();
+        assert!(dac_2.staged()[7]);
+        dac_1.mark_latched();
+        dac_2.mark_latched();
+        assert_eq!(dac_1.staged(), [false; 8]);
+        assert_eq!(dac_2.staged(), [false; 8]);
+        // nothing left to commit, and the state can be reapplied with an update on the line
+        dac_2.commit().unwrap();
+        dac_2.reapply_state().unwrap();
+        let pulse = [
+            Event::Ldac(false),
+            Event::Delay(LDAC_PULSE_NS),
+            Event::Ldac(true),
+        ];
+        assert_eq!(*log.events(), [pulse.clone(), pulse].concat());
+        spi_1.done();
+        spi_2.done();
+    }
+}
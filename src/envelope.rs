@@ -0,0 +1,246 @@
+//! Gate-triggered ADSR envelopes in fixed point, one code per tick to feed [`Ad53xx::set_channel`].
+//!
+//! Every segment takes exactly its number of ticks and follows `1 - (1 - t)^3` from where it starts to
+//! its target, computed by repeated multiplication: fast at first and easing into the target, as the
+//! exponential segments of an analog envelope do.
+//!
+//! [`Ad53xx::set_channel`]: crate::Ad53xx::set_channel
+
+/// Times in ticks and sustain level of an [`Envelope`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Adsr {
+    /// Ticks to rise to the max code after the gate goes on
+    pub attack: u32,
+    /// Ticks to fall from the max code to the sustain level
+    pub decay: u32,
+    /// Code held while the gate stays on, saturated at the max code
+    pub sustain: u16,
+    /// Ticks to fall to 0 after the gate goes off
+    pub release: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// ADSR envelope generator, advanced by [`tick`](Self::tick)
+#[derive(Debug, Clone, Copy)]
+pub struct Envelope {
+    adsr: Adsr,
+    max: u16,
+    stage: Stage,
+    /// Level the current segment started from
+    from: u16,
+    /// Ticks into the current segment
+    elapsed: u32,
+    level: u16,
+    /// Code last returned by [`tick`](Self::tick)
+    last: Option<u16>,
+}
+
+impl Envelope {
+    /// Idle envelope of `adsr` for codes up to `max`, e.g. 4095 for the AD5328
+    pub fn new(adsr: Adsr, max: u16) -> Self {
+        Self {
+            adsr,
+            max,
+            stage: Stage::Idle,
+            from: 0,
+            elapsed: 0,
+            level: 0,
+            last: None,
+        }
+    }
+
+    /// Change the times and sustain level, a segment under way keeps going from where it is
+    pub fn set_adsr(&mut self, adsr: Adsr) {
+        self.adsr = adsr;
+    }
+
+    /// The times and sustain level
+    pub fn adsr(&self) -> Adsr {
+        self.adsr
+    }
+
+    /// Start the attack from the current level, so retriggering before the release ends doesn't jump to 0
+    pub fn gate_on(&mut self) {
+        self.start(Stage::Attack);
+    }
+
+    /// Start the release from the current level, unless the envelope is idle
+    pub fn gate_off(&mut self) {
+        if self.stage != Stage::Idle {
+            self.start(Stage::Release);
+        }
+    }
+
+    /// Whether the envelope is at rest, before the first gate or after its release
+    pub fn is_idle(&self) -> bool {
+        self.stage == Stage::Idle
+    }
+
+    /// The current code
+    pub fn level(&self) -> u16 {
+        self.level
+    }
+
+    /// Advance by one tick, returning the new code if it differs from the one returned last, always on the
+    /// first tick. Segments of 0 ticks are skipped within the tick
+    pub fn tick(&mut self) -> Option<u16> {
+        loop {
+            let (ticks, target, next) = match self.stage {
+                Stage::Idle => break,
+                Stage::Sustain => {
+                    self.level = self.adsr.sustain.min(self.max);
+                    break;
+                }
+                Stage::Attack => (self.adsr.attack, self.max, Stage::Decay),
+                Stage::Decay => (
+                    self.adsr.decay,
+                    self.adsr.sustain.min(self.max),
+                    Stage::Sustain,
+                ),
+                Stage::Release => (self.adsr.release, 0, Stage::Idle),
+            };
+            if self.elapsed >= ticks {
+                self.level = target;
+                self.start(next);
+                continue;
+            }
+            self.elapsed += 1;
+            self.level = segment(self.from, target, self.elapsed, ticks);
+            if self.elapsed == ticks {
+                self.start(next);
+            }
+            break;
+        }
+        if self.last == Some(self.level) {
+            return None;
+        }
+        self.last = Some(self.level);
+        self.last
+    }
+
+    fn start(&mut self, stage: Stage) {
+        self.stage = stage;
+        self.from = self.level;
+        self.elapsed = 0;
+    }
+}
+
+/// Level `elapsed` ticks into a segment of `ticks` from `from` to `target`, exactly `target` at the end
+fn segment(from: u16, target: u16, elapsed: u32, ticks: u32) -> u16 {
+    // remaining fraction of the segment in Q16, cubed for the curve
+    let remaining = (u64::from(ticks - elapsed) << 16) / u64::from(ticks);
+    let cube = (((remaining * remaining) >> 16) * remaining) >> 16;
+    let curve = (1 << 16) - cube as i64;
+    let delta = i64::from(target) - i64::from(from);
+    (i64::from(from) + ((delta * curve + (1 << 15)) >> 16)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADSR: Adsr = Adsr {
+        attack: 4,
+        decay: 6,
+        sustain: 1000,
+        release: 8,
+    };
+
+    /// Codes of `ticks` ticks, `None` where unchanged
+    fn run(envelope: &mut Envelope, ticks: usize) -> Vec<Option<u16>> {
+        (0..ticks).map(|_| envelope.tick()).collect()
+    }
+
+    fn changed(codes: &[Option<u16>]) -> Vec<u16> {
+        codes.iter().flatten().copied().collect()
+    }
+
+    #[test]
+    fn segments_take_their_ticks_and_are_monotonic() {
+        let mut envelope = Envelope::new(ADSR, 4095);
+        assert_eq!(run(&mut envelope, 2), [Some(0), None]);
+
+        envelope.gate_on();
+        let attack = changed(&run(&mut envelope, 4));
+        assert_eq!(attack.len(), 4);
+        assert_eq!(attack.last(), Some(&4095));
+        assert!(attack.windows(2).all(|pair| pair[0] < pair[1]));
+        // easing into the target
+        assert!(attack[0] > 4095 / 4);
+
+        let decay = changed(&run(&mut envelope, 6));
+        assert_eq!(decay.len(), 6);
+        assert_eq!(decay.last(), Some(&1000));
+        assert!(decay.windows(2).all(|pair| pair[0] > pair[1]));
+        assert_eq!(run(&mut envelope, 100), [None; 100]);
+
+        envelope.gate_off();
+        let release = run(&mut envelope, 8);
+        assert!(release.iter().all(Option::is_some));
+        let release = changed(&release);
+        assert_eq!(release.last(), Some(&0));
+        assert!(release.windows(2).all(|pair| pair[0] > pair[1]));
+        assert!(envelope.is_idle());
+        assert_eq!(envelope.tick(), None);
+    }
+
+    #[test]
+    fn zero_ticks_are_skipped() {
+        let mut envelope = Envelope::new(Adsr::default(), 4095);
+        envelope.gate_on();
+        // straight through attack and decay to a sustain of 0
+        assert_eq!(envelope.tick(), Some(0));
+        envelope.set_adsr(Adsr {
+            sustain: 3000,
+            ..Adsr::default()
+        });
+        envelope.gate_on();
+        assert_eq!(envelope.tick(), Some(3000));
+        envelope.gate_off();
+        assert_eq!(envelope.tick(), Some(0));
+        assert!(envelope.is_idle());
+
+        let mut envelope = Envelope::new(
+            Adsr {
+                sustain: 5000,
+                decay: 2,
+                ..Adsr::default()
+            },
+            255,
+        );
+        envelope.gate_on();
+        assert_eq!(changed(&run(&mut envelope, 4)), [255]);
+    }
+
+    #[test]
+    fn retrigger_attacks_from_the_current_level() {
+        let mut envelope = Envelope::new(ADSR, 4095);
+        envelope.gate_on();
+        run(&mut envelope, 20);
+        envelope.gate_off();
+        run(&mut envelope, 2);
+        let level = envelope.level();
+        assert!(level > 0 && level < 1000);
+
+        envelope.gate_on();
+        let attack = changed(&run(&mut envelope, 4));
+        assert!(attack[0] > level);
+        assert_eq!(attack, {
+            let mut fresh = Envelope::new(ADSR, 4095);
+            fresh.level = level;
+            fresh.gate_on();
+            changed(&run(&mut fresh, 4))
+        });
+        assert_eq!(attack.last(), Some(&4095));
+    }
+}
@@ -0,0 +1,69 @@
+//! C interface to the command encoding, enabled with the `ffi` feature.
+//!
+//! Only value-level encoding crosses the boundary: every function returns the 16-bit command word,
+//! which the caller clocks out MSB first with SYNC held low, or a negative error code.
+//! The matching header is `include/ad5328.h`, generated with `cbindgen`.
+
+use crate::{command, Ad5328Config, Channel, BUF, GAIN, LDAC, VDD};
+
+/// Channel index is not in 0..=7
+pub const AD5328_ERR_CHANNEL: i32 = -1;
+/// Channel value is over 4095
+pub const AD5328_ERR_VALUE: i32 = -2;
+/// LDAC mode is not in 0..=2
+pub const AD5328_ERR_LDAC_MODE: i32 = -3;
+
+/// Data write of `value` to `channel` (0 for A, ..., 7 for H)
+#[no_mangle]
+pub extern "C" fn ad5328_encode_set_channel(channel: u8, value: u16) -> i32 {
+    if channel > 7 {
+        return AD5328_ERR_CHANNEL;
+    }
+    command::set_channel(Channel::from(channel), value).map_or(AD5328_ERR_VALUE, i32::from)
+}
+
+/// Control word with the GAIN, BUF and VDD bits of channels A...D and E...H
+#[no_mangle]
+pub extern "C" fn ad5328_encode_config(
+    gain_ad: bool,
+    gain_eh: bool,
+    buf_ad: bool,
+    buf_eh: bool,
+    vdd_ad: bool,
+    vdd_eh: bool,
+) -> u16 {
+    let gain = |b| if b { GAIN::Gain02Vref } else { GAIN::Gain0Vref };
+    let buf = |b| if b { BUF::Buffered } else { BUF::Unbuffered };
+    let vdd = |b| if b { VDD::VddAsRef } else { VDD::ExternalRef };
+    let config = Ad5328Config {
+        gain: (gain(gain_ad), gain(gain_eh)),
+        buf: (buf(buf_ad), buf(buf_eh)),
+        vdd: (vdd(vdd_ad), vdd(vdd_eh)),
+        ldac: LDAC::LdacHigh,
+    };
+    config.as_commands()[0]
+}
+
+/// LDAC mode word, 0 for LDAC low, 1 for LDAC high and 2 for a single update
+#[no_mangle]
+pub extern "C" fn ad5328_encode_ldac(mode: u8) -> i32 {
+    let ldac = match mode {
+        0 => LDAC::LdacLow,
+        1 => LDAC::LdacHigh,
+        2 => LDAC::LdacSingleUpdate,
+        _ => return AD5328_ERR_LDAC_MODE,
+    };
+    i32::from(ldac.as_u16())
+}
+
+/// Power-down of the channels whose bit is set, bit 0 for channel A
+#[no_mangle]
+pub extern "C" fn ad5328_encode_power_down(channels: u8) -> u16 {
+    command::power_down(core::array::from_fn(|n| channels & (1 << n) != 0))
+}
+
+/// Reset of all DAC data, including the control data if `full_reset` is set
+#[no_mangle]
+pub extern "C" fn ad5328_encode_reset(full_reset: bool) -> u16 {
+    command::reset(full_reset)
+}
@@ -0,0 +1,180 @@
+//! Encoding of commands into raw frame bytes, for callers that move the data themselves (e.g. via DMA).
+//!
+//! The bytes are exactly what [`Ad5328`](crate::Ad5328) clocks out: one 16-bit frame per command, MSB first.
+//! In standalone mode SYNC still has to be toggled around every two bytes.
+
+use crate::{command, Ad5328Config, Channel};
+
+/// Errors while encoding frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EncodeError {
+    /// The output buffer can't hold all encoded frames
+    BufferTooSmall,
+    /// Out of bounds channel value
+    Oob,
+}
+
+/// A single operation as issued through the driver
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Op {
+    /// See [`Ad5328::set_channel`](crate::Ad5328::set_channel)
+    SetChannel(Channel, u16),
+    /// See [`Ad5328::configure`](crate::Ad5328::configure), encodes to two frames
+    Configure(Ad5328Config),
+    /// See [`Ad5328::power_down`](crate::Ad5328::power_down)
+    PowerDown([bool; 8]),
+    /// See [`Ad5328::reset`](crate::Ad5328::reset), `true` for a full reset
+    Reset(bool),
+}
+
+impl Op {
+    /// Number of bytes this operation encodes to
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Op::Configure(_) => 4,
+            _ => 2,
+        }
+    }
+}
+
+/// Encode a channel value write
+pub fn encode_set_channel(
+    channel: Channel,
+    value: u16,
+    buf: &mut [u8; 2],
+) -> Result<(), EncodeError> {
+    let cmd = command::set_channel(channel, value).ok_or(EncodeError::Oob)?;
+    *buf = command::to_bytes(cmd);
+    Ok(())
+}
+
+/// Encode both configuration frames
+pub fn encode_config(config: &Ad5328Config, buf: &mut [u8; 4]) {
+    let [gain_buf_vdd, ldac] = config.as_commands();
+    buf[..2].copy_from_slice(&command::to_bytes(gain_buf_vdd));
+    buf[2..].copy_from_slice(&command::to_bytes(ldac));
+}
+
+/// Encode a power-down of the channels set to true, Channel A -> 0, ..., Channel H -> 7
+pub fn encode_power_down(channels: [bool; 8], buf: &mut [u8; 2]) {
+    *buf = command::to_bytes(command::power_down(channels));
+}
+
+/// Encode a reset of all DAC data, optionally including the control data
+pub fn encode_reset(full_reset: bool, buf: &mut [u8; 2]) {
+    *buf = command::to_bytes(command::reset(full_reset));
+}
+
+/// Encode a sequence of operations back to back, returning the number of bytes written.
+/// Nothing is written to `buf` unless all operations fit and are valid.
+pub fn encode_into(ops: &[Op], buf: &mut [u8]) -> Result<usize, EncodeError> {
+    let len: usize = ops.iter().map(Op::encoded_len).sum();
+    if len > buf.len() {
+        return Err(EncodeError::BufferTooSmall);
+    }
+    for op in ops {
+        if let Op::SetChannel(channel, value) = *op {
+            command::set_channel(channel, value).ok_or(EncodeError::Oob)?;
+        }
+    }
+
+    let cmds = ops.iter().flat_map(|op| {
+        let (cmds, n) = match *op {
+            // values were validated above
+            Op::SetChannel(channel, value) => (
+                [command::set_channel(channel, value).unwrap_or_default(), 0],
+                1,
+            ),
+            Op::Configure(config) => (config.as_commands(), 2),
+            Op::PowerDown(channels) => ([command::power_down(channels), 0], 1),
+            Op::Reset(full_reset) => ([command::reset(full_reset), 0], 1),
+        };
+        cmds.into_iter().take(n)
+    });
+    for (out, cmd) in buf.chunks_exact_mut(2).zip(cmds) {
+        out.copy_from_slice(&command::to_bytes(cmd));
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ad5328, NoEnable, GAIN, LDAC};
+    use core::convert::Infallible;
+    use embedded_hal::blocking::spi::Write;
+
+    #[derive(Default)]
+    struct Capture(Vec<u8>);
+
+    impl Write<u8> for Capture {
+        type Error = Infallible;
+
+        fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+            self.0.extend_from_slice(words);
+            Ok(())
+        }
+    }
+
+    fn config() -> Ad5328Config {
+        Ad5328Config {
+            gain: (GAIN::Gain02Vref, GAIN::Gain0Vref),
+            ldac: LDAC::LdacLow,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn encoding_matches_driver_output() {
+        let power_down = [false, false, true, false, false, true, false, false];
+        let mut dac = Ad5328::init_without_enable(Capture::default(), config()).unwrap();
+        dac.set_channel(Channel::D, 0xfff).unwrap();
+        dac.power_down(power_down).unwrap();
+        dac.reset(false).unwrap();
+        dac.set_channel(Channel::A, 0).unwrap();
+        let (captured, NoEnable) = dac.release();
+
+        let ops = [
+            Op::Configure(config()),
+            Op::SetChannel(Channel::D, 0xfff),
+            Op::PowerDown(power_down),
+            Op::Reset(false),
+            Op::SetChannel(Channel::A, 0),
+        ];
+        let mut buf = [0; 16];
+        assert_eq!(encode_into(&ops, &mut buf), Ok(captured.0.len()));
+        assert_eq!(buf[..captured.0.len()], captured.0[..]);
+    }
+
+    #[test]
+    fn single_frame_encoders() {
+        let mut frame = [0; 2];
+        encode_set_channel(Channel::C, 0x123, &mut frame).unwrap();
+        assert_eq!(frame, [0x21, 0x23]);
+        assert_eq!(
+            encode_set_channel(Channel::C, 4096, &mut frame),
+            Err(EncodeError::Oob)
+        );
+        let mut config_frames = [0; 4];
+        encode_config(&Ad5328Config::default(), &mut config_frames);
+        assert_eq!(config_frames, [0x80, 0x0c, 0xa0, 0x01]);
+    }
+
+    #[test]
+    fn encode_into_reports_errors_without_writing() {
+        let mut buf = [0; 4];
+        let ops = [Op::SetChannel(Channel::A, 1), Op::Configure(config())];
+        assert_eq!(
+            encode_into(&ops, &mut buf),
+            Err(EncodeError::BufferTooSmall)
+        );
+        let ops = [
+            Op::SetChannel(Channel::A, 1),
+            Op::SetChannel(Channel::B, 4096),
+        ];
+        assert_eq!(encode_into(&ops, &mut buf), Err(EncodeError::Oob));
+        assert_eq!(buf, [0; 4]);
+    }
+}
@@ -0,0 +1,88 @@
+//! A borrowed view of one channel of the driver, so a subsystem can own its [`Channel`] choice, e.g.
+//! `struct Vco { out: Channel }`, and write through `dac.channel(vco.out)`.
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, MultiChannelDac, Transport};
+
+/// One channel of an [`Ad53xx`], see [`Ad53xx::channel`]. Every method forwards to the driver method of the
+/// same meaning, so the frames are the same as with the direct calls
+pub struct ChannelHandle<'a, SPI, EN, R, L> {
+    dac: &'a mut Ad53xx<SPI, EN, R, L>,
+    channel: Channel,
+}
+
+impl<SPI, EN, R, L> Ad53xx<SPI, EN, R, L> {
+    /// Borrow `channel` of the driver, e.g. `dac.channel(Channel::C).set(2048)`
+    pub fn channel(&mut self, channel: Channel) -> ChannelHandle<'_, SPI, EN, R, L> {
+        ChannelHandle { dac: self, channel }
+    }
+}
+
+impl<SPI, EN, R, L> ChannelHandle<'_, SPI, EN, R, L> {
+    /// The channel this handle writes
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+}
+
+impl<SPI, EN, R, L, S, P> ChannelHandle<'_, SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Set the value of the channel, see [`Ad53xx::set_channel`]
+    pub fn set(&mut self, value: u16) -> Result<(), Error<S, P>> {
+        self.dac.set_channel(self.channel, value)
+    }
+
+    /// Power the channel up or down, leaving the other channels as last set through the driver
+    pub fn power(&mut self, on: bool) -> Result<(), Error<S, P>> {
+        self.dac.power(u8::from(self.channel), on)
+    }
+
+    /// Set the channel to an output of `mv` millivolts, see [`Ad53xx::set_channel_millivolts`]
+    pub fn set_millivolts(&mut self, mv: u32, vref_mv: u32) -> Result<(), Error<S, P>> {
+        self.dac.set_channel_millivolts(self.channel, mv, vref_mv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Ad5328, Ad5328Config, Channel};
+    use embedded_hal_mock::eh0::spi;
+
+    struct Vco {
+        out: Channel,
+    }
+
+    #[test]
+    fn handle_writes_the_frames_of_the_direct_calls() {
+        let expected = [
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x28, 0x00]),
+            spi::Transaction::write(vec![0xc0, 0x04]),
+            spi::Transaction::write(vec![0xc0, 0x00]),
+            spi::Transaction::write(vec![0x23, 0xe8]),
+        ];
+        let vco = Vco { out: Channel::C };
+
+        let mut spi = spi::Mock::new(&expected);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(vco.out, 2048).unwrap();
+        dac.power_down([false, false, true, false, false, false, false, false])
+            .unwrap();
+        dac.power_down([false; 8]).unwrap();
+        dac.set_channel_millivolts(vco.out, 1000, 4096).unwrap();
+        spi.done();
+
+        let mut spi = spi::Mock::new(&expected);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        let mut out = dac.channel(vco.out);
+        assert_eq!(u8::from(out.channel()), 2);
+        out.set(2048).unwrap();
+        out.power(false).unwrap();
+        out.power(true).unwrap();
+        out.set_millivolts(1000, 4096).unwrap();
+        spi.done();
+    }
+}
@@ -0,0 +1,477 @@
+//! Ways of getting command frames onto the wire.
+
+use core::convert::Infallible;
+
+use embedded_hal::{
+    blocking::spi::{Transfer, Write},
+    digital::v2::OutputPin,
+};
+
+use crate::{Ad5328Config, Ad53xx, Error};
+
+/// Clocks a single encoded command frame out to the DAC, including whatever SYNC framing the bus needs.
+/// This is implemented for the supported HAL flavors and is not meant to be implemented downstream.
+///
+/// It is what the driver is generic over, with the public [`Interface`] as one implementation and the SPI bus
+/// plus enable pin pairs as the others, which [`Interface`] explains
+#[doc(hidden)]
+pub trait Transport<EN> {
+    type SpiError;
+    type PinError;
+
+    fn write_frame(
+        &mut self,
+        enable: &mut EN,
+        frame: &[u8; 2],
+    ) -> Result<(), Error<Self::SpiError, Self::PinError>>;
+}
+
+/// Run `write` with the enable pin asserted, for the transports that frame SYNC through an `OutputPin`.
+/// SYNC is released again when `write` fails too, reporting the error of the bus over one of the pin
+pub(crate) fn framed<EN, S, P>(
+    enable: &mut EN,
+    write: impl FnOnce() -> Result<(), S>,
+) -> Result<(), Error<S, P>>
+where
+    EN: OutputPin<Error = P>,
+{
+    enable.set_low().map_err(Error::Pin)?;
+    if let Err(e) = write() {
+        // the SPI error is the one worth reporting
+        let _ = enable.set_high();
+        return Err(Error::Spi(e));
+    }
+    enable.set_high().map_err(Error::Pin)?;
+    Ok(())
+}
+
+/// The default transport: a blocking SPI bus with an enable pin wired to SYNC
+impl<SPI, EN, S, P> Transport<EN> for SPI
+where
+    SPI: Write<u8, Error = S>,
+    EN: OutputPin<Error = P>,
+{
+    type SpiError = S;
+    type PinError = P;
+
+    fn write_frame(&mut self, enable: &mut EN, frame: &[u8; 2]) -> Result<(), Error<S, P>> {
+        framed(enable, || self.write(frame))
+    }
+}
+
+/// Stands in for the enable pin when SYNC is driven by the SPI peripheral's hardware chip select.
+/// Asserting and releasing it does nothing, so frames go straight to the bus.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoEnable;
+
+impl OutputPin for NoEnable {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// Enable pin behind an inverting level translator: it is driven high to select the DAC and low to release it.
+#[derive(Debug)]
+pub struct ActiveHigh<P>(pub P);
+
+impl<P: OutputPin> OutputPin for ActiveHigh<P> {
+    type Error = P::Error;
+
+    fn set_low(&mut self) -> Result<(), P::Error> {
+        self.0.set_high()
+    }
+
+    fn set_high(&mut self) -> Result<(), P::Error> {
+        self.0.set_low()
+    }
+}
+
+/// Borrowed SPI bus, letting a driver be built over `&mut SPI` for as long as it is needed.
+/// The bus is released again when the driver is dropped or [`released`](crate::Ad5328::release).
+pub struct SpiRef<'a, SPI>(pub &'a mut SPI);
+
+impl<SPI, S> Write<u8> for SpiRef<'_, SPI>
+where
+    SPI: Write<u8, Error = S>,
+{
+    type Error = S;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), S> {
+        self.0.write(words)
+    }
+}
+
+/// SPI bus that can only shift LSB first. Every byte is bit-reversed before it is handed to the bus,
+/// so it arrives MSB first on the wire as the AD5328 expects.
+pub struct LsbFirst<SPI>(pub SPI);
+
+impl<SPI, S> Write<u8> for LsbFirst<SPI>
+where
+    SPI: Write<u8, Error = S>,
+{
+    type Error = S;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), S> {
+        let mut buf = [0; 8];
+        for chunk in words.chunks(buf.len()) {
+            let reversed = &mut buf[..chunk.len()];
+            for (out, word) in reversed.iter_mut().zip(chunk) {
+                *out = word.reverse_bits();
+            }
+            self.0.write(reversed)?;
+        }
+        Ok(())
+    }
+}
+
+/// SPI bus configured for native 16-bit frames, sending each command as a single `u16` word.
+pub struct Spi16<SPI>(pub SPI);
+
+impl<SPI, EN, S, P> Transport<EN> for Spi16<SPI>
+where
+    SPI: Write<u16, Error = S>,
+    EN: OutputPin<Error = P>,
+{
+    type SpiError = S;
+    type PinError = P;
+
+    fn write_frame(&mut self, enable: &mut EN, frame: &[u8; 2]) -> Result<(), Error<S, P>> {
+        framed(enable, || self.0.write(&[u16::from_be_bytes(*frame)]))
+    }
+}
+
+/// SPI bus that only implements `Transfer`. The bytes read back are discarded,
+/// as the AD5328 has no data output in standalone mode.
+pub struct SpiTransfer<SPI>(pub SPI);
+
+impl<SPI, EN, S, P> Transport<EN> for SpiTransfer<SPI>
+where
+    SPI: Transfer<u8, Error = S>,
+    EN: OutputPin<Error = P>,
+{
+    type SpiError = S;
+    type PinError = P;
+
+    fn write_frame(&mut self, enable: &mut EN, frame: &[u8; 2]) -> Result<(), Error<S, P>> {
+        let mut words = *frame;
+        framed(enable, || self.0.transfer(&mut words).map(|_| ()))
+    }
+}
+
+/// A custom transport for the 16-bit command frames, e.g. a bit-banged 3-wire interface or a host-side test double.
+///
+/// The implementation is responsible for the whole frame, including SYNC: it is asserted before the first bit
+/// and released after the last one, and frames are shifted out MSB first.
+///
+/// The built-in SPI buses with an enable pin, e.g. through [`init`](Ad53xx::init), don't implement this trait.
+/// They reach the driver through the same internal transport as an `Interface` does, which keeps their bus and
+/// pin errors apart as [`Error::Spi`] and [`Error::Pin`] and the pin separate, so that
+/// [`release`](Ad53xx::release) hands it back. Everything else the driver offers works the same on both
+pub trait Interface {
+    type Error;
+
+    /// Clock out a single 16-bit command frame
+    fn write_frame(&mut self, frame: u16) -> Result<(), Self::Error>;
+}
+
+/// Stands in for the enable pin when the driver talks through a custom [`Interface`], which frames SYNC itself.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InterfaceSync;
+
+impl<I> Transport<InterfaceSync> for I
+where
+    I: Interface,
+{
+    type SpiError = I::Error;
+    type PinError = Infallible;
+
+    fn write_frame(
+        &mut self,
+        _enable: &mut InterfaceSync,
+        frame: &[u8; 2],
+    ) -> Result<(), Error<I::Error, Infallible>> {
+        Interface::write_frame(self, u16::from_be_bytes(*frame)).map_err(Error::Spi)
+    }
+}
+
+impl<'a, SPI, EN, R, S, P> Ad53xx<SpiRef<'a, SPI>, EN, R>
+where
+    SPI: Write<u8, Error = S>,
+    EN: OutputPin<Error = P>,
+    R: Default,
+{
+    /// Initialize a new Ad5328 instance over a borrowed SPI bus, while configuring it for the first time.
+    /// The bus can be used for other devices again once the driver is dropped or released.
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal compatible SPI instance, borrowed for the lifetime of the driver
+    /// * `enable` - embedded-hal compatible GPIO pin
+    /// * `config` - The Ad5328 device configuration struct
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ad5328::{Ad5328, Ad5328Config, Channel, NoEnable};
+    /// # use embedded_hal::blocking::spi::Write;
+    /// # use embedded_hal_mock::eh0::spi;
+    /// # let mut spi = spi::Mock::new(&[
+    /// #     spi::Transaction::write(vec![0x80, 0x0c]),
+    /// #     spi::Transaction::write(vec![0xa0, 0x01]),
+    /// #     spi::Transaction::write(vec![0x08, 0x00]),
+    /// #     spi::Transaction::write(vec![0x01, 0x02]),
+    /// # ]);
+    /// # let mut spi_check = spi.clone();
+    /// // Get `spi` from your embedded-hal
+    /// let mut dac = Ad5328::init_borrowed(&mut spi, NoEnable, Ad5328Config::default()).unwrap();
+    /// dac.set_channel(Channel::A, 2048).unwrap();
+    /// let (_, enable) = dac.release();
+    /// // The bus is free for another device again
+    /// spi.write(&[0x01, 0x02]).unwrap();
+    /// # spi_check.done();
+    /// ```
+    pub fn init_borrowed(
+        spi: &'a mut SPI,
+        enable: EN,
+        config: Ad5328Config,
+    ) -> Result<Self, Error<S, P>> {
+        Self::init(SpiRef(spi), enable, config)
+    }
+}
+
+impl<SPI, R, S> Ad53xx<SPI, NoEnable, R>
+where
+    SPI: Write<u8, Error = S>,
+    R: Default,
+{
+    /// Initialize a new Ad5328 instance without an enable pin, while configuring it for the first time.
+    /// Use this when SYNC is driven by the SPI peripheral's hardware chip select.
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal compatible SPI instance
+    /// * `config` - The Ad5328 device configuration struct
+    pub fn init_without_enable(
+        spi: SPI,
+        config: Ad5328Config,
+    ) -> Result<Self, Error<S, Infallible>> {
+        Self::init(spi, NoEnable, config)
+    }
+}
+
+impl<SPI, EN, R, S, P> Ad53xx<Spi16<SPI>, EN, R>
+where
+    SPI: Write<u16, Error = S>,
+    EN: OutputPin<Error = P>,
+    R: Default,
+{
+    /// Initialize a new Ad5328 instance on an SPI peripheral using 16-bit frames, while configuring it for the first time
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal compatible SPI instance writing `u16` words MSB first
+    /// * `enable` - embedded-hal compatible GPIO pin
+    /// * `config` - The Ad5328 device configuration struct
+    pub fn init_16bit(spi: SPI, enable: EN, config: Ad5328Config) -> Result<Self, Error<S, P>> {
+        Self::init(Spi16(spi), enable, config)
+    }
+}
+
+impl<SPI, EN, R, S, P> Ad53xx<LsbFirst<SPI>, EN, R>
+where
+    SPI: Write<u8, Error = S>,
+    EN: OutputPin<Error = P>,
+    R: Default,
+{
+    /// Initialize a new Ad5328 instance on an SPI peripheral that shifts LSB first, while configuring it for the first time
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal compatible SPI instance, see [`LsbFirst`]
+    /// * `enable` - embedded-hal compatible GPIO pin
+    /// * `config` - The Ad5328 device configuration struct
+    pub fn init_lsb_first(spi: SPI, enable: EN, config: Ad5328Config) -> Result<Self, Error<S, P>> {
+        Self::init(LsbFirst(spi), enable, config)
+    }
+}
+
+impl<SPI, EN, R, S, P> Ad53xx<SpiTransfer<SPI>, EN, R>
+where
+    SPI: Transfer<u8, Error = S>,
+    EN: OutputPin<Error = P>,
+    R: Default,
+{
+    /// Initialize a new Ad5328 instance on an SPI peripheral that only implements `Transfer`,
+    /// while configuring it for the first time
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal compatible SPI instance
+    /// * `enable` - embedded-hal compatible GPIO pin
+    /// * `config` - The Ad5328 device configuration struct
+    pub fn init_transfer(spi: SPI, enable: EN, config: Ad5328Config) -> Result<Self, Error<S, P>> {
+        Self::init(SpiTransfer(spi), enable, config)
+    }
+}
+
+impl<I, R> Ad53xx<I, InterfaceSync, R>
+where
+    I: Interface,
+    R: Default,
+{
+    /// Initialize a new Ad5328 instance on a custom [`Interface`], while configuring it for the first time.
+    /// Errors of the interface are reported as [`Error::Spi`].
+    /// # Arguments
+    ///
+    /// * `interface` - the transport clocking out each 16-bit frame
+    /// * `config` - The Ad5328 device configuration struct
+    pub fn init_interface(
+        interface: I,
+        config: Ad5328Config,
+    ) -> Result<Self, Error<I::Error, Infallible>> {
+        Self::init(interface, InterfaceSync, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Event, Log, LogPin, LogSpi};
+    use crate::Ad5328;
+    use crate::Channel;
+
+    #[derive(Default)]
+    struct Frames(Vec<u16>);
+
+    impl Interface for Frames {
+        type Error = Infallible;
+
+        fn write_frame(&mut self, frame: u16) -> Result<(), Infallible> {
+            self.0.push(frame);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn every_command_goes_through_the_interface() {
+        let mut dac = Ad5328::init_interface(Frames::default(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::C, 0x123).unwrap();
+        dac.power_down([false, true, false, false, false, false, false, false])
+            .unwrap();
+        dac.reset(true).unwrap();
+        let (frames, _) = dac.release();
+        assert_eq!(frames.0, [0x800c, 0xa001, 0x2123, 0xc002, 0xf000]);
+    }
+
+    #[derive(Default)]
+    struct TransferOnly(Vec<u8>);
+
+    impl Transfer<u8> for TransferOnly {
+        type Error = Infallible;
+
+        fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+            self.0.extend_from_slice(words);
+            words.fill(0xff);
+            Ok(words)
+        }
+    }
+
+    #[test]
+    fn transfer_only_bus_sends_the_same_bytes() {
+        let mut dac =
+            Ad5328::init_transfer(TransferOnly::default(), NoEnable, Ad5328Config::default())
+                .unwrap();
+        dac.set_channel(Channel::C, 0x123).unwrap();
+        dac.set_channel(Channel::C, 0x123).unwrap();
+        let (SpiTransfer(spi), NoEnable) = dac.release();
+        assert_eq!(spi.0, [0x80, 0x0c, 0xa0, 0x01, 0x21, 0x23, 0x21, 0x23]);
+    }
+
+    #[test]
+    fn lsb_first_bus_gets_every_byte_reversed() {
+        use embedded_hal_mock::eh0::spi;
+
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x01, 0x30]),
+            spi::Transaction::write(vec![0x05, 0x80]),
+            spi::Transaction::write(vec![0x84, 0xc4]),
+            spi::Transaction::write(vec![0x03, 0xff]),
+            spi::Transaction::write(vec![0x0f, 0x00]),
+            spi::Transaction::write(vec![0x07, 0x00]),
+            // longer writes go out in chunks of the internal buffer
+            spi::Transaction::write((0..8).map(|n: u8| n.reverse_bits()).collect()),
+            spi::Transaction::write((8..16).map(|n: u8| n.reverse_bits()).collect()),
+            spi::Transaction::write((16..20).map(|n: u8| n.reverse_bits()).collect()),
+        ]);
+        let mut dac =
+            Ad5328::init_lsb_first(spi.clone(), NoEnable, Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::C, 0x123).unwrap();
+        dac.power_down([true; 8]).unwrap();
+        dac.reset(true).unwrap();
+        dac.reset(false).unwrap();
+        let (mut lsb_first, NoEnable) = dac.release();
+        let bytes: Vec<u8> = (0..20).collect();
+        lsb_first.write(&bytes).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn active_high_pin_is_raised_around_every_frame() {
+        let log = Log::default();
+        let mut dac = Ad5328::init(
+            LogSpi::new(&log),
+            ActiveHigh(LogPin(log.clone())),
+            Ad5328Config::default(),
+        )
+        .unwrap();
+        dac.configure(Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::A, 1).unwrap();
+        dac.power_down([false; 8]).unwrap();
+        dac.reset(true).unwrap();
+
+        let log = log.events();
+        assert_eq!(log.len(), 3 * 7);
+        for frame in log.chunks(3) {
+            assert!(matches!(
+                frame,
+                [Event::Sync(true), Event::Bytes(_), Event::Sync(false)]
+            ));
+        }
+    }
+
+    #[test]
+    fn sync_is_released_when_the_bus_fails() {
+        use embedded_hal_mock::eh0::{pin, MockError};
+
+        let failing = || LogSpi {
+            log: Log::default(),
+            failing: true,
+        };
+        let levels = |levels: [pin::State; 2]| levels.map(pin::Transaction::set);
+
+        let mut enable = pin::Mock::new(&levels([pin::State::Low, pin::State::High]));
+        let result = Ad5328::init(failing(), enable.clone(), Ad5328Config::default());
+        assert!(matches!(result, Err(Error::Spi(()))));
+        enable.done();
+
+        let mut enable = pin::Mock::new(&levels([pin::State::High, pin::State::Low]));
+        let result = Ad5328::init(
+            failing(),
+            ActiveHigh(enable.clone()),
+            Ad5328Config::default(),
+        );
+        assert!(matches!(result, Err(Error::Spi(()))));
+        enable.done();
+
+        // the bus error is reported over a pin that fails to release SYNC as well
+        let mut enable = pin::Mock::new(&[
+            pin::Transaction::set(pin::State::Low),
+            pin::Transaction::set(pin::State::High)
+                .with_error(MockError::Io(std::io::ErrorKind::Other)),
+        ]);
+        let result = Ad5328::init(failing(), enable.clone(), Ad5328Config::default());
+        assert!(matches!(result, Err(Error::Spi(()))));
+        enable.done();
+    }
+}
@@ -1,10 +1,75 @@
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
-use embedded_hal::{blocking::spi::Write, digital::v2::OutputPin};
+pub mod bank;
+pub mod calibration;
+pub mod chain;
+pub mod chip_select;
+mod command;
+mod dither;
+pub mod envelope;
+pub mod frames;
+pub mod handle;
+mod interface;
+pub mod measure;
+mod mute;
+pub mod nonblocking;
+pub mod osc;
+mod playback;
+pub mod quad;
+pub mod ramp;
+pub mod refresh;
+pub mod resolution;
+pub mod scale;
+mod slew;
+pub mod snapshot;
+mod tempco;
+#[cfg(test)]
+mod testing;
+mod throttle;
+mod units;
+pub mod wavetable;
+
+use embedded_hal::blocking::delay::DelayUs;
+use resolution::{AnyResolution, Bits10, Bits12, Bits8, Resolution};
+use scale::{Calibration, Rounding, UnitScale, VoctCorrection};
+
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "eh1")]
+pub mod eh1;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "linux")]
+pub mod linux;
+#[cfg(feature = "heapless")]
+pub mod queue;
+#[cfg(feature = "rand")]
+pub mod random;
+#[cfg(feature = "critical-section")]
+pub mod shared;
+#[cfg(feature = "embassy-sync")]
+pub mod shared_async;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+#[cfg(feature = "uom")]
+pub mod voltage;
+#[cfg(feature = "write-iter")]
+mod write_iter;
+
+#[doc(hidden)]
+pub use interface::Transport;
+pub use interface::{
+    ActiveHigh, Interface, InterfaceSync, LsbFirst, NoEnable, Spi16, SpiRef, SpiTransfer,
+};
 
 #[repr(u8)]
 /// All available DAC channels (A..H). These are configurable in two groups: A...D and E...H.
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Channel {
     /// DAC Channel A
     A,
@@ -50,26 +115,377 @@ impl Channel {
     fn as_u16(&self) -> u16 {
         (*self as u16) << 12
     }
+
+    /// The configuration group of the channel
+    pub fn group(self) -> Group {
+        if u8::from(self) < 4 {
+            Group::AToD
+        } else {
+            Group::EToH
+        }
+    }
+}
+
+/// The two groups of four channels that share their GAIN, BUF and VDD settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Group {
+    /// Channels A to D, the first of each pair in [`Ad5328Config`]
+    AToD,
+    /// Channels E to H, the second of each pair in [`Ad5328Config`]
+    EToH,
+}
+
+impl Group {
+    /// The channels of the group in ascending order
+    pub fn channels(self) -> [Channel; 4] {
+        match self {
+            Group::AToD => [Channel::A, Channel::B, Channel::C, Channel::D],
+            Group::EToH => [Channel::E, Channel::F, Channel::G, Channel::H],
+        }
+    }
+}
+
+/// A 12-bit code, checked once when it is made so [`Ad53xx::set_channel_value`] needs no bounds check.
+/// In a const context an out of bounds code fails to compile, e.g. for tables in flash:
+///
+/// ```
+/// use ad5328::DacValue;
+///
+/// const fn code(code: u16) -> DacValue {
+///     match DacValue::new(code) {
+///         Some(value) => value,
+///         None => panic!("not a 12-bit code"),
+///     }
+/// }
+///
+/// const RAMP: [DacValue; 3] = [code(0), code(2048), code(4095)];
+/// assert_eq!(u16::from(RAMP[2]), 4095);
+/// ```
+///
+/// ```compile_fail
+/// # use ad5328::DacValue;
+/// const TOO_HIGH: DacValue = match DacValue::new(4096) {
+///     Some(value) => value,
+///     None => panic!("not a 12-bit code"),
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DacValue(u16);
+
+impl DacValue {
+    /// The full-scale code
+    pub const MAX: DacValue = DacValue(4095);
+
+    /// `code` if it is at most [`MAX`](Self::MAX)
+    pub const fn new(code: u16) -> Option<Self> {
+        if code > Self::MAX.0 {
+            return None;
+        }
+        Some(Self(code))
+    }
+}
+
+impl From<DacValue> for u16 {
+    fn from(value: DacValue) -> Self {
+        value.0
+    }
+}
+
+/// One value per channel, `map[Channel::A]` to `map[Channel::H]`, e.g. for per-channel calibration data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelMap<T>(pub [T; 8]);
+
+impl<T> core::ops::Index<Channel> for ChannelMap<T> {
+    type Output = T;
+
+    fn index(&self, channel: Channel) -> &T {
+        &self.0[usize::from(u8::from(channel))]
+    }
+}
+
+impl<T> core::ops::IndexMut<Channel> for ChannelMap<T> {
+    fn index_mut(&mut self, channel: Channel) -> &mut T {
+        &mut self.0[usize::from(u8::from(channel))]
+    }
+}
+
+/// Settings of the conversions to codes, see [`scale`], and of the channels they are written to
+#[derive(Clone, Copy, Default)]
+struct Pipeline {
+    zero_note: u8,
+    voct: ChannelMap<Option<&'static VoctCorrection>>,
+    lut: ChannelMap<Option<scale::Lut>>,
+    calibration: ChannelMap<Calibration>,
+    master_trim: Calibration,
+    limits: ChannelMap<Option<(u16, u16)>>,
+    limit_policy: LimitPolicy,
+    inverted: ChannelMap<bool>,
+    value_policy: ValuePolicy,
+    /// Primary per replica
+    mirror_of: ChannelMap<Option<Channel>>,
+    dither: ChannelMap<Option<dither::Dither>>,
+    slew: ChannelMap<Option<slew::Slew>>,
+    /// Code in bounds written by [`Ad53xx::mute`]
+    mute_code: ChannelMap<u16>,
+    muted: ChannelMap<Option<mute::Mute>>,
+    /// Physical channel per logical one, `None` for the identity
+    channel_map: Option<ChannelMap<Channel>>,
+    /// Skip writes of a channel's last code
+    dedup: bool,
+    /// Codes a write has to move from the last code of the channel, in LSBs of the part
+    deadband: ChannelMap<u16>,
+    units: ChannelMap<UnitScale>,
+    rounding: ChannelMap<Rounding>,
+    /// Value last written through the pipeline, `None` after a raw code
+    setpoint: ChannelMap<Option<u16>>,
+    /// Output last set through [`Ad53xx::set_channel_millivolts`], `None` after a write of a code
+    millivolts: ChannelMap<Option<u32>>,
+    tempco: ChannelMap<Option<tempco::Tempco>>,
+    /// Temperature given to [`Ad53xx::update_temperature`], in tenths of a degree Celsius
+    temperature: Option<i16>,
+    /// Ticks given to [`Ad53xx::set_now`]
+    now: u32,
+    throttle: ChannelMap<Option<throttle::Throttle>>,
+}
+
+/// What happens to a value over the max value, see [`Ad53xx::with_value_policy`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ValuePolicy {
+    /// Fail like an out of bounds code without writing anything
+    #[default]
+    Strict,
+    /// Write the nearest code in bounds instead, e.g. for values from modulation sources
+    Saturate,
+}
+
+/// What a write of a channel did, see [`Ad53xx::update_channel`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Written {
+    /// The frame was sent
+    Sent,
+    /// The code was the channel's last code with [deduplication](Ad53xx::with_dedup), or within its
+    /// [deadband](Ad53xx::set_deadband), so no frame was sent
+    Skipped,
+    /// The channel is [muted](Ad53xx::mute), the code is held for the unmute
+    Held,
+    /// The channel was written within its [rate limit](Ad53xx::set_rate_limit), the code is written by a later
+    /// [`poll`](Ad53xx::poll) unless another write replaces it first
+    Pending,
+}
+
+/// What happens to a code outside the limits of its channel, see [`Ad53xx::set_limits`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LimitPolicy {
+    /// Write the nearest limit instead
+    #[default]
+    Clamp,
+    /// Fail with [`Error::Limited`] without writing anything
+    Reject,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error<S, P> {
     /// SPI bus error
     Spi(S),
     /// Pin error
     Pin(P),
-    /// Connection error (device not found)
+    /// Connection error (device not found), or an output measured far off by
+    /// [`auto_calibrate`](Ad53xx::auto_calibrate)
     Conn,
     /// Address error (invalid or out of bounds)
     Address,
     /// Port error (invalid or out of bounds)
     Port,
-    /// Out of bounds error
+    /// Out of bounds error, e.g. a reference of 0
     Oob,
+    /// Out of bounds channel value, with the maximum code of the part
+    OobMax(u16),
+    /// Staging needs the DAC registers latched, but the configured LDAC mode is [`LDAC::LdacLow`]
+    Ldac,
+    /// The LDAC pin could not be driven, with its error converted to the pin error type, it has still been set
+    /// high again if possible
+    LdacPin(P),
+    /// Lookup table that isn't non-decreasing, has less than two points or points over the maximum code
+    Lut,
+    /// The code is outside the limits of its channel with [`LimitPolicy::Reject`], carried
+    Limited(u16),
+    /// Channel map that uses a physical channel twice
+    ChannelMap,
+    /// Write to a replica of a mirrored channel, or a mirror that would chain
+    Mirror,
+    /// No room left in the queue of a [`Deferred`](queue::Deferred) driver
+    QueueFull,
+}
+
+impl<S, P> Error<S, P> {
+    /// Drop the inner SPI and pin errors, keeping only the variant.
+    /// Handy for logging errors whose inner types can't be formatted, e.g. with `defmt`
+    pub fn erase(self) -> Error<(), ()> {
+        match self {
+            Error::Spi(_) => Error::Spi(()),
+            Error::Pin(_) => Error::Pin(()),
+            Error::Conn => Error::Conn,
+            Error::Address => Error::Address,
+            Error::Port => Error::Port,
+            Error::Oob => Error::Oob,
+            Error::OobMax(max) => Error::OobMax(max),
+            Error::Ldac => Error::Ldac,
+            Error::LdacPin(_) => Error::LdacPin(()),
+            Error::Lut => Error::Lut,
+            Error::Limited(code) => Error::Limited(code),
+            Error::ChannelMap => Error::ChannelMap,
+            Error::Mirror => Error::Mirror,
+            Error::QueueFull => Error::QueueFull,
+        }
+    }
+}
+
+impl<S, P> core::fmt::Display for Error<S, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Error::Spi(_) => "SPI bus error",
+            Error::Pin(_) => "enable pin error",
+            Error::Conn => "device not found",
+            Error::Address => "invalid or out of bounds address",
+            Error::Port => "invalid or out of bounds port",
+            Error::Oob => "channel value out of bounds",
+            Error::OobMax(max) => return write!(f, "channel value over the maximum of {}", max),
+            Error::Ldac => "staging with LDAC mode low",
+            Error::LdacPin(_) => "LDAC pin error",
+            Error::Lut => "invalid lookup table",
+            Error::Limited(code) => {
+                return write!(f, "code {} outside the limits of the channel", code)
+            }
+            Error::ChannelMap => "physical channel mapped twice",
+            Error::Mirror => "invalid channel mirror",
+            Error::QueueFull => "command queue full",
+        })
+    }
+}
+
+/// The inner SPI or pin error is the `source()` of [`Error::Spi`], [`Error::Pin`] and [`Error::LdacPin`],
+/// so errors can be propagated into e.g. `anyhow` with `?`:
+///
+/// ```
+/// # use ad5328::{Ad5328, Ad5328Config, Channel, NoEnable};
+/// # struct Rig;
+/// # impl embedded_hal::blocking::spi::Write<u8> for Rig {
+/// #     type Error = std::io::Error;
+/// #     fn write(&mut self, _: &[u8]) -> std::io::Result<()> { Ok(()) }
+/// # }
+/// fn ramp(dac: &mut Ad5328<Rig, NoEnable>) -> anyhow::Result<()> {
+///     for value in (0..4096).step_by(256) {
+///         dac.set_channel(Channel::A, value)?;
+///     }
+///     Ok(())
+/// }
+/// # let mut dac = Ad5328::init_without_enable(Rig, Ad5328Config::default()).unwrap();
+/// # ramp(&mut dac).unwrap();
+/// ```
+impl<S, P> core::error::Error for Error<S, P>
+where
+    S: core::error::Error + 'static,
+    P: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::Spi(e) => Some(e),
+            Error::Pin(e) | Error::LdacPin(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Error while streaming a sequence of samples, together with the index of the sample it refers to.
+/// For an out of bounds sample nothing has been written; for a bus error all samples before `index` have been.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StreamError<S, P> {
+    /// Index of the offending sample
+    pub index: usize,
+    /// The underlying error
+    pub error: Error<S, P>,
+}
+
+impl<S, P> core::fmt::Display for StreamError<S, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} at sample {}", self.error, self.index)
+    }
+}
+
+impl<S, P> core::error::Error for StreamError<S, P>
+where
+    S: core::error::Error + 'static,
+    P: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        core::error::Error::source(&self.error)
+    }
+}
+
+/// Stage of [`Ad53xx::reapply_state`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReapplyStage {
+    /// The two configuration frames
+    Config,
+    /// The power-down frame
+    PowerDown,
+    /// The code of a channel, Channel A -> 0, ..., Channel H -> 7
+    Value(u8),
+    /// The LDAC update transferring the codes to the outputs
+    Update,
+}
+
+/// Error of [`Ad53xx::reapply_state`], together with the stage it failed in. All stages before it are done
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReapplyError<S, P> {
+    /// The stage that failed
+    pub stage: ReapplyStage,
+    /// The underlying error
+    pub error: Error<S, P>,
+}
+
+impl<S, P> core::fmt::Display for ReapplyError<S, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.stage {
+            ReapplyStage::Config => write!(f, "{} reapplying the configuration", self.error),
+            ReapplyStage::PowerDown => write!(f, "{} reapplying the power-down state", self.error),
+            ReapplyStage::Value(n) => {
+                write!(f, "{} reapplying the code of channel {}", self.error, n)
+            }
+            ReapplyStage::Update => write!(f, "{} updating the outputs", self.error),
+        }
+    }
+}
+
+impl<S, P> core::error::Error for ReapplyError<S, P>
+where
+    S: core::error::Error + 'static,
+    P: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        core::error::Error::source(&self.error)
+    }
 }
 
 #[repr(u8)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 /// The gain of the DACs is controlled by setting Bit 4 for the first group of DACs (A, B, C, and D) and Bit 5 for the second group of DACs (E, F, G, and H).
 pub enum GAIN {
     /// Output range of 0V to Vref
@@ -86,6 +502,9 @@ impl GAIN {
 
 #[repr(u8)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 /// This controls whether the reference of a group of DACs is buffered or unbuffered. The reference of the first group of DACs (A, B, C, and D) is controlled by setting Bit 2, and the second group of DACs (E, F, G, and H) is controlled by setting Bit 3.  
 pub enum BUF {
     /// Unbuffered reference
@@ -102,6 +521,9 @@ impl BUF {
 
 #[repr(u8)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 /// These bits are set when VDD is to be used as a reference. The first group of DACs (A, B, C, and D) can be set up to use VDD by setting Bit 0, and the second group of DACs (E, F, G, and H) by setting Bit 1. The VDD bits have priority over the BUF bits. When VDD is used as the reference, it is always unbuffered and has an output range of 0 V to VREF regardless of the state of the GAIN and BUF bits.
 pub enum VDD {
     /// Use external voltage reference
@@ -118,6 +540,9 @@ impl VDD {
 
 #[repr(u8)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 /// LDAC mode controls LDAC, which determines when data is transferred from the input registers to the DAC registers. There are three options when updating the DAC registers, as shown in Table 8 (DS p17). If the user wishes to update the DAC through software, the LDAC pin should be tied high and the LDAC mode bits set as required. Alternatively, if the user wishes to control the DAC through hardware, that is, the LDAC pin, the LDAC mode bits should be set to LDAC high (default mode).
 pub enum LDAC {
     /// This option sets LDAC permanently low, VDD allowing the DAC registers to be updated continuously
@@ -135,6 +560,10 @@ impl LDAC {
 }
 
 /// Configures GAIN, BUF and VDD bits (for channels A...D and E...H respectively) as well as LDAC behavior (for all channels)
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Ad5328Config {
     pub gain: (GAIN, GAIN),
     pub buf: (BUF, BUF),
@@ -154,6 +583,14 @@ impl Default for Ad5328Config {
 }
 
 impl Ad5328Config {
+    /// All control bits cleared, as after power-on or a full reset
+    const POWER_ON: Self = Self {
+        gain: (GAIN::Gain0Vref, GAIN::Gain0Vref),
+        buf: (BUF::Unbuffered, BUF::Unbuffered),
+        vdd: (VDD::ExternalRef, VDD::ExternalRef),
+        ldac: LDAC::LdacLow,
+    };
+
     /// Serialize the config as two easily digestible commands
     fn as_commands(&self) -> [u16; 2] {
         [
@@ -169,26 +606,391 @@ impl Ad5328Config {
     }
 }
 
-pub struct Ad5328<SPI, EN> {
+/// A single DAC output, so code driving one output doesn't need to know about the driver or channel behind it
+pub trait DacChannel {
+    /// Error of a write to the output
+    type Error;
+
+    /// Set the raw 12-bit code of the output. Max value is 4095
+    fn set_raw(&mut self, value: u16) -> Result<(), Self::Error>;
+}
+
+/// An octal DAC addressed by channel index, so application code doesn't depend on the concrete device
+pub trait MultiChannelDac {
+    /// Error of the device
+    type Error;
+
+    /// Number of channels, valid indices are `0..CHANNELS`
+    const CHANNELS: usize;
+
+    /// Set the raw code of channel `ch`
+    fn set(&mut self, ch: u8, code: u16) -> Result<(), Self::Error>;
+
+    /// Power channel `ch` up or down, leaving the other channels as they are
+    fn power(&mut self, ch: u8, on: bool) -> Result<(), Self::Error>;
+}
+
+/// Power-up handling for [`Ad5328::init_with_delay`]
+#[derive(Clone, Copy)]
+pub struct Startup {
+    /// Time to wait before the first command, in microseconds
+    pub startup_us: u32,
+    /// Issue a full reset before configuring
+    pub full_reset: bool,
+}
+
+/// Stands in for the LDAC pin when it is tied high or low and not driven by the driver,
+/// [`Ad53xx::commit`] and [`Ad53xx::set_channels_synced`] then use the LDAC command
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoLdac;
+
+#[doc(hidden)]
+/// An LDAC pin pulsed by the driver to transfer the input registers to the DAC registers, failing with an error
+/// of the pin error type `P`
+pub trait LdacPulse<P> {
+    /// Pulse LDAC low and back high, or `None` if there is no pin to pulse
+    fn pulse(&mut self) -> Option<Result<(), P>>;
+}
+
+impl<P> LdacPulse<P> for NoLdac {
+    fn pulse(&mut self) -> Option<Result<(), P>> {
+        None
+    }
+}
+
+/// Driver for the AD5308, AD5318 and AD5328 over an SPI bus and an enable pin wired to SYNC,
+/// usually named through the [`Ad5308`], [`Ad5318`] and [`Ad5328`] aliases.
+/// The parts only differ in the width `R` of their codes, see [`resolution`].
+///
+/// `SPI` can be any blocking `Write<u8>` implementation, including bus proxies such as those from `shared-bus`.
+/// The enable pin is asserted before each 16-bit frame and released only after the `write` call for that frame
+/// has returned, so a proxy's locking never lets another device onto the bus while SYNC is low.
+///
+/// `L` is the LDAC pin if the driver pulses it, see `eh1::LdacPin`, and [`NoLdac`] otherwise.
+pub struct Ad53xx<SPI, EN, R, L = NoLdac> {
     spi: SPI,
     enable: EN,
     cmd_buf: [u8; 2],
+    /// Data bits of the frame last written to each logical channel, `None` until one is
+    data: [Option<u16>; 8],
+    powered_down: [bool; 8],
+    config: Ad5328Config,
+    staged: [bool; 8],
+    resolution: R,
+    ldac_pin: L,
+    pipeline: Pipeline,
 }
 
-impl<SPI, EN, S, P> Ad5328<SPI, EN>
-where
-    SPI: Write<u8, Error = S>,
-    EN: OutputPin<Error = P>,
-{
-    fn write(&mut self, cmd: u16) -> Result<(), Error<S, P>> {
-        self.enable.set_low().map_err(Error::Pin)?;
-        self.cmd_buf[0] = (cmd >> 8) as u8;
-        self.cmd_buf[1] = (cmd & 0xff) as u8;
-        self.spi.write(&self.cmd_buf).map_err(Error::Spi)?;
-        self.enable.set_high().map_err(Error::Pin)?;
-        Ok(())
+/// Driver for the 8-bit AD5308
+pub type Ad5308<SPI, EN> = Ad53xx<SPI, EN, Bits8>;
+/// Driver for the 10-bit AD5318
+pub type Ad5318<SPI, EN> = Ad53xx<SPI, EN, Bits10>;
+/// Driver for the 12-bit AD5328
+pub type Ad5328<SPI, EN> = Ad53xx<SPI, EN, Bits12>;
+
+impl<SPI, EN, R> Ad53xx<SPI, EN, R> {
+    fn new(spi: SPI, enable: EN) -> Self
+    where
+        R: Default,
+    {
+        Self {
+            spi,
+            enable,
+            cmd_buf: [0; 2],
+            data: [None; 8],
+            powered_down: [false; 8],
+            config: Ad5328Config::POWER_ON,
+            staged: [false; 8],
+            resolution: R::default(),
+            ldac_pin: NoLdac,
+            pipeline: Pipeline::default(),
+        }
+    }
+}
+
+impl<SPI, EN, R, L> Ad53xx<SPI, EN, R, L> {
+    /// Continue with the resolution picked at runtime, e.g. on boards populated with either part.
+    /// The bus is not touched, the parts share their configuration and control commands
+    ///
+    /// ```
+    /// # use ad5328::{resolution::AnyResolution, Ad5328, Ad5328Config, Channel, Error, NoEnable};
+    /// # use embedded_hal_mock::eh0::spi;
+    /// # let spi = spi::Mock::new(&[
+    /// #     spi::Transaction::write(vec![0x80, 0x0c]),
+    /// #     spi::Transaction::write(vec![0xa0, 0x01]),
+    /// #     spi::Transaction::write(vec![0x0f, 0xfc]),
+    /// # ]);
+    /// # let mut spi_check = spi.clone();
+    /// # let ad5318_fitted = true;
+    /// let resolution = if ad5318_fitted {
+    ///     AnyResolution::Bits10
+    /// } else {
+    ///     AnyResolution::Bits12
+    /// };
+    /// let mut dac = Ad5328::init_without_enable(spi, Ad5328Config::default())
+    ///     .unwrap()
+    ///     .with_resolution(resolution);
+    /// dac.set_channel(Channel::A, 1023).unwrap();
+    /// assert!(matches!(dac.set_channel(Channel::A, 1024), Err(Error::OobMax(1023))));
+    /// # spi_check.done();
+    /// ```
+    pub fn with_resolution(self, resolution: AnyResolution) -> Ad53xx<SPI, EN, AnyResolution, L> {
+        Ad53xx {
+            spi: self.spi,
+            enable: self.enable,
+            cmd_buf: self.cmd_buf,
+            data: self.data,
+            powered_down: self.powered_down,
+            config: self.config,
+            staged: self.staged,
+            resolution,
+            ldac_pin: self.ldac_pin,
+            pipeline: self.pipeline,
+        }
+    }
+
+    /// Handle values out of bounds by `policy`, [`ValuePolicy::Strict`] unless set. With
+    /// [`ValuePolicy::Saturate`], [`set_channel`](Self::set_channel), the batch methods and the conversions of the
+    /// other `set_channel*` methods write the nearest code in bounds instead of failing, but inputs without a
+    /// nearest code, such as NaN or a reference of 0 mV, still fail
+    pub fn with_value_policy(mut self, policy: ValuePolicy) -> Self {
+        self.pipeline.value_policy = policy;
+        self
+    }
+
+    /// The policy set with [`with_value_policy`](Self::with_value_policy)
+    pub fn value_policy(&self) -> ValuePolicy {
+        self.pipeline.value_policy
+    }
+
+    /// Skip the frame of any write of a channel's [last code](Self::last_code) if `dedup`, off unless set, e.g.
+    /// for a control loop writing all channels every tick on a shared bus. This covers [`set_channel`](Self::set_channel)
+    /// and all other writes of channel values, [`update_channel`](Self::update_channel) tells whether a frame was
+    /// sent. Channels whose last code is unknown, such as after a [`reset`](Self::reset) or
+    /// [`invalidate_cache`](Self::invalidate_cache), are always written
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.pipeline.dedup = dedup;
+        self
+    }
+
+    /// Whether writes of a channel's last code are skipped, see [`with_dedup`](Self::with_dedup)
+    pub fn dedups(&self) -> bool {
+        self.pipeline.dedup
+    }
+
+    /// Skip writes of `channel` whose code is within `lsb` codes of its [last code](Self::last_code), e.g. for
+    /// setpoints from a noisy potentiometer. The comparison is with the code last written, not the value last
+    /// requested, so a slow drift still gets through once it is more than `lsb` codes away. The codes compared
+    /// are those after the value pipeline, and the replicas of a [mirrored](Self::mirror) channel follow its
+    /// deadband. [`update_channel`](Self::update_channel) tells whether a frame was sent, and
+    /// [`force_channel`](Self::force_channel) writes regardless. A `lsb` of 0, the default, writes every value
+    pub fn set_deadband(&mut self, channel: Channel, lsb: u16) {
+        self.pipeline.deadband[channel] = lsb;
+    }
+
+    /// Deadband of `channel` set through [`set_deadband`](Self::set_deadband), 0 unless set
+    pub fn deadband(&self, channel: Channel) -> u16 {
+        self.pipeline.deadband[channel]
+    }
+
+    /// Forget the last codes of all channels, e.g. after a brownout of the chip, so the next write of every
+    /// channel is sent even with [deduplication](Self::with_dedup)
+    pub fn invalidate_cache(&mut self) {
+        self.data = [None; 8];
+    }
+
+    /// Whether out of bounds values saturate, see [`ValuePolicy::Saturate`]
+    fn saturates(&self) -> bool {
+        matches!(self.pipeline.value_policy, ValuePolicy::Saturate)
+    }
+
+    /// `value`, or if it is over the max value the max value with [`ValuePolicy::Saturate`]
+    fn in_bounds<S, P>(&self, value: u16) -> Result<u16, Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let max = self.resolution.max_code();
+        match value {
+            value if value <= max => Ok(value),
+            _ if self.saturates() => Ok(max),
+            _ => Err(self.resolution.out_of_bounds()),
+        }
+    }
+
+    /// Set the MIDI note that [`set_note`](Self::set_note) outputs as 0 V, note 0 unless set
+    pub fn set_zero_note(&mut self, note: u8) {
+        self.pipeline.zero_note = note;
+    }
+
+    /// Correct the codes [`set_note`](Self::set_note) writes to `channel` with `correction`, replacing an earlier one.
+    /// The driver keeps a reference, e.g. to a `static` built with the `const` [`VoctCorrection::new`], as it
+    /// does for the points of a [lookup table](Self::set_lut)
+    ///
+    /// ```
+    /// # use ad5328::{scale::VoctCorrection, Ad5328, Ad5328Config, Channel};
+    /// # use embedded_hal_mock::eh0::spi;
+    /// # let spi = spi::Mock::new(&[
+    /// #     spi::Transaction::write(vec![0x80, 0x0c]),
+    /// #     spi::Transaction::write(vec![0xa0, 0x01]),
+    /// # ]);
+    /// # let mut spi_check = spi.clone();
+    /// static VCO: VoctCorrection = match VoctCorrection::new(&[0, 2, 5, 9]) {
+    ///     Some(correction) => correction,
+    ///     None => panic!(),
+    /// };
+    /// let mut dac = Ad5328::init_without_enable(spi, Ad5328Config::default()).unwrap();
+    /// dac.set_voct_correction(Channel::A, &VCO);
+    /// # spi_check.done();
+    /// ```
+    pub fn set_voct_correction(&mut self, channel: Channel, correction: &'static VoctCorrection) {
+        self.pipeline.voct[channel] = Some(correction);
+    }
+
+    /// Go back to the ideal 1 V/oct codes for `channel`
+    pub fn clear_voct_correction(&mut self, channel: Channel) {
+        self.pipeline.voct[channel] = None;
+    }
+
+    /// The corrections installed per channel, e.g. to persist them with other calibration data
+    pub fn voct_corrections(&self) -> &ChannelMap<Option<&'static VoctCorrection>> {
+        &self.pipeline.voct
+    }
+
+    /// Data write of `value` to `channel` through the channel's lookup table, calibration, the master trim and
+    /// the channel's limits
+    fn set_command<S, P>(&self, channel: Channel, value: u16) -> Result<u16, Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let value = self.in_bounds(value)?;
+        self.pipeline_command(channel, value)
+    }
+
+    /// Data write as for [`set_command`](Self::set_command) of a `value` known to be in bounds
+    fn pipeline_command<S, P>(&self, channel: Channel, value: u16) -> Result<u16, Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let max = self.resolution.max_code();
+        let code = match self.pipeline.lut[channel] {
+            Some(lut) => lut.code(value, max),
+            None => value,
+        };
+        let code = self.pipeline.calibration[channel].apply(code, max);
+        let code = match (self.pipeline.tempco[channel], self.pipeline.temperature) {
+            (Some(tempco), Some(temperature)) => tempco.apply(code, max, temperature),
+            _ => code,
+        };
+        let code = self.pipeline.master_trim.apply(code, max);
+        self.limited_command(channel, code)
+    }
+
+    /// Data write of `code` to `channel` within the channel's limits, then inverted if the channel is
+    fn limited_command<S, P>(&self, channel: Channel, code: u16) -> Result<u16, Error<S, P>>
+    where
+        R: Resolution,
+    {
+        if self.pipeline.mirror_of[channel].is_some() {
+            return Err(Error::Mirror);
+        }
+        let code = match self.pipeline.limits[channel] {
+            Some((min, max)) if !(min..=max).contains(&code) => match self.pipeline.limit_policy {
+                LimitPolicy::Clamp => code.clamp(min, max),
+                LimitPolicy::Reject => return Err(Error::Limited(code)),
+            },
+            _ => code,
+        };
+        let code = if self.pipeline.inverted[channel] {
+            self.resolution.max_code() - code
+        } else {
+            code
+        };
+        command::set_code(self.physical_channel(channel), code, self.resolution.bits())
+            .ok_or_else(|| self.resolution.out_of_bounds())
+    }
+
+    /// Physical channel the logical `channel` is written to, see [`set_channel_map`](Self::set_channel_map)
+    pub fn physical_channel(&self, channel: Channel) -> Channel {
+        match &self.pipeline.channel_map {
+            Some(map) => map[channel],
+            None => channel,
+        }
+    }
+
+    /// Mask of physical channels for a mask of logical `channels`, replicas taking the entry of their primary
+    fn physical_mask(&self, channels: [bool; 8]) -> [bool; 8] {
+        let mut mask = [false; 8];
+        for n in 0..8 {
+            let channel = Channel::from(n as u8);
+            let source = self.pipeline.mirror_of[channel].unwrap_or(channel);
+            mask[usize::from(u8::from(self.physical_channel(channel)))] =
+                channels[usize::from(u8::from(source))];
+        }
+        mask
+    }
+
+    /// The primary `replica` mirrors, see [`mirror`](Self::mirror)
+    pub fn primary_of(&self, replica: Channel) -> Option<Channel> {
+        self.pipeline.mirror_of[replica]
+    }
+
+    /// Code written for `value` on `channel` after its lookup table, calibration, the master trim, limits and
+    /// inversion, or `None` if `value` is out of bounds or rejected by the limits.
+    /// This is exactly what [`set_channel`](Self::set_channel) sends, e.g. for host tools predicting the output
+    pub fn calibrated_code(&self, channel: Channel, value: u16) -> Option<u16>
+    where
+        R: Resolution,
+    {
+        let bits = self.resolution.bits();
+        self.set_command::<(), ()>(channel, value)
+            .ok()
+            .map(|cmd| (cmd & command::MAX_VALUE) >> (12 - bits))
+    }
+
+    /// Code last written to `channel`, after the value pipeline and including the writes to mirror replicas,
+    /// or `None` until the driver writes it. A [`reset`](Self::reset), a change of the
+    /// [channel map](Self::set_channel_map) and [`invalidate_cache`](Self::invalidate_cache) forget all codes
+    pub fn last_code(&self, channel: Channel) -> Option<u16>
+    where
+        R: Resolution,
+    {
+        let bits = self.resolution.bits();
+        self.data[usize::from(u8::from(channel))].map(|data| data >> (12 - bits))
+    }
+
+    /// Gain of the group of `channel` as last configured, always 1 with VDD as reference
+    fn gain(&self, channel: Channel) -> GAIN {
+        let (gain, vdd) = match self.physical_channel(channel).group() {
+            Group::AToD => (self.config.gain.0, self.config.vdd.0),
+            Group::EToH => (self.config.gain.1, self.config.vdd.1),
+        };
+        match vdd {
+            VDD::VddAsRef => GAIN::Gain0Vref,
+            VDD::ExternalRef => gain,
+        }
+    }
+
+    #[cfg(feature = "eh1")]
+    fn with_ldac<L2>(self, ldac_pin: L2) -> Ad53xx<SPI, EN, R, L2> {
+        Ad53xx {
+            spi: self.spi,
+            enable: self.enable,
+            cmd_buf: self.cmd_buf,
+            data: self.data,
+            powered_down: self.powered_down,
+            config: self.config,
+            staged: self.staged,
+            resolution: self.resolution,
+            ldac_pin,
+            pipeline: self.pipeline,
+        }
     }
+}
 
+impl<SPI, EN, R, S, P> Ad53xx<SPI, EN, R>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+{
     /// Initialize a new Ad5328 instance, while configuring it for the first time
     /// # Arguments
     ///
@@ -199,6 +1001,18 @@ where
     /// # Example
     ///
     /// ```
+    /// # use ad5328::{Ad5328, Ad5328Config, VDD};
+    /// # use embedded_hal_mock::eh0::{pin, spi};
+    /// # let spi = spi::Mock::new(&[
+    /// #     spi::Transaction::write(vec![0x80, 0x0f]),
+    /// #     spi::Transaction::write(vec![0xa0, 0x01]),
+    /// # ]);
+    /// # let toggle = [
+    /// #     pin::Transaction::set(pin::State::Low),
+    /// #     pin::Transaction::set(pin::State::High),
+    /// # ];
+    /// # let enable = pin::Mock::new(toggle.iter().chain(toggle.iter()));
+    /// # let (mut spi_check, mut enable_check) = (spi.clone(), enable.clone());
     /// // Get `spi` and `enable` from your embedded-hal
     /// let config = Ad5328Config {
     ///     // to use Vdd as the voltage reference for all channels of the DAC
@@ -206,55 +1020,2426 @@ where
     ///     ..Default::default()
     /// };
     /// let dac = Ad5328::init(spi, enable, config).unwrap();
+    /// # spi_check.done();
+    /// # enable_check.done();
     /// ```
-    pub fn init(spi: SPI, enable: EN, config: Ad5328Config) -> Result<Self, Error<S, P>> {
-        let mut ad5328 = Self {
-            spi,
-            enable,
-            cmd_buf: [0; 2],
-        };
+    pub fn init(spi: SPI, enable: EN, config: Ad5328Config) -> Result<Self, Error<S, P>>
+    where
+        R: Default,
+    {
+        let mut ad5328 = Self::new(spi, enable);
         ad5328.configure(config)?;
         Ok(ad5328)
     }
 
+    /// Initialize a new Ad5328 instance after waiting for the chip to power up, see [`Startup`].
+    /// If the startup sequence fails on the SPI bus it is retried once, after waiting again
+    pub fn init_with_delay<D: DelayUs<u32>>(
+        spi: SPI,
+        enable: EN,
+        config: Ad5328Config,
+        delay: &mut D,
+        startup: Startup,
+    ) -> Result<Self, Error<S, P>>
+    where
+        R: Default,
+    {
+        let mut ad5328 = Self::new(spi, enable);
+        delay.delay_us(startup.startup_us);
+        match ad5328.start(config, startup.full_reset) {
+            // most likely the chip wasn't ready yet
+            Err(Error::Spi(_)) => {
+                delay.delay_us(startup.startup_us);
+                ad5328.start(config, startup.full_reset)?;
+            }
+            result => result?,
+        }
+        Ok(ad5328)
+    }
+}
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+{
+    fn write(&mut self, cmd: u16) -> Result<(), Error<S, P>> {
+        #[cfg(feature = "log")]
+        log::trace!("ad5328: {}", command::Describe(cmd));
+        self.cmd_buf = command::to_bytes(cmd);
+        self.spi.write_frame(&mut self.enable, &self.cmd_buf)
+    }
+
+    fn start(&mut self, config: Ad5328Config, full_reset: bool) -> Result<(), Error<S, P>> {
+        if full_reset {
+            self.reset(true)?;
+        }
+        self.configure(config)
+    }
+
     /// (Re-)configure the already initialized Ad5328
     pub fn configure(&mut self, config: Ad5328Config) -> Result<(), Error<S, P>> {
-        for cmd in config.as_commands() {
+        let cmds = config.as_commands();
+        #[cfg(feature = "log")]
+        log::debug!(
+            "ad5328: configure {}, {}",
+            command::Describe(cmds[0]),
+            command::Describe(cmds[1])
+        );
+        for cmd in cmds {
             self.write(cmd)?;
         }
+        self.config = config;
+        if !matches!(config.ldac, LDAC::LdacHigh) {
+            // both other modes transfer the input registers
+            self.staged = [false; 8];
+        }
         Ok(())
     }
 
     /// Reset all DAC data. A full reset will also reset all control data
     pub fn reset(&mut self, full_reset: bool) -> Result<(), Error<S, P>> {
-        let cmd = if full_reset { 0xf000 } else { 0xe000 };
+        let cmd = command::reset(full_reset);
+        #[cfg(feature = "log")]
+        log::debug!("ad5328: {}", command::Describe(cmd));
         self.write(cmd)?;
+        self.staged = [false; 8];
+        self.data = [None; 8];
+        self.pipeline.setpoint = ChannelMap::default();
+        self.pipeline.millivolts = ChannelMap::default();
+        if full_reset {
+            self.powered_down = [false; 8];
+            self.config = Ad5328Config::POWER_ON;
+        }
+        Ok(())
+    }
+
+    /// Send the tracked state again, e.g. after a brownout of the chip's supply reset its registers: first the
+    /// configuration, then the power-down state, then the last code of every channel written so far, A first, see
+    /// [`last_code`](Self::last_code). Unless the LDAC mode is [`LDAC::LdacLow`], the mode is sent as latched and
+    /// the codes are then transferred to the outputs by an LDAC update, unless a channel is
+    /// [staged](Self::stage_channel) and waiting for its [`commit`](Self::commit).
+    ///
+    /// On an error the stages before the failed one are done, and the whole call can simply be repeated
+    pub fn reapply_state(&mut self) -> Result<(), ReapplyError<S, P>>
+    where
+        L: LdacPulse<P>,
+    {
+        let error = |stage| move |error| ReapplyError { stage, error };
+        let mut config = self.config;
+        if matches!(config.ldac, LDAC::LdacSingleUpdate) {
+            // latched since the update, sending the update again would commit staged channels early
+            config.ldac = LDAC::LdacHigh;
+        }
+        for cmd in config.as_commands() {
+            self.write(cmd).map_err(error(ReapplyStage::Config))?;
+        }
+        self.power_down(self.powered_down)
+            .map_err(error(ReapplyStage::PowerDown))?;
+        for n in 0..8 {
+            self.rewrite(Channel::from(n))
+                .map_err(error(ReapplyStage::Value(n)))?;
+        }
+        if !matches!(self.config.ldac, LDAC::LdacLow) && !self.staged.contains(&true) {
+            self.latch().map_err(error(ReapplyStage::Update))?;
+        }
         Ok(())
     }
 
     /// Power down the channels that are set to true in their respective position
-    /// Channel A -> 0, ..., Channel H -> 7
+    /// Channel A -> 0, ..., Channel H -> 7, as logical channels
     pub fn power_down(&mut self, channels: [bool; 8]) -> Result<(), Error<S, P>> {
-        let mut cmd = 0xc000;
-        for (n, &power_down) in channels.iter().enumerate() {
-            cmd |= (if power_down { 1 } else { 0 }) << n;
-        }
+        let cmd = command::power_down(self.physical_mask(channels));
+        #[cfg(feature = "log")]
+        log::debug!("ad5328: {}", command::Describe(cmd));
         self.write(cmd)?;
+        self.powered_down = channels;
+        Ok(())
+    }
+
+    /// Set the value for a DAC channel. Max value is the [`max_code`](Resolution::max_code) of `R`,
+    /// 255 for the AD5308, 1023 for the AD5318 and 4095 for the AD5328, larger values fail or saturate by the
+    /// [`ValuePolicy`]
+    ///
+    /// With a lookup table installed through [`set_lut`](Self::set_lut), `value` is mapped through it first, then
+    /// corrected by the channel's [`Calibration`] if one is set through [`set_calibration`](Self::set_calibration),
+    /// then by the [master trim](Self::set_master_trim), and finally held within the channel's
+    /// [limits](Self::set_limits) and [inverted](Self::set_inverted), as are the values of all other
+    /// `set_channel*` methods. Only the limits and inversion apply to [`set_channel_raw`](Self::set_channel_raw)
+    pub fn set_channel(&mut self, channel: Channel, value: u16) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        self.update_channel(channel, value)?;
         Ok(())
     }
 
-    /// Set the value for a DAC channel. Max value is 4095
-    pub fn set_channel(&mut self, channel: Channel, value: u16) -> Result<(), Error<S, P>> {
-        if value > 4095 {
-            return Err(Error::Oob);
+    /// As [`set_channel`](Self::set_channel), telling whether the frame was sent, or skipped by
+    /// [deduplication](Self::with_dedup) or a [deadband](Self::set_deadband), held by a [mute](Self::mute), or
+    /// left pending by a [rate limit](Self::set_rate_limit)
+    pub fn update_channel(&mut self, channel: Channel, value: u16) -> Result<Written, Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let cmd = self.set_command(channel, value)?;
+        self.write_data(channel, Some(value), cmd)
+    }
+
+    /// As [`update_channel`](Self::update_channel), sending the frame to the channel and its replicas even if
+    /// [deduplication](Self::with_dedup) or a [deadband](Self::set_deadband) would skip it. A
+    /// [mute](Self::mute) or [rate limit](Self::set_rate_limit) still applies, see the returned [`Written`]
+    pub fn force_channel(&mut self, channel: Channel, value: u16) -> Result<Written, Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let cmd = self.set_command(channel, value)?;
+        for n in 0..8 {
+            let target = Channel::from(n);
+            let source = self.pipeline.mirror_of[target].unwrap_or(target);
+            if u8::from(source) == u8::from(channel) {
+                self.data[usize::from(n)] = None;
+            }
         }
-        let cmd = channel.as_u16() | value;
-        self.write(cmd)?;
+        self.write_data(channel, Some(value), cmd)
+    }
+
+    /// As [`set_channel`](Self::set_channel), masking `value` to the width of the part instead of rejecting it,
+    /// e.g. for the output of a wrapping phase accumulator
+    pub fn set_channel_masked(&mut self, channel: Channel, value: u16) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let value = value & self.resolution.max_code();
+        let cmd = self.pipeline_command(channel, value)?;
+        self.write_data(channel, Some(value), cmd)?;
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Set the code of a DAC channel as is, bypassing the channel's lookup table, e.g. to measure the
+    /// transfer function of the output stage. Max value as for [`set_channel`](Self::set_channel),
+    /// the channel's limits and inversion still apply
+    pub fn set_channel_raw(&mut self, channel: Channel, code: u16) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let code = self.in_bounds(code)?;
+        let cmd = self.limited_command(channel, code)?;
+        self.write_data(channel, None, cmd)?;
+        Ok(())
+    }
+
+    /// Map all values written to `channel` through `points`, see [`Lut`](scale::Lut), replacing an earlier table.
+    /// The points need to be non-decreasing, at least two and at most the max value, otherwise [`Error::Lut`]
+    pub fn set_lut(&mut self, channel: Channel, points: &'static [u16]) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let lut = scale::Lut::new(points, self.resolution.max_code()).ok_or(Error::Lut)?;
+        self.pipeline.lut[channel] = Some(lut);
+        Ok(())
+    }
+
+    /// Write the values of `channel` as they are again
+    pub fn remove_lut(&mut self, channel: Channel) {
+        self.pipeline.lut[channel] = None;
+    }
+
+    /// Correct all values written to `channel` by `calibration`, after the lookup table, replacing an earlier one.
+    /// [`set_channel_raw`](Self::set_channel_raw) bypasses it as well
+    pub fn set_calibration(&mut self, channel: Channel, calibration: Calibration) {
+        self.pipeline.calibration[channel] = calibration;
+    }
+
+    /// Pass the codes of `channel` through unchanged again
+    pub fn clear_calibration(&mut self, channel: Channel) {
+        self.pipeline.calibration[channel] = Calibration::default();
+    }
+
+    /// The calibration of every channel, the default one where none is set
+    pub fn calibrations(&self) -> &ChannelMap<Calibration> {
+        &self.pipeline.calibration
+    }
+
+    /// Replace the calibration of every channel, e.g. with a [`CalibrationSet`](calibration::CalibrationSet)
+    /// read back from an EEPROM
+    pub fn set_calibrations(&mut self, calibrations: ChannelMap<Calibration>) {
+        self.pipeline.calibration = calibrations;
+    }
+
+    /// Correct the codes of all channels by `offset` and `gain_ppm` as for a [`Calibration`], e.g. for an error
+    /// of the shared reference. It applies after the calibration of the channel, both saturating at the ends of
+    /// the code range, and like it is bypassed by [`set_channel_raw`](Self::set_channel_raw)
+    pub fn set_master_trim(&mut self, offset: i16, gain_ppm: i32) {
+        self.pipeline.master_trim = Calibration { offset, gain_ppm };
+    }
+
+    /// Pass the codes of all channels through the master trim unchanged again
+    pub fn clear_master_trim(&mut self) {
+        self.pipeline.master_trim = Calibration::default();
+    }
+
+    /// The master trim, the default [`Calibration`] if none is set
+    pub fn master_trim(&self) -> Calibration {
+        self.pipeline.master_trim
+    }
+
+    /// Keep the codes written to `channel` within `min..=max`, replacing earlier limits. They bound the code
+    /// actually sent, after the lookup table and calibration, and codes outside are handled by the
+    /// [`LimitPolicy`]. Limits that are empty or over the max value fail like an out of bounds code
+    pub fn set_limits(&mut self, channel: Channel, min: u16, max: u16) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        if min > max || max > self.resolution.max_code() {
+            return Err(self.resolution.out_of_bounds());
+        }
+        self.pipeline.limits[channel] = Some((min, max));
+        Ok(())
+    }
+
+    /// Allow the full code range on `channel` again
+    pub fn clear_limits(&mut self, channel: Channel) {
+        self.pipeline.limits[channel] = None;
+    }
+
+    /// The limits of every channel as `(min, max)`, `None` where the full code range is allowed
+    pub fn limits(&self) -> &ChannelMap<Option<(u16, u16)>> {
+        &self.pipeline.limits
+    }
+
+    /// Set what happens to codes outside the limits of their channel, [`LimitPolicy::Clamp`] unless set
+    pub fn set_limit_policy(&mut self, policy: LimitPolicy) {
+        self.pipeline.limit_policy = policy;
+    }
+
+    /// Write `max_code - code` instead of `code` to `channel`, e.g. for an inverting output stage. This is the
+    /// last step for every value, so calibrations and [limits](Self::set_limits) are still in terms of the
+    /// codes before the inversion
+    pub fn set_inverted(&mut self, channel: Channel, inverted: bool) {
+        self.pipeline.inverted[channel] = inverted;
+    }
+
+    /// Whether `channel` is inverted, see [`set_inverted`](Self::set_inverted)
+    pub fn is_inverted(&self, channel: Channel) -> bool {
+        self.pipeline.inverted[channel]
+    }
+
+    /// Round the values of `channel` between two codes by `rounding` in the conversions of
+    /// [`set_channel_millivolts`](Self::set_channel_millivolts), [`set_channel_ratio`](Self::set_channel_ratio),
+    /// [`set_channel_scaled_u16`](Self::set_channel_scaled_u16), [`set_channel_i16`](Self::set_channel_i16) and
+    /// the fixed-point setters, e.g. [`Rounding::TowardZero`] for a channel that must never overshoot.
+    /// [`Rounding::Nearest`] unless set
+    pub fn set_rounding(&mut self, channel: Channel, rounding: Rounding) {
+        self.pipeline.rounding[channel] = rounding;
+    }
+
+    /// Rounding of `channel` set through [`set_rounding`](Self::set_rounding)
+    pub fn rounding(&self, channel: Channel) -> Rounding {
+        self.pipeline.rounding[channel]
+    }
+
+    /// Write the logical channels `Channel::A` to `Channel::H` to the physical channels `map[0]` to `map[7]`,
+    /// e.g. for board revisions with swapped outputs. All methods take logical channels, and
+    /// all per-channel settings such as calibrations and limits stay with the logical channel.
+    /// A map using a physical channel twice fails with [`Error::ChannelMap`], keeping the current one.
+    /// The [last codes](Self::last_code) are forgotten, they were written to other outputs
+    pub fn set_channel_map(&mut self, map: [Channel; 8]) -> Result<(), Error<S, P>> {
+        let used = map
+            .iter()
+            .fold(0u8, |used, &channel| used | 1 << u8::from(channel));
+        if used != 0xff {
+            return Err(Error::ChannelMap);
+        }
+        let identity = map
+            .iter()
+            .enumerate()
+            .all(|(n, &channel)| usize::from(u8::from(channel)) == n);
+        self.pipeline.channel_map = (!identity).then_some(ChannelMap(map));
+        self.data = [None; 8];
+        Ok(())
+    }
+
+    /// Write every code written to `primary` to `replica` as well, right after it, e.g. for paralleled outputs
+    /// that must never disagree. The replica carries the code as sent to the primary, without its own lookup
+    /// table, calibration, limits or inversion, and takes the power state of the primary in
+    /// [`power_down`](Self::power_down). Writes to the replica itself fail with [`Error::Mirror`], except that
+    /// [`set_all`](Self::set_all) and [`set_group`](Self::set_group) leave it to its primary.
+    ///
+    /// A channel mirroring itself, a primary that is a replica or a replica that is a primary, i.e. chains and
+    /// cycles, fail with [`Error::Mirror`]. An earlier primary of `replica` is replaced
+    pub fn mirror(&mut self, primary: Channel, replica: Channel) -> Result<(), Error<S, P>> {
+        if u8::from(primary) == u8::from(replica)
+            || self.pipeline.mirror_of[primary].is_some()
+            || self
+                .pipeline
+                .mirror_of
+                .0
+                .iter()
+                .any(|primary| primary.map(u8::from) == Some(u8::from(replica)))
+        {
+            return Err(Error::Mirror);
+        }
+        self.pipeline.mirror_of[replica] = Some(primary);
+        Ok(())
+    }
+
+    /// Stop mirroring to `replica`, it keeps its last code until written again
+    pub fn unmirror(&mut self, replica: Channel) {
+        self.pipeline.mirror_of[replica] = None;
+    }
+
+    /// Write the data write `cmd` of `channel` as [`send_data`](Self::send_data) does, unless its
+    /// [rate limit](Self::set_rate_limit) holds it back for a later [`poll`](Self::poll). `setpoint` is the
+    /// value `cmd` was made from by the value pipeline, `None` for a raw code
+    fn write_data(
+        &mut self,
+        channel: Channel,
+        setpoint: Option<u16>,
+        cmd: u16,
+    ) -> Result<Written, Error<S, P>>
+    where
+        R: Resolution,
+    {
+        self.write_data_via(channel, setpoint, cmd, &mut Self::write)
+    }
+
+    /// As [`write_data`](Self::write_data), handing the frames to `send` instead of the transport
+    pub(crate) fn write_data_via<F>(
+        &mut self,
+        channel: Channel,
+        setpoint: Option<u16>,
+        cmd: u16,
+        send: &mut F,
+    ) -> Result<Written, Error<S, P>>
+    where
+        R: Resolution,
+        F: FnMut(&mut Self, u16) -> Result<(), Error<S, P>>,
+    {
+        self.pipeline.setpoint[channel] =
+            setpoint.map(|value| value.min(self.resolution.max_code()));
+        self.pipeline.millivolts[channel] = None;
+        let code = cmd & command::MAX_VALUE;
+        let now = self.pipeline.now;
+        if let Some(throttle) = &mut self.pipeline.throttle[channel] {
+            if throttle.hold(now, code) {
+                return Ok(Written::Pending);
+            }
+        }
+        let written = self.send_data_via(channel, code, send)?;
+        if let Some(throttle) = &mut self.pipeline.throttle[channel] {
+            throttle.written(now, written);
+        }
+        Ok(written)
+    }
+
+    /// Write the data bits `code` to `channel`, then to its replicas, unless they are within the deadband of
+    /// `channel`
+    pub(crate) fn send_data(&mut self, channel: Channel, code: u16) -> Result<Written, Error<S, P>>
+    where
+        R: Resolution,
+    {
+        self.send_data_via(channel, code, &mut Self::write)
+    }
+
+    /// As [`send_data`](Self::send_data), handing the frames to `send`
+    fn send_data_via<F>(
+        &mut self,
+        channel: Channel,
+        code: u16,
+        send: &mut F,
+    ) -> Result<Written, Error<S, P>>
+    where
+        R: Resolution,
+        F: FnMut(&mut Self, u16) -> Result<(), Error<S, P>>,
+    {
+        if self.pipeline.muted[channel].is_none() && self.within_deadband(channel, code) {
+            return Ok(Written::Skipped);
+        }
+        let written = self.put_via(channel, code, send)?;
+        for replica in 0..8 {
+            let replica = Channel::from(replica);
+            if self.pipeline.mirror_of[replica].map(u8::from) == Some(u8::from(channel)) {
+                self.put_via(replica, code, send)?;
+            }
+        }
+        Ok(written)
+    }
+
+    /// Whether the data bits `code` are within the deadband of `channel` around its last code
+    fn within_deadband(&self, channel: Channel, code: u16) -> bool
+    where
+        R: Resolution,
+    {
+        let deadband = self.pipeline.deadband[channel];
+        let Some(last) = self.data[usize::from(u8::from(channel))] else {
+            return false;
+        };
+        let shift = 12 - self.resolution.bits();
+        deadband != 0 && (code >> shift).abs_diff(last >> shift) <= deadband
+    }
+
+    /// Write the data bits `code` to the physical channel of `channel`, unless it is muted, which holds them
+    /// for the unmute instead, or they are its last code and writes of those are skipped
+    fn put(&mut self, channel: Channel, code: u16) -> Result<Written, Error<S, P>> {
+        self.put_via(channel, code, &mut Self::write)
+    }
+
+    /// As [`put`](Self::put), handing the frame to `send`
+    fn put_via<F>(
+        &mut self,
+        channel: Channel,
+        code: u16,
+        send: &mut F,
+    ) -> Result<Written, Error<S, P>>
+    where
+        F: FnMut(&mut Self, u16) -> Result<(), Error<S, P>>,
+    {
+        if let Some(mute) = &mut self.pipeline.muted[channel] {
+            mute.held = Some(code);
+            return Ok(Written::Held);
+        }
+        if self.pipeline.dedup && self.data[usize::from(u8::from(channel))] == Some(code) {
+            return Ok(Written::Skipped);
+        }
+        send(
+            self,
+            command::set_channel_unchecked(self.physical_channel(channel), code),
+        )?;
+        self.data[usize::from(u8::from(channel))] = Some(code);
+        Ok(Written::Sent)
+    }
+
+    /// Write the data bits `code` to the physical channel of `channel`, recording them as its last code
+    fn put_unmuted(&mut self, channel: Channel, code: u16) -> Result<(), Error<S, P>> {
+        self.write(command::set_channel_unchecked(
+            self.physical_channel(channel),
+            code,
+        ))?;
+        self.data[usize::from(u8::from(channel))] = Some(code);
+        Ok(())
+    }
+
+    /// Write the code last written to `channel` again, to the physical channel it maps to now.
+    /// `false` without writing anything if the driver hasn't written the channel yet
+    pub(crate) fn rewrite(&mut self, channel: Channel) -> Result<bool, Error<S, P>> {
+        let Some(code) = self.data[usize::from(u8::from(channel))] else {
+            return Ok(false);
+        };
+        self.write(command::set_channel_unchecked(
+            self.physical_channel(channel),
+            code,
+        ))?;
+        Ok(true)
+    }
+
+    /// Write every logical channel to the physical channel of the same name again
+    pub fn clear_channel_map(&mut self) {
+        self.pipeline.channel_map = None;
+        self.data = [None; 8];
+    }
+
+    /// Set a DAC channel to an output of `mv` millivolts, rounded to nearest code unless set otherwise through
+    /// [`set_rounding`](Self::set_rounding), see [`scale::millivolts_to_code_rounded`].
+    /// `vref_mv` is the reference of the channel's group, its supply voltage if the group uses VDD as reference.
+    /// The gain is the group's as last configured through this driver, always 1 with VDD as reference.
+    /// An output over full scale fails like an out of bounds code
+    pub fn set_channel_millivolts(
+        &mut self,
+        channel: Channel,
+        mv: u32,
+        vref_mv: u32,
+    ) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let code = match scale::millivolts_to_code_rounded(
+            mv,
+            vref_mv,
+            self.gain(channel),
+            self.resolution.bits(),
+            self.pipeline.rounding[channel],
+        ) {
+            None if self.saturates() && vref_mv != 0 => Some(self.resolution.max_code()),
+            code => code,
+        }
+        .ok_or_else(|| self.resolution.out_of_bounds())?;
+        self.set_channel(channel, code)?;
+        self.pipeline.millivolts[channel] = Some(mv);
+        Ok(())
+    }
+
+    /// Write the channels whose last value was set in millivolts through
+    /// [`set_channel_millivolts`](Self::set_channel_millivolts) again for the reference `vref_mv` of `group`,
+    /// A or E first, so their outputs stay where they are as a measured reference drifts. The codes go through
+    /// the same rounding, calibration and limits as the original call. Channels last set to a code, e.g.
+    /// through [`set_channel`](Self::set_channel), are left alone. With a [channel map](Self::set_channel_map)
+    /// these are the logical channels on the physical channels of `group`. A setpoint over the new full scale
+    /// fails like an out of bounds code, leaving the channels after it as they were
+    pub fn update_vref(&mut self, group: Group, vref_mv: u32) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        for n in 0..8 {
+            let channel = Channel::from(n);
+            if self.physical_channel(channel).group() != group {
+                continue;
+            }
+            if let Some(mv) = self.pipeline.millivolts[channel] {
+                self.set_channel_millivolts(channel, mv, vref_mv)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set a DAC channel to the 1 V/oct pitch of MIDI `note`, relative to the note set with
+    /// [`set_zero_note`](Self::set_zero_note), see [`scale::note_to_code`], and corrected by the channel's
+    /// [`VoctCorrection`] if one is set. `vref_mv` and the gain are as for
+    /// [`set_channel_millivolts`](Self::set_channel_millivolts). Notes below the zero note or over full scale,
+    /// before or after the correction, fail like an out of bounds code, or saturate with [`ValuePolicy::Saturate`]
+    pub fn set_note(&mut self, channel: Channel, note: u8, vref_mv: u32) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let zero_note = self.pipeline.zero_note;
+        let bits = self.resolution.bits();
+        let max = self.resolution.max_code();
+        let semitones = note.saturating_sub(zero_note);
+        let code = match scale::note_to_code(note, zero_note, vref_mv, self.gain(channel), bits) {
+            None if self.saturates() && vref_mv != 0 => {
+                Some(if note < zero_note { 0 } else { max })
+            }
+            code => code,
+        }
+        .and_then(|code| match self.pipeline.voct[channel] {
+            Some(correction) if self.saturates() => {
+                let code = i32::from(code) + i32::from(correction.offset(semitones));
+                Some(code.clamp(0, i32::from(max)) as u16)
+            }
+            Some(correction) => correction.apply(code, semitones, bits),
+            None => Some(code),
+        })
+        .ok_or_else(|| self.resolution.out_of_bounds())?;
+        self.set_channel(channel, code)
+    }
+
+    /// Set a DAC channel to a `ratio` of full scale, see [`scale::ratio_to_code`]: 0.0 to 1.0 map onto the whole code range,
+    /// slightly out of range ratios are clamped and NaN fails like an out of bounds code.
+    /// [`set_channel_scaled_u16`](Self::set_channel_scaled_u16) takes the ratio as an integer instead
+    #[cfg(feature = "float")]
+    pub fn set_channel_ratio(&mut self, channel: Channel, ratio: f32) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let code = scale::ratio_to_code_rounded(
+            ratio,
+            self.resolution.bits(),
+            self.pipeline.rounding[channel],
+        )
+        .ok_or_else(|| self.resolution.out_of_bounds())?;
+        self.set_channel(channel, code)
+    }
+
+    /// Set a DAC channel from a full-range 16-bit `value`, 0 for code 0 and 65535 for the full-scale code,
+    /// see [`scale::scaled_u16_to_code`]. Every value is in bounds
+    pub fn set_channel_scaled_u16(
+        &mut self,
+        channel: Channel,
+        value: u16,
+    ) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let code = scale::scaled_u16_to_code_rounded(
+            value,
+            self.resolution.bits(),
+            self.pipeline.rounding[channel],
+        );
+        self.set_channel(channel, code)
+    }
+
+    /// Set a DAC channel from a bipolar `value` around midscale, `i16::MIN` for code 0, 0 for midscale and `i16::MAX`
+    /// for the full-scale code, see [`scale::i16_to_code`] for the rounding. Every value is in bounds
+    pub fn set_channel_i16(&mut self, channel: Channel, value: i16) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let code = scale::i16_to_code_rounded(
+            value,
+            self.resolution.bits(),
+            self.pipeline.rounding[channel],
+        );
+        self.set_channel(channel, code)
+    }
+
+    /// Set a DAC channel to a fraction of the reference, see [`scale::u0f16_to_code`]. Every value is in bounds
+    #[cfg(feature = "fixed")]
+    pub fn set_channel_fixed(
+        &mut self,
+        channel: Channel,
+        value: fixed::types::U0F16,
+    ) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let code = scale::u0f16_to_code_rounded(
+            value,
+            self.resolution.bits(),
+            self.pipeline.rounding[channel],
+        );
+        self.set_channel(channel, code)
+    }
+
+    /// Set a DAC channel to a bipolar fraction around midscale, -1 for code 0, see [`scale::i1f15_to_code`].
+    /// Every value is in bounds
+    #[cfg(feature = "fixed")]
+    pub fn set_channel_fixed_bipolar(
+        &mut self,
+        channel: Channel,
+        value: fixed::types::I1F15,
+    ) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let code = scale::i1f15_to_code_rounded(
+            value,
+            self.resolution.bits(),
+            self.pipeline.rounding[channel],
+        );
+        self.set_channel(channel, code)
+    }
+
+    /// Set several channels, one frame per pair in the order given, so a channel listed twice ends up with its last value.
+    /// All values are checked before the first frame is written, an out of bounds or rejected value fails without
+    /// touching the bus.
+    ///
+    /// With [`LDAC::LdacHigh`], the default, the outputs only change on the next LDAC pulse or command;
+    /// with [`LDAC::LdacLow`] each output updates as soon as its frame lands.
+    pub fn set_channels(&mut self, values: &[(Channel, u16)]) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        if let Some(error) = values
+            .iter()
+            .find_map(|&(channel, value)| self.set_command(channel, value).err())
+        {
+            return Err(error);
+        }
+        for &(channel, value) in values {
+            self.set_channel(channel, value)?;
+        }
+        Ok(())
+    }
+
+    /// Write `value` to the input register of `channel` only, to be transferred to the output by [`commit`](Self::commit)
+    /// together with all other staged channels. Fails with [`Error::Ldac`] if the configured LDAC mode is
+    /// [`LDAC::LdacLow`], which would update the output right away
+    pub fn stage_channel(&mut self, channel: Channel, value: u16) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        if matches!(self.config.ldac, LDAC::LdacLow) {
+            return Err(Error::Ldac);
+        }
+        self.set_channel(channel, value)?;
+        for n in 0..8 {
+            let staged = Channel::from(n as u8);
+            let source = self.pipeline.mirror_of[staged].unwrap_or(staged);
+            if u8::from(source) == u8::from(channel) {
+                self.staged[n] = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Transfer all staged channels to their outputs at once, with a pulse on the LDAC pin if the driver has one
+    /// and a single LDAC update command otherwise. Does nothing if no channel is staged
+    pub fn commit(&mut self) -> Result<(), Error<S, P>>
+    where
+        L: LdacPulse<P>,
+    {
+        if !self.staged.contains(&true) {
+            return Ok(());
+        }
+        #[cfg(feature = "log")]
+        log::debug!("ad5328: commit");
+        self.latch()
+    }
+
+    fn latch(&mut self) -> Result<(), Error<S, P>>
+    where
+        L: LdacPulse<P>,
+    {
+        match self.ldac_pin.pulse() {
+            Some(Ok(())) => {
+                self.staged = [false; 8];
+                Ok(())
+            }
+            Some(Err(e)) => Err(Error::LdacPin(e)),
+            None => self.update_outputs(),
+        }
+    }
+
+    /// Transfer all input registers to the DAC registers once, as a pulse on the LDAC pin would.
+    /// Every call writes exactly one [`LDAC::LdacSingleUpdate`] frame, the registers stay latched afterwards
+    pub fn update_outputs(&mut self) -> Result<(), Error<S, P>> {
+        self.set_ldac_mode(LDAC::LdacSingleUpdate)
+    }
+
+    /// Write the LDAC mode on its own, leaving GAIN, BUF and VDD as they are
+    pub fn set_ldac_mode(&mut self, ldac: LDAC) -> Result<(), Error<S, P>> {
+        let cmd = ldac.as_u16();
+        #[cfg(feature = "log")]
+        log::debug!("ad5328: {}", command::Describe(cmd));
+        self.write(cmd)?;
+        self.config.ldac = ldac;
+        if !matches!(ldac, LDAC::LdacHigh) {
+            self.staged = [false; 8];
+        }
+        Ok(())
+    }
+
+    /// Set several channels so that all their outputs change on the same LDAC update: one frame per pair,
+    /// as for [`set_channels`](Self::set_channels), then a pulse on the LDAC pin if the driver has one
+    /// and a single [`LDAC::LdacSingleUpdate`] command otherwise. An empty slice writes nothing.
+    ///
+    /// With the LDAC mode latched, the default, that is all. With [`LDAC::LdacLow`] the driver switches to
+    /// [`LDAC::LdacHigh`] first and back to [`LDAC::LdacLow`] after the update, costing two extra frames.
+    /// If a frame fails in between, the LDAC mode is left as last written
+    pub fn set_channels_synced(&mut self, values: &[(Channel, u16)]) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+        L: LdacPulse<P>,
+    {
+        if values.is_empty() {
+            return Ok(());
+        }
+        if let Some(error) = values
+            .iter()
+            .find_map(|&(channel, value)| self.set_command(channel, value).err())
+        {
+            return Err(error);
+        }
+        let restore = matches!(self.config.ldac, LDAC::LdacLow);
+        if restore {
+            self.set_ldac_mode(LDAC::LdacHigh)?;
+        }
+        self.set_channels(values)?;
+        self.latch()?;
+        if restore {
+            self.set_ldac_mode(LDAC::LdacLow)?;
+        }
+        Ok(())
+    }
+
+    /// Codes of a differential pair around the midscale code `2^(bits - 1)`, `mid + value` for `pos` and
+    /// `mid - value` for `neg`, each saturated at the rails
+    fn differential(
+        &self,
+        pos: Channel,
+        neg: Channel,
+        value: i16,
+    ) -> Result<[(Channel, u16); 2], Error<S, P>>
+    where
+        R: Resolution,
+    {
+        if u8::from(pos) == u8::from(neg) {
+            return Err(Error::Address);
+        }
+        let mid = i32::from(self.resolution.max_code()) / 2 + 1;
+        let code = |code: i32| code.clamp(0, i32::from(self.resolution.max_code())) as u16;
+        Ok([
+            (pos, code(mid + i32::from(value))),
+            (neg, code(mid - i32::from(value))),
+        ])
+    }
+
+    /// Drive `pos` and `neg` symmetrically around midscale, e.g. into a difference amplifier: `2048 + value` on
+    /// `pos` and `2048 - value` on `neg` for the AD5328, `pos` first, as for [`set_channels`](Self::set_channels).
+    /// Each leg saturates at the rails on its own. The full-scale code is one below `2 * 2048`, so the positive
+    /// side clips one code early: from `value = 2048` on `pos` stays at 4095 while `neg` reaches 0, and from
+    /// `value = -2048` on `neg` stays at 4095 while `pos` is 0. The same channel for both legs is an
+    /// [`Error::Address`]
+    pub fn set_differential(
+        &mut self,
+        pos: Channel,
+        neg: Channel,
+        value: i16,
+    ) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let pair = self.differential(pos, neg, value)?;
+        self.set_channels(&pair)
+    }
+
+    /// As [`set_differential`](Self::set_differential), but both legs change on the same LDAC update as with
+    /// [`set_channels_synced`](Self::set_channels_synced), so the pair never passes through a common-mode step
+    pub fn set_differential_synced(
+        &mut self,
+        pos: Channel,
+        neg: Channel,
+        value: i16,
+    ) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+        L: LdacPulse<P>,
+    {
+        let pair = self.differential(pos, neg, value)?;
+        self.set_channels_synced(&pair)
+    }
+
+    /// Channels staged since the last [`commit`](Self::commit), Channel A -> 0, ..., Channel H -> 7
+    pub fn staged(&self) -> [bool; 8] {
+        self.staged
+    }
+
+    /// Record the staged channels as transferred to their outputs by an LDAC pulse from outside the driver, e.g.
+    /// by `eh1::LdacBus::latch_all` for every DAC on a shared line, without writing or pulsing anything
+    pub fn mark_latched(&mut self) {
+        self.staged = [false; 8];
+    }
+
+    /// Write a snapshot of the desired outputs, `frame[0]` for channel A to `frame[7]` for channel H.
+    /// Only the `Some` entries are written, in channel order, and all of them are checked before the first is
+    pub fn write_frame(&mut self, frame: [Option<u16>; 8]) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        for (n, value) in frame.into_iter().enumerate() {
+            if let Some(value) = value {
+                self.set_command(Channel::from(n as u8), value)?;
+            }
+        }
+        for (n, value) in frame.into_iter().enumerate() {
+            if let Some(value) = value {
+                self.set_channel(Channel::from(n as u8), value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set every channel to `value`, A first, e.g. `set_all(0)` for a safe state or `set_all(2048)` for midscale of an AD5328.
+    /// An out of bounds value, or one rejected by the limits of any channel, fails with that channel as
+    /// [`StreamError::index`] before anything is written. On a bus error the index is the channel that failed,
+    /// so it equals the number of channels already written
+    pub fn set_all(&mut self, value: u16) -> Result<(), StreamError<S, P>>
+    where
+        R: Resolution,
+    {
+        let mut cmds = [None; 8];
+        for (index, cmd) in cmds.iter_mut().enumerate() {
+            let channel = Channel::from(index as u8);
+            if self.pipeline.mirror_of[channel].is_none() {
+                *cmd = Some(
+                    self.set_command(channel, value)
+                        .map_err(|error| StreamError { index, error })?,
+                );
+            }
+        }
+        for (index, cmd) in cmds.into_iter().enumerate() {
+            if let Some(cmd) = cmd {
+                self.write_data(Channel::from(index as u8), Some(value), cmd)
+                    .map_err(|error| StreamError { index, error })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the four channels of `group` to `value`, one frame each in the order of [`Group::channels`], so A or E
+    /// first. With a [channel map](Self::set_channel_map) these are the logical channels of the group. All four
+    /// values are checked before the first frame is written, so an out of bounds or rejected value fails without
+    /// touching the bus
+    pub fn set_group(&mut self, group: Group, value: u16) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        let mut cmds = [None; 4];
+        for (cmd, channel) in cmds.iter_mut().zip(group.channels()) {
+            if self.pipeline.mirror_of[channel].is_none() {
+                *cmd = Some(self.set_command(channel, value)?);
+            }
+        }
+        for (cmd, channel) in cmds.into_iter().zip(group.channels()) {
+            if let Some(cmd) = cmd {
+                self.write_data(channel, Some(value), cmd)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Release the SPI instance and enable pin, an LDAC pin is dropped unless taken back first
+    pub fn release(self) -> (SPI, EN) {
+        (self.spi, self.enable)
+    }
+}
+
+impl<SPI, EN, L, S, P> Ad53xx<SPI, EN, Bits12, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+{
+    /// Set a DAC channel to `value` as [`set_channel`](Self::set_channel) does, without a bounds check as
+    /// every [`DacValue`] is in bounds
+    pub fn set_channel_value(
+        &mut self,
+        channel: Channel,
+        value: DacValue,
+    ) -> Result<(), Error<S, P>> {
+        let cmd = self.pipeline_command(channel, value.into())?;
+        self.write_data(channel, Some(value.into()), cmd)?;
+        Ok(())
+    }
+}
+
+/// Channel indices 0..=7 are channels A..H, others are an [`Error::Address`].
+/// The power state of the other channels is the one last set through this driver
+impl<SPI, EN, R, L, S, P> MultiChannelDac for Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    type Error = Error<S, P>;
+
+    const CHANNELS: usize = 8;
+
+    fn set(&mut self, ch: u8, code: u16) -> Result<(), Error<S, P>> {
+        if ch >= 8 {
+            return Err(Error::Address);
+        }
+        self.set_channel(Channel::from(ch), code)
+    }
+
+    fn power(&mut self, ch: u8, on: bool) -> Result<(), Error<S, P>> {
+        if ch >= 8 {
+            return Err(Error::Address);
+        }
+        let mut channels = self.powered_down;
+        channels[usize::from(ch)] = !on;
+        self.power_down(channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Delays;
+    use core::convert::Infallible;
+    use embedded_hal::blocking::spi::Write;
+    use embedded_hal_mock::eh0::{pin, spi};
+
+    fn frames(enable_toggles: usize) -> pin::Mock {
+        let toggle = [
+            pin::Transaction::set(pin::State::Low),
+            pin::Transaction::set(pin::State::High),
+        ];
+        pin::Mock::new(toggle.iter().cycle().take(enable_toggles * 2))
+    }
+
+    #[test]
+    fn init_writes_config_commands() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+        ]);
+        let mut enable = frames(2);
+        Ad5328::init(spi.clone(), enable.clone(), Ad5328Config::default()).unwrap();
+        spi.done();
+        enable.done();
+    }
+
+    #[test]
+    fn set_channel_writes_msb_first() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x21, 0x23]),
+        ]);
+        let mut enable = frames(3);
+        let mut dac = Ad5328::init(spi.clone(), enable.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::C, 0x123).unwrap();
+        assert!(matches!(
+            dac.set_channel(Channel::C, 4096),
+            Err(Error::OobMax(4095))
+        ));
+        spi.done();
+        enable.done();
+    }
+
+    #[test]
+    fn no_enable_routes_every_command_to_spi() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x71, 0x00]),
+            spi::Transaction::write(vec![0xc0, 0x03]),
+            spi::Transaction::write(vec![0xe0, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::H, 0x100).unwrap();
+        dac.power_down([true, true, false, false, false, false, false, false])
+            .unwrap();
+        dac.reset(false).unwrap();
+        spi.done();
+    }
+
+    fn other_device_write<SPI: Write<u8>>(spi: &mut SPI) {
+        let _ = spi.write(&[0xaa, 0x55]);
+    }
+
+    #[test]
+    fn borrowed_bus_is_shared_between_dac_operations() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x10, 0x01]),
+            spi::Transaction::write(vec![0xaa, 0x55]),
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x1f, 0xff]),
+        ]);
+        let mut enable = frames(6);
+        let mut spi_check = spi.clone();
+
+        let mut dac =
+            Ad5328::init_borrowed(&mut spi, enable.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::B, 1).unwrap();
+        let (_, pin) = dac.release();
+
+        other_device_write(&mut spi);
+
+        let mut dac = Ad5328::init_borrowed(&mut spi, pin, Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::B, 4095).unwrap();
+
+        spi_check.done();
+        enable.done();
+    }
+
+    #[derive(Default)]
+    struct WordSpi(Vec<u16>);
+
+    impl Write<u16> for WordSpi {
+        type Error = Infallible;
+
+        fn write(&mut self, words: &[u16]) -> Result<(), Infallible> {
+            self.0.extend_from_slice(words);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn word_writes_match_byte_writes() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x80, 0x3c]),
+            spi::Transaction::write(vec![0xa0, 0x02]),
+            spi::Transaction::write(vec![0xc0, 0x81]),
+            spi::Transaction::write(vec![0x5a, 0xbc]),
+        ]);
+        let mut enable = frames(6);
+        let config = || Ad5328Config {
+            gain: (GAIN::Gain02Vref, GAIN::Gain02Vref),
+            ldac: LDAC::LdacSingleUpdate,
+            ..Default::default()
+        };
+        let power_down = [true, false, false, false, false, false, false, true];
+
+        let mut bytes = Ad5328::init(spi.clone(), enable.clone(), Ad5328Config::default()).unwrap();
+        bytes.configure(config()).unwrap();
+        bytes.power_down(power_down).unwrap();
+        bytes.set_channel(Channel::F, 0xabc).unwrap();
+        spi.done();
+        enable.done();
+
+        let mut words =
+            Ad5328::init_16bit(WordSpi::default(), NoEnable, Ad5328Config::default()).unwrap();
+        words.configure(config()).unwrap();
+        words.power_down(power_down).unwrap();
+        words.set_channel(Channel::F, 0xabc).unwrap();
+        let (Spi16(spi), _) = words.release();
+        assert_eq!(spi.0, [0x800c, 0xa001, 0x803c, 0xa002, 0xc081, 0x5abc]);
+    }
+
+    #[test]
+    fn errors_describe_themselves_and_their_source() {
+        use core::error::Error as _;
+
+        let spi: Error<std::io::Error, Infallible> = Error::Spi(std::io::Error::other("bus stuck"));
+        assert_eq!(spi.to_string(), "SPI bus error");
+        assert_eq!(spi.source().unwrap().to_string(), "bus stuck");
+
+        let oob: Error<std::io::Error, Infallible> = Error::Oob;
+        assert_eq!(oob.to_string(), "channel value out of bounds");
+        assert!(oob.source().is_none());
+        let stream = StreamError {
+            index: 4,
+            error: oob,
+        };
+        assert_eq!(
+            stream.to_string(),
+            "channel value out of bounds at sample 4"
+        );
+    }
+
+    #[test]
+    fn errors_coerce_to_error_trait_objects() {
+        let spi: Error<std::io::Error, std::io::Error> =
+            Error::Spi(std::io::Error::other("bus stuck"));
+        let error: &dyn core::error::Error = &spi;
+        assert_eq!(error.source().unwrap().to_string(), "bus stuck");
+        let oob: Error<std::io::Error, std::io::Error> = Error::Oob;
+        let boxed: Box<dyn core::error::Error + Send + Sync> = Box::new(oob);
+        assert_eq!(boxed.to_string(), "channel value out of bounds");
+        let stream = StreamError {
+            index: 2,
+            error: Error::<std::io::Error, std::io::Error>::Oob,
+        };
+        let _: &dyn core::error::Error = &stream;
+    }
+
+    /// Fails the first `failures` writes, logging the ones that go through
+    struct FlakySpi {
+        failures: usize,
+        written: Vec<[u8; 2]>,
+    }
+
+    impl Write<u8> for FlakySpi {
+        type Error = ();
+
+        fn write(&mut self, words: &[u8]) -> Result<(), ()> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(());
+            }
+            self.written.push([words[0], words[1]]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn init_with_delay_retries_once_after_a_bus_error() {
+        let startup = Startup {
+            startup_us: 500,
+            full_reset: true,
+        };
+        let spi = FlakySpi {
+            failures: 1,
+            written: Vec::new(),
+        };
+        let mut delay = Delays::default();
+        let dac =
+            Ad5328::init_with_delay(spi, NoEnable, Ad5328Config::default(), &mut delay, startup)
+                .unwrap();
+        assert_eq!(delay.0, [500, 500]);
+        let (spi, NoEnable) = dac.release();
+        assert_eq!(spi.written, [[0xf0, 0x00], [0x80, 0x0c], [0xa0, 0x01]]);
+
+        let spi = FlakySpi {
+            failures: 2,
+            written: Vec::new(),
+        };
+        let result =
+            Ad5328::init_with_delay(spi, NoEnable, Ad5328Config::default(), &mut delay, startup);
+        assert!(matches!(result, Err(Error::Spi(()))));
+    }
+
+    fn park<D: MultiChannelDac>(dac: &mut D) -> Result<(), D::Error> {
+        for ch in 0..D::CHANNELS as u8 {
+            dac.set(ch, 0)?;
+        }
+        dac.power(1, false)?;
+        dac.power(6, false)?;
+        dac.power(1, true)
+    }
+
+    #[test]
+    fn multi_channel_dac_keeps_other_channels_powered_down() {
+        let mut expected = vec![
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+        ];
+        expected.extend((0..8).map(|ch| spi::Transaction::write(vec![ch << 4, 0x00])));
+        expected.extend([
+            spi::Transaction::write(vec![0xc0, 0x02]),
+            spi::Transaction::write(vec![0xc0, 0x42]),
+            spi::Transaction::write(vec![0xc0, 0x40]),
+        ]);
+        let mut spi = spi::Mock::new(&expected);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        park(&mut dac).unwrap();
+        assert!(matches!(dac.set(8, 0), Err(Error::Address)));
+        assert!(matches!(dac.power(8, true), Err(Error::Address)));
+        spi.done();
+    }
+
+    #[test]
+    fn reapply_state_sends_config_power_and_codes() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x11, 0x23]),
+            spi::Transaction::write(vec![0x7f, 0xff]),
+            spi::Transaction::write(vec![0xc0, 0x04]),
+            // reapplied
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0xc0, 0x04]),
+            spi::Transaction::write(vec![0x11, 0x23]),
+            spi::Transaction::write(vec![0x7f, 0xff]),
+            spi::Transaction::write(vec![0xa0, 0x02]),
+            // latched after the update, and no update with a channel staged
+            spi::Transaction::write(vec![0x00, 0x05]),
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0xc0, 0x04]),
+            spi::Transaction::write(vec![0x00, 0x05]),
+            spi::Transaction::write(vec![0x11, 0x23]),
+            spi::Transaction::write(vec![0x7f, 0xff]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::B, 0x123).unwrap();
+        dac.set_channel(Channel::H, 0xfff).unwrap();
+        dac.power_down([false, false, true, false, false, false, false, false])
+            .unwrap();
+        dac.reapply_state().unwrap();
+        dac.stage_channel(Channel::A, 5).unwrap();
+        dac.reapply_state().unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn reapply_state_reports_the_failed_stage() {
+        /// Fails every frame after the first `budget`
+        struct Budget(usize);
+
+        impl Write<u8> for Budget {
+            type Error = ();
+
+            fn write(&mut self, _: &[u8]) -> Result<(), ()> {
+                self.0 = self.0.checked_sub(1).ok_or(())?;
+                Ok(())
+            }
+        }
+
+        let stages = [
+            ReapplyStage::Config,
+            ReapplyStage::Config,
+            ReapplyStage::PowerDown,
+            ReapplyStage::Value(3),
+            ReapplyStage::Value(6),
+            ReapplyStage::Update,
+        ];
+        for (budget, stage) in stages.into_iter().enumerate() {
+            let mut dac =
+                Ad5328::init_without_enable(Budget(usize::MAX), Ad5328Config::default()).unwrap();
+            dac.set_channel(Channel::D, 1).unwrap();
+            dac.set_channel(Channel::G, 2).unwrap();
+            dac.spi.0 = budget;
+            let err = dac.reapply_state().unwrap_err();
+            assert_eq!(err.stage, stage);
+            assert!(matches!(err.error, Error::Spi(())));
+        }
+        let err = ReapplyError::<(), ()> {
+            stage: ReapplyStage::Value(3),
+            error: Error::Spi(()),
+        };
+        assert_eq!(
+            err.to_string(),
+            "SPI bus error reapplying the code of channel 3"
+        );
+    }
+
+    #[test]
+    fn dedup_skips_the_last_code_until_it_is_unknown() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x00, 0x64]),
+            spi::Transaction::write(vec![0x10, 0x64]),
+            spi::Transaction::write(vec![0x00, 0x65]),
+            // forced
+            spi::Transaction::write(vec![0x00, 0x65]),
+            spi::Transaction::write(vec![0xe0, 0x00]),
+            spi::Transaction::write(vec![0x00, 0x65]),
+            spi::Transaction::write(vec![0x00, 0x65]),
+            spi::Transaction::write(vec![0x20, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default())
+            .unwrap()
+            .with_dedup(true);
+        assert!(dac.dedups());
+        assert_eq!(dac.update_channel(Channel::A, 100).unwrap(), Written::Sent);
+        assert_eq!(
+            dac.update_channel(Channel::A, 100).unwrap(),
+            Written::Skipped
+        );
+        dac.set_channels(&[(Channel::A, 100), (Channel::B, 100)])
+            .unwrap();
+        dac.write_frame([Some(101), Some(100), None, None, None, None, None, None])
+            .unwrap();
+        assert_eq!(dac.force_channel(Channel::A, 101).unwrap(), Written::Sent);
+        dac.reset(false).unwrap();
+        dac.set_channel(Channel::A, 101).unwrap();
+        dac.invalidate_cache();
+        dac.set_channel(Channel::A, 101).unwrap();
+        dac.set_channel(Channel::A, 101).unwrap();
+        dac.mute([Channel::C]).unwrap();
+        assert_eq!(dac.update_channel(Channel::C, 0).unwrap(), Written::Held);
+        spi.done();
+    }
+
+    #[test]
+    fn rounding_is_per_channel() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x00, 0x01]),
+            spi::Transaction::write(vec![0x10, 0x02]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_rounding(Channel::A, Rounding::TowardZero);
+        assert_eq!(dac.rounding(Channel::A), Rounding::TowardZero);
+        assert_eq!(dac.rounding(Channel::B), Rounding::Nearest);
+        // 1.5 and 0.5 codes
+        dac.set_channel_millivolts(Channel::A, 3, 8192).unwrap();
+        dac.set_channel_millivolts(Channel::B, 3, 8192).unwrap();
+        dac.set_channel_millivolts(Channel::A, 1, 8192).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn deadband_writes_a_staircase_sparsely() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            // rising by 1 per call, every third step gets through
+            spi::Transaction::write(vec![0x00, 0x64]),
+            spi::Transaction::write(vec![0x00, 0x67]),
+            spi::Transaction::write(vec![0x00, 0x6a]),
+            spi::Transaction::write(vec![0x00, 0x6d]),
+            // falling back
+            spi::Transaction::write(vec![0x00, 0x6a]),
+            spi::Transaction::write(vec![0x00, 0x67]),
+            // forced
+            spi::Transaction::write(vec![0x00, 0x65]),
+            // no deadband
+            spi::Transaction::write(vec![0x00, 0x66]),
+            spi::Transaction::write(vec![0x00, 0x67]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_deadband(Channel::A, 2);
+        assert_eq!(dac.deadband(Channel::A), 2);
+        assert_eq!(dac.deadband(Channel::B), 0);
+        let written: Vec<_> = (100..=110)
+            .map(|value| dac.update_channel(Channel::A, value).unwrap())
+            .collect();
+        assert_eq!(written.iter().filter(|&&w| w == Written::Sent).count(), 4);
+        assert_eq!(dac.last_code(Channel::A), Some(109));
+        for value in (102..=108).rev() {
+            dac.set_channel(Channel::A, value).unwrap();
+        }
+        dac.force_channel(Channel::A, 101).unwrap();
+        dac.set_deadband(Channel::A, 0);
+        for value in [102, 103] {
+            dac.set_channel(Channel::A, value).unwrap();
+        }
+        spi.done();
+    }
+
+    /// Pseudo-random input for `arbitrary`, the same on every run
+    #[cfg(feature = "arbitrary")]
+    fn unstructured_inputs() -> impl Iterator<Item = Vec<u8>> {
+        let mut state = 0x2545_f491_u32;
+        (0..256).map(move |_| {
+            (0..16)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect()
+        })
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_configs_encode_to_control_and_ldac_words() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        for bytes in unstructured_inputs() {
+            let config = Ad5328Config::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            let [control, ldac] = config.as_commands();
+            assert_eq!(control & 0xffc0, 0x8000);
+            assert_eq!(ldac & 0xfffc, 0xa000);
+            assert_ne!(ldac, 0xa003);
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_channels_address_their_input_register() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        for bytes in unstructured_inputs() {
+            let mut u = Unstructured::new(&bytes);
+            let channel = Channel::arbitrary(&mut u).unwrap();
+            let value = u.int_in_range(0..=command::MAX_VALUE).unwrap();
+            let cmd = command::set_channel(channel, value).unwrap();
+            assert_eq!(cmd >> 12, u16::from(u8::from(channel)));
+            assert_eq!(cmd & 0x0fff, value);
+        }
+    }
+
+    #[test]
+    fn narrower_parts_shift_codes_into_the_high_bits() {
+        let config = [
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+        ];
+        let mut spi = spi::Mock::new(config.iter().chain(&[
+            spi::Transaction::write(vec![0x1f, 0xf0]),
+            spi::Transaction::write(vec![0x20, 0x10]),
+        ]));
+        let mut dac = Ad5308::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::B, 255).unwrap();
+        dac.set_channel(Channel::C, 1).unwrap();
+        assert!(matches!(
+            dac.set_channel(Channel::C, 256),
+            Err(Error::OobMax(255))
+        ));
+        spi.done();
+
+        let mut spi = spi::Mock::new(config.iter().chain(&[
+            spi::Transaction::write(vec![0x1f, 0xfc]),
+            spi::Transaction::write(vec![0x78, 0x00]),
+        ]));
+        let mut dac = Ad5318::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::B, 1023).unwrap();
+        MultiChannelDac::set(&mut dac, 7, 512).unwrap();
+        assert!(matches!(
+            dac.set_channel(Channel::C, 1024),
+            Err(Error::OobMax(1023))
+        ));
+        spi.done();
+    }
+
+    #[test]
+    fn runtime_resolution_shifts_codes_and_reports_the_maximum() {
+        for (resolution, max, frame) in [
+            (AnyResolution::Bits8, 255, [0x4f, 0xf0]),
+            (AnyResolution::Bits10, 1023, [0x4f, 0xfc]),
+            (AnyResolution::Bits12, 4095, [0x4f, 0xff]),
+        ] {
+            let mut spi = spi::Mock::new(&[
+                spi::Transaction::write(vec![0x80, 0x0c]),
+                spi::Transaction::write(vec![0xa0, 0x01]),
+                spi::Transaction::write(frame.to_vec()),
+            ]);
+            let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default())
+                .unwrap()
+                .with_resolution(resolution);
+            assert_eq!(resolution.max_code(), max);
+            dac.set_channel(Channel::E, max).unwrap();
+            let err = dac.set_channel(Channel::E, max + 1).unwrap_err();
+            assert!(matches!(err, Error::OobMax(m) if m == max));
+            assert_eq!(
+                err.to_string(),
+                format!("channel value over the maximum of {}", max)
+            );
+            spi.done();
+        }
+    }
+
+    #[test]
+    fn set_channels_checks_everything_before_writing() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x01, 0x00]),
+            spi::Transaction::write(vec![0x72, 0x00]),
+            spi::Transaction::write(vec![0x00, 0x05]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channels(&[]).unwrap();
+        assert!(matches!(
+            dac.set_channels(&[(Channel::A, 1), (Channel::B, 4096)]),
+            Err(Error::OobMax(4095))
+        ));
+        dac.set_channels(&[(Channel::A, 0x100), (Channel::H, 0x200), (Channel::A, 5)])
+            .unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn set_all_reports_the_channels_written_before_a_failure() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        let err = dac.set_all(4096).unwrap_err();
+        assert!(matches!(
+            err,
+            StreamError {
+                index: 0,
+                error: Error::OobMax(4095)
+            }
+        ));
+        spi.done();
+
+        /// Fails the fourth frame after the configuration
+        struct FailingSpi(Vec<[u8; 2]>);
+
+        impl Write<u8> for FailingSpi {
+            type Error = ();
+
+            fn write(&mut self, words: &[u8]) -> Result<(), ()> {
+                if self.0.len() == 5 {
+                    return Err(());
+                }
+                self.0.push([words[0], words[1]]);
+                Ok(())
+            }
+        }
+
+        let mut dac =
+            Ad5328::init_without_enable(FailingSpi(Vec::new()), Ad5328Config::default()).unwrap();
+        let err = dac.set_all(2048).unwrap_err();
+        assert!(matches!(
+            err,
+            StreamError {
+                index: 3,
+                error: Error::Spi(())
+            }
+        ));
+        let (spi, _) = dac.release();
+        assert_eq!(spi.0[2..], [[0x08, 0x00], [0x18, 0x00], [0x28, 0x00]]);
+    }
+
+    #[test]
+    fn write_frame_only_writes_present_entries() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x1a, 0xbc]),
+            spi::Transaction::write(vec![0x60, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.write_frame([None; 8]).unwrap();
+        let mut frame = [None; 8];
+        frame[1] = Some(0xabc);
+        frame[6] = Some(0);
+        dac.write_frame(frame).unwrap();
+        frame[2] = Some(4096);
+        assert!(matches!(dac.write_frame(frame), Err(Error::OobMax(4095))));
+        spi.done();
+    }
+
+    #[test]
+    fn staged_channels_are_committed_with_one_ldac_update() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x10, 0x10]),
+            spi::Transaction::write(vec![0x50, 0x20]),
+            spi::Transaction::write(vec![0xa0, 0x02]),
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.commit().unwrap();
+        dac.stage_channel(Channel::B, 0x10).unwrap();
+        dac.stage_channel(Channel::F, 0x20).unwrap();
+        assert!(matches!(
+            dac.stage_channel(Channel::F, 4096),
+            Err(Error::OobMax(4095))
+        ));
+        assert_eq!(
+            dac.staged(),
+            [false, true, false, false, false, true, false, false]
+        );
+        dac.commit().unwrap();
+        assert_eq!(dac.staged(), [false; 8]);
+        dac.commit().unwrap();
+        dac.configure(Ad5328Config {
+            ldac: LDAC::LdacLow,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(matches!(dac.stage_channel(Channel::A, 1), Err(Error::Ldac)));
+        spi.done();
+    }
+
+    #[test]
+    fn millivolts_follow_the_group_gain_and_reference() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x3d]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x08, 0x00]),
+            spi::Transaction::write(vec![0x44, 0x00]),
+            spi::Transaction::write(vec![0x4f, 0xff]),
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x48, 0x00]),
+        ]);
+        // A...D with VDD as reference, which overrides their gain of 2; E...H at gain 2
+        let mut dac = Ad5328::init_without_enable(
+            spi.clone(),
+            Ad5328Config {
+                gain: (GAIN::Gain02Vref, GAIN::Gain02Vref),
+                vdd: (VDD::VddAsRef, VDD::ExternalRef),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        dac.set_channel_millivolts(Channel::A, 2500, 5000).unwrap();
+        dac.set_channel_millivolts(Channel::E, 1250, 2500).unwrap();
+        dac.set_channel_millivolts(Channel::E, 4095, 2048).unwrap();
+        assert!(matches!(
+            dac.set_channel_millivolts(Channel::E, 4096, 2048),
+            Err(Error::OobMax(4095))
+        ));
+        dac.configure(Ad5328Config::default()).unwrap();
+        dac.set_channel_millivolts(Channel::E, 1250, 2500).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn reference_updates_rewrite_millivolt_setpoints() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x06, 0x66]),
+            spi::Transaction::write(vec![0x1c, 0xcd]),
+            spi::Transaction::write(vec![0x23, 0x33]),
+            spi::Transaction::write(vec![0x23, 0xe8]),
+            spi::Transaction::write(vec![0x43, 0x33]),
+            // +1% on A to D leaves C and E alone
+            spi::Transaction::write(vec![0x06, 0x56]),
+            spi::Transaction::write(vec![0x1c, 0xac]),
+            // 2000 mV is over full scale, B keeps its code
+            spi::Transaction::write(vec![0x08, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel_millivolts(Channel::A, 1000, 2500).unwrap();
+        dac.set_channel_millivolts(Channel::B, 2000, 2500).unwrap();
+        dac.set_channel_millivolts(Channel::C, 500, 2500).unwrap();
+        dac.set_channel(Channel::C, 1000).unwrap();
+        dac.set_channel_millivolts(Channel::E, 500, 2500).unwrap();
+        dac.update_vref(Group::AToD, 2525).unwrap();
+        assert!(matches!(
+            dac.update_vref(Group::AToD, 2000),
+            Err(Error::OobMax(4095))
+        ));
+        assert_eq!(dac.last_code(Channel::B), Some(3244));
+        spi.done();
+    }
+
+    #[test]
+    fn notes_are_relative_to_the_zero_note() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x1c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+            spi::Transaction::write(vec![0x03, 0x33]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+            spi::Transaction::write(vec![0x06, 0x66]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(
+            spi.clone(),
+            Ad5328Config {
+                gain: (GAIN::Gain02Vref, GAIN::Gain0Vref),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        dac.set_note(Channel::A, 0, 2500).unwrap();
+        dac.set_note(Channel::A, 12, 2500).unwrap();
+        assert!(matches!(
+            dac.set_note(Channel::A, 127, 2500),
+            Err(Error::OobMax(4095))
+        ));
+        dac.set_zero_note(36);
+        assert!(matches!(
+            dac.set_note(Channel::A, 35, 2500),
+            Err(Error::OobMax(4095))
+        ));
+        dac.set_note(Channel::A, 36, 2500).unwrap();
+        dac.set_note(Channel::A, 60, 2500).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn voct_corrections_only_apply_to_their_channel() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x03, 0x33]),
+            spi::Transaction::write(vec![0x13, 0x3b]),
+            spi::Transaction::write(vec![0x13, 0x33]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        static CORRECTION: VoctCorrection = match VoctCorrection::new(&[-4, 8]) {
+            Some(correction) => correction,
+            None => panic!(),
+        };
+        dac.set_voct_correction(Channel::B, &CORRECTION);
+        dac.set_note(Channel::A, 12, 5000).unwrap();
+        dac.set_note(Channel::B, 12, 5000).unwrap();
+        // code 0 - 4 is below the range
+        assert!(matches!(
+            dac.set_note(Channel::B, 0, 5000),
+            Err(Error::OobMax(4095))
+        ));
+        assert_eq!(dac.voct_corrections()[Channel::B], Some(&CORRECTION));
+        dac.clear_voct_correction(Channel::B);
+        dac.set_note(Channel::B, 12, 5000).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn luts_map_values_but_not_raw_codes() {
+        static SQUARED: [u16; 5] = [0, 256, 1024, 2304, 4095];
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            // 2048 is 2 / 4095 past the middle point
+            spi::Transaction::write(vec![0x24, 0x01]),
+            spi::Transaction::write(vec![0x28, 0x00]),
+            spi::Transaction::write(vec![0x08, 0x00]),
+            spi::Transaction::write(vec![0x24, 0x01]),
+            spi::Transaction::write(vec![0x28, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        assert!(matches!(
+            dac.set_lut(Channel::C, &[0, 4096]),
+            Err(Error::Lut)
+        ));
+        dac.set_lut(Channel::C, &SQUARED).unwrap();
+        dac.set_channel(Channel::C, 2048).unwrap();
+        dac.set_channel_raw(Channel::C, 2048).unwrap();
+        dac.set_channels(&[(Channel::A, 2048), (Channel::C, 2048)])
+            .unwrap();
+        dac.remove_lut(Channel::C);
+        dac.set_channel(Channel::C, 2048).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn calibration_saturates_and_clears_to_passthrough() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x58, 0x09]),
+            spi::Transaction::write(vec![0x5f, 0xff]),
+            spi::Transaction::write(vec![0x18, 0x00]),
+            spi::Transaction::write(vec![0x5f, 0xff]),
+            spi::Transaction::write(vec![0x58, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        let calibration = Calibration {
+            offset: 8,
+            gain_ppm: 500,
+        };
+        dac.set_calibration(Channel::F, calibration);
+        // 2048 * 1.0005 + 8 = 2057.02
+        assert_eq!(dac.calibrated_code(Channel::F, 2048), Some(2057));
+        dac.set_channel(Channel::F, 2048).unwrap();
+        dac.set_channel_scaled_u16(Channel::F, u16::MAX).unwrap();
+        dac.set_channel(Channel::B, 2048).unwrap();
+        assert_eq!(dac.calibrations()[Channel::F], calibration);
+        dac.clear_calibration(Channel::F);
+        for value in [0, 1, 2048, 4095] {
+            assert_eq!(dac.calibrated_code(Channel::F, value), Some(value));
+        }
+        assert_eq!(dac.calibrated_code(Channel::F, 4096), None);
+        dac.set_channel(Channel::F, 4095).unwrap();
+        dac.set_channel(Channel::F, 2048).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn master_trim_applies_after_the_channel_calibration() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x29, 0x3b]),
+            spi::Transaction::write(vec![0x48, 0xcd]),
+            spi::Transaction::write(vec![0x2f, 0xff]),
+            spi::Transaction::write(vec![0x40, 0x00]),
+            spi::Transaction::write(vec![0x28, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_calibration(
+            Channel::C,
+            Calibration {
+                offset: 100,
+                gain_ppm: 0,
+            },
+        );
+        dac.set_master_trim(0, 100_000);
+        assert_eq!(
+            dac.master_trim(),
+            Calibration {
+                offset: 0,
+                gain_ppm: 100_000
+            }
+        );
+        // (2048 + 100) * 1.1 = 2362.8, not 2048 * 1.1 + 100 = 2352.8
+        dac.set_channel(Channel::C, 2048).unwrap();
+        // 2048 * 1.1 = 2252.8
+        dac.set_channel(Channel::E, 2048).unwrap();
+        assert_eq!(dac.calibrated_code(Channel::C, 2048), Some(2363));
+
+        // saturates at both ends
+        dac.set_master_trim(-20, 1_000_000);
+        dac.set_channel(Channel::C, 4000).unwrap();
+        dac.set_channel(Channel::E, 5).unwrap();
+        assert_eq!(dac.calibrated_code(Channel::E, 4095), Some(4095));
+
+        dac.clear_master_trim();
+        dac.clear_calibration(Channel::C);
+        assert_eq!(dac.master_trim(), Calibration::default());
+        dac.set_channel(Channel::C, 2048).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn channel_map_translates_right_before_encoding() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x2c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x71, 0x23]),
+            spi::Transaction::write(vec![0x14, 0x56]),
+            spi::Transaction::write(vec![0xc0, 0x82]),
+            spi::Transaction::write(vec![0x70, 0x01]),
+            spi::Transaction::write(vec![0x20, 0x02]),
+            // with the gain of E..H
+            spi::Transaction::write(vec![0x73, 0xe8]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(
+            spi.clone(),
+            Ad5328Config {
+                gain: (GAIN::Gain0Vref, GAIN::Gain02Vref),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        use Channel::*;
+        assert!(matches!(
+            dac.set_channel_map([H, C, B, D, E, F, G, H]),
+            Err(Error::ChannelMap)
+        ));
+        assert_eq!(u8::from(dac.physical_channel(A)), 0);
+        dac.set_channel_map([H, C, B, D, E, F, G, A]).unwrap();
+        assert_eq!(u8::from(dac.physical_channel(A)), 7);
+        dac.set_channel(A, 0x123).unwrap();
+        dac.set_channel(C, 0x456).unwrap();
+        dac.power_down([true, false, true, false, false, false, false, false])
+            .unwrap();
+        dac.write_frame([Some(1), Some(2), None, None, None, None, None, None])
+            .unwrap();
+        dac.set_channel_millivolts(A, 1000, 2048).unwrap();
+        dac.set_channel_map([A, B, C, D, E, F, G, H]).unwrap();
+        assert!(dac.pipeline.channel_map.is_none());
+        dac.set_channel_map([H, C, B, D, E, F, G, A]).unwrap();
+        dac.clear_channel_map();
+        dac.set_channel(A, 0).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn value_policy_saturates_or_fails_without_a_frame() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        assert_eq!(dac.value_policy(), ValuePolicy::Strict);
+        assert!(matches!(
+            dac.set_channel(Channel::A, 4096),
+            Err(Error::OobMax(4095))
+        ));
+        assert!(matches!(
+            dac.set_channels(&[(Channel::A, 1), (Channel::B, 4096)]),
+            Err(Error::OobMax(4095))
+        ));
+        assert!(matches!(
+            dac.set_channel_millivolts(Channel::A, 5000, 2500),
+            Err(Error::OobMax(4095))
+        ));
+        spi.done();
+
+        let mut expected = vec![
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            spi::Transaction::write(vec![0x00, 0x01]),
+            spi::Transaction::write(vec![0x1f, 0xff]),
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            // below and far above the zero note
+            spi::Transaction::write(vec![0x00, 0x00]),
+            spi::Transaction::write(vec![0x0f, 0xff]),
+        ];
+        for channel in 0..8 {
+            expected.push(spi::Transaction::write(vec![channel << 4 | 0x0f, 0xff]));
+        }
+        let mut spi = spi::Mock::new(&expected);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default())
+            .unwrap()
+            .with_value_policy(ValuePolicy::Saturate);
+        dac.set_channel(Channel::A, 4096).unwrap();
+        dac.set_channel_raw(Channel::A, u16::MAX).unwrap();
+        dac.set_channels(&[(Channel::A, 1), (Channel::B, 4096)])
+            .unwrap();
+        dac.set_channel_millivolts(Channel::A, 5000, 2500).unwrap();
+        assert!(matches!(
+            dac.set_channel_millivolts(Channel::A, 5000, 0),
+            Err(Error::OobMax(4095))
+        ));
+        #[cfg(feature = "float")]
+        assert!(matches!(
+            dac.set_channel_ratio(Channel::A, f32::NAN),
+            Err(Error::OobMax(4095))
+        ));
+        dac.set_zero_note(24);
+        dac.set_note(Channel::A, 12, 2500).unwrap();
+        dac.set_note(Channel::A, 127, 2500).unwrap();
+        dac.set_all(5000).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn masked_values_wrap_within_the_data_bits() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x02, 0x34]),
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            spi::Transaction::write(vec![0x70, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel_masked(Channel::A, 0x1234).unwrap();
+        dac.set_channel_masked(Channel::A, u16::MAX).unwrap();
+        dac.set_channel_masked(Channel::H, 0x1000).unwrap();
+        assert!(matches!(
+            dac.set_channel(Channel::A, 0x1234),
+            Err(Error::OobMax(4095))
+        ));
+        spi.done();
+
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x1f, 0xf0]),
+        ]);
+        let mut dac = Ad5308::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel_masked(Channel::B, 0x1ff).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn dac_values_are_checked_once() {
+        assert_eq!(DacValue::new(4095), Some(DacValue::MAX));
+        assert_eq!(DacValue::new(4096), None);
+        assert_eq!(DacValue::new(0).map(u16::from), Some(0));
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x5f, 0xff]),
+            spi::Transaction::write(vec![0x5f, 0xff]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel_value(Channel::F, DacValue::MAX).unwrap();
+        dac.set_channel(Channel::F, 4095).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn set_group_checks_all_four_before_writing() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x48, 0x00]),
+            spi::Transaction::write(vec![0x58, 0x00]),
+            spi::Transaction::write(vec![0x68, 0x00]),
+            spi::Transaction::write(vec![0x78, 0x00]),
+            spi::Transaction::write(vec![0x00, 0x10]),
+            spi::Transaction::write(vec![0x10, 0x10]),
+            spi::Transaction::write(vec![0x20, 0x10]),
+            spi::Transaction::write(vec![0x30, 0x10]),
+        ]);
+        assert_eq!(Channel::D.group(), Group::AToD);
+        assert_eq!(Channel::E.group(), Group::EToH);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_group(Group::EToH, 2048).unwrap();
+        assert!(matches!(
+            dac.set_group(Group::AToD, 4096),
+            Err(Error::OobMax(4095))
+        ));
+        // rejected for D after A to C passed
+        dac.set_limits(Channel::D, 0, 15).unwrap();
+        dac.set_limit_policy(LimitPolicy::Reject);
+        assert!(matches!(
+            dac.set_group(Group::AToD, 16),
+            Err(Error::Limited(16))
+        ));
+        dac.clear_limits(Channel::D);
+        dac.set_group(Group::AToD, 16).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn differential_legs_saturate_on_their_own() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            // zero
+            spi::Transaction::write(vec![0x08, 0x00]),
+            spi::Transaction::write(vec![0x18, 0x00]),
+            // full positive and negative
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            spi::Transaction::write(vec![0x10, 0x00]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+            spi::Transaction::write(vec![0x1f, 0xff]),
+            // 2047 is the last value without clipping, 2048 clips the positive leg only
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            spi::Transaction::write(vec![0x10, 0x01]),
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            spi::Transaction::write(vec![0x10, 0x00]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+            spi::Transaction::write(vec![0x1f, 0xff]),
+            // synced
+            spi::Transaction::write(vec![0x08, 0x64]),
+            spi::Transaction::write(vec![0x17, 0x9c]),
+            spi::Transaction::write(vec![0xa0, 0x02]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        for value in [0, i16::MAX, i16::MIN, 2047, 2048, -2048] {
+            dac.set_differential(Channel::A, Channel::B, value).unwrap();
+        }
+        dac.set_differential_synced(Channel::A, Channel::B, 100)
+            .unwrap();
+        assert!(matches!(
+            dac.set_differential(Channel::A, Channel::A, 0),
+            Err(Error::Address)
+        ));
+        spi.done();
+    }
+
+    #[test]
+    fn mirrored_writes_reach_the_replica() {
+        let mut expected = vec![
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x01, 0x23]),
+            spi::Transaction::write(vec![0x21, 0x23]),
+            spi::Transaction::write(vec![0x00, 0x05]),
+            spi::Transaction::write(vec![0x20, 0x05]),
+            spi::Transaction::write(vec![0x03, 0xe8]),
+            spi::Transaction::write(vec![0x23, 0xe8]),
+            spi::Transaction::write(vec![0x10, 0x01]),
+            spi::Transaction::write(vec![0x00, 0x02]),
+            spi::Transaction::write(vec![0x20, 0x02]),
+            // set_all skips the replica, it follows A
+            spi::Transaction::write(vec![0x00, 0x07]),
+            spi::Transaction::write(vec![0x20, 0x07]),
+            spi::Transaction::write(vec![0x10, 0x07]),
+        ];
+        for channel in 3..8 {
+            expected.push(spi::Transaction::write(vec![channel << 4, 0x07]));
+        }
+        expected.extend([
+            // the replica takes the power state of its primary
+            spi::Transaction::write(vec![0xc0, 0x05]),
+            spi::Transaction::write(vec![0xc0, 0x00]),
+            spi::Transaction::write(vec![0x20, 0x01]),
+        ]);
+        let mut spi = spi::Mock::new(&expected);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        assert!(matches!(
+            dac.mirror(Channel::A, Channel::A),
+            Err(Error::Mirror)
+        ));
+        dac.mirror(Channel::A, Channel::C).unwrap();
+        assert_eq!(dac.primary_of(Channel::C).map(u8::from), Some(0));
+        for (primary, replica) in [(Channel::C, Channel::D), (Channel::B, Channel::A)] {
+            assert!(matches!(dac.mirror(primary, replica), Err(Error::Mirror)));
+        }
+        dac.set_channel(Channel::A, 0x123).unwrap();
+        dac.set_channel_raw(Channel::A, 5).unwrap();
+        dac.set_channel_millivolts(Channel::A, 1000, 4096).unwrap();
+        dac.set_channels(&[(Channel::B, 1), (Channel::A, 2)])
+            .unwrap();
+        for result in [
+            dac.set_channel(Channel::C, 1),
+            dac.set_channels(&[(Channel::B, 1), (Channel::C, 1)]),
+        ] {
+            assert!(matches!(result, Err(Error::Mirror)));
+        }
+        dac.set_all(7).unwrap();
+        let mut channels = [false; 8];
+        channels[0] = true;
+        dac.power_down(channels).unwrap();
+        dac.power_down([false, false, true, false, false, false, false, false])
+            .unwrap();
+        dac.unmirror(Channel::C);
+        assert!(dac.primary_of(Channel::C).is_none());
+        dac.set_channel(Channel::C, 1).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn inversion_is_the_last_step() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x3f, 0xff]),
+            spi::Transaction::write(vec![0x37, 0xff]),
+            spi::Transaction::write(vec![0x30, 0x00]),
+            // limits of 100..=3000 before the inversion
+            spi::Transaction::write(vec![0x3f, 0x9b]),
+            spi::Transaction::write(vec![0x34, 0x47]),
+            spi::Transaction::write(vec![0x34, 0x47]),
+            spi::Transaction::write(vec![0x38, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        assert!(!dac.is_inverted(Channel::D));
+        dac.set_inverted(Channel::D, true);
+        assert!(dac.is_inverted(Channel::D));
+        for value in [0, 2048, 4095] {
+            dac.set_channel(Channel::D, value).unwrap();
+        }
+        dac.set_limits(Channel::D, 100, 3000).unwrap();
+        dac.set_channel(Channel::D, 0).unwrap();
+        dac.set_channel(Channel::D, 4095).unwrap();
+        dac.set_channel_raw(Channel::D, 4095).unwrap();
+        assert_eq!(dac.calibrated_code(Channel::D, 2048), Some(2047));
+        dac.set_inverted(Channel::D, false);
+        dac.set_channel(Channel::D, 2048).unwrap();
+        spi.done();
+
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x3f, 0xf0]),
+            spi::Transaction::write(vec![0x37, 0xf0]),
+            spi::Transaction::write(vec![0x30, 0x00]),
+        ]);
+        let mut dac = Ad5308::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_inverted(Channel::D, true);
+        for value in [0, 128, 255] {
+            dac.set_channel(Channel::D, value).unwrap();
+        }
+        spi.done();
+    }
+
+    #[test]
+    fn limits_clamp_or_reject_the_emitted_code() {
+        let mut expected = vec![
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            // clamped
+            spi::Transaction::write(vec![0x10, 0x64]),
+            spi::Transaction::write(vec![0x10, 0x64]),
+            spi::Transaction::write(vec![0x1b, 0xb8]),
+            spi::Transaction::write(vec![0x1b, 0xb8]),
+            spi::Transaction::write(vec![0x1b, 0xb8]),
+            spi::Transaction::write(vec![0x1b, 0xb8]),
+            // rejected
+            spi::Transaction::write(vec![0x1b, 0xb8]),
+            spi::Transaction::write(vec![0x10, 0x64]),
+        ];
+        for channel in 0..8 {
+            expected.push(spi::Transaction::write(vec![channel << 4 | 0x07, 0xd0]));
+        }
+        expected.push(spi::Transaction::write(vec![0x1f, 0xff]));
+        let mut spi = spi::Mock::new(&expected);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        assert!(matches!(
+            dac.set_limits(Channel::B, 5, 4),
+            Err(Error::OobMax(4095))
+        ));
+        assert!(matches!(
+            dac.set_limits(Channel::B, 0, 4096),
+            Err(Error::OobMax(4095))
+        ));
+        dac.set_limits(Channel::B, 100, 3000).unwrap();
+        assert_eq!(dac.limits()[Channel::B], Some((100, 3000)));
+        for value in [99, 100, 3000, 3001] {
+            dac.set_channel(Channel::B, value).unwrap();
+        }
+        dac.set_channel_raw(Channel::B, 4095).unwrap();
+        // the limits bound the calibrated code, 2995 + 10 is over them
+        dac.set_calibration(
+            Channel::B,
+            Calibration {
+                offset: 10,
+                gain_ppm: 0,
+            },
+        );
+        assert_eq!(dac.calibrated_code(Channel::B, 2990), Some(3000));
+        dac.set_channel(Channel::B, 2995).unwrap();
+        dac.clear_calibration(Channel::B);
+
+        dac.set_limit_policy(LimitPolicy::Reject);
+        assert!(matches!(
+            dac.set_channel(Channel::B, 3001),
+            Err(Error::Limited(3001))
+        ));
+        assert!(matches!(
+            dac.set_channel(Channel::B, 99),
+            Err(Error::Limited(99))
+        ));
+        assert_eq!(dac.calibrated_code(Channel::B, 99), None);
+        dac.set_channel(Channel::B, 3000).unwrap();
+        dac.set_channel(Channel::B, 100).unwrap();
+        // rejected for B before A is written
+        for value in [50, 3500] {
+            let err = dac.set_all(value).unwrap_err();
+            assert!(matches!(
+                err,
+                StreamError {
+                    index: 1,
+                    error: Error::Limited(_)
+                }
+            ));
+        }
+        dac.set_all(2000).unwrap();
+
+        dac.clear_limits(Channel::B);
+        assert_eq!(dac.limits()[Channel::B], None);
+        dac.set_channel(Channel::B, 4095).unwrap();
+        spi.done();
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn ratios_are_written_as_codes() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x18, 0x00]),
+            spi::Transaction::write(vec![0x1f, 0xff]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel_ratio(Channel::B, 0.5).unwrap();
+        dac.set_channel_ratio(Channel::B, 1.0001).unwrap();
+        assert!(matches!(
+            dac.set_channel_ratio(Channel::B, f32::NAN),
+            Err(Error::OobMax(4095))
+        ));
+        spi.done();
+    }
+
+    #[test]
+    fn scaled_u16_values_cover_the_part_range() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x3f, 0xff]),
+            spi::Transaction::write(vec![0x3f, 0xf0]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel_scaled_u16(Channel::D, u16::MAX).unwrap();
+        let mut dac = dac.with_resolution(AnyResolution::Bits8);
+        dac.set_channel_scaled_u16(Channel::D, u16::MAX).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn i16_values_are_centered_on_midscale() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x60, 0x00]),
+            spi::Transaction::write(vec![0x68, 0x00]),
+            spi::Transaction::write(vec![0x6f, 0xff]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel_i16(Channel::G, i16::MIN).unwrap();
+        dac.set_channel_i16(Channel::G, 0).unwrap();
+        dac.set_channel_i16(Channel::G, i16::MAX).unwrap();
+        spi.done();
+    }
+
+    #[cfg(feature = "fixed")]
+    #[test]
+    fn fixed_values_are_written_as_codes() {
+        use fixed::types::{I1F15, U0F16};
+
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x04, 0x00]),
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channel_fixed(Channel::A, U0F16::from_num(0.25))
+            .unwrap();
+        dac.set_channel_fixed(Channel::A, U0F16::MAX).unwrap();
+        dac.set_channel_fixed_bipolar(Channel::A, I1F15::NEG_ONE)
+            .unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn update_outputs_writes_one_frame_per_call() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0xa0, 0x02]),
+            spi::Transaction::write(vec![0xa0, 0x02]),
+            spi::Transaction::write(vec![0xa0, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.update_outputs().unwrap();
+        dac.update_outputs().unwrap();
+        dac.set_ldac_mode(LDAC::LdacLow).unwrap();
+        assert!(matches!(dac.stage_channel(Channel::A, 1), Err(Error::Ldac)));
+        spi.done();
+    }
+
+    #[test]
+    fn synced_channels_update_with_one_ldac_command() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x00, 0x10]),
+            spi::Transaction::write(vec![0x10, 0x20]),
+            spi::Transaction::write(vec![0x20, 0x30]),
+            spi::Transaction::write(vec![0x30, 0x40]),
+            spi::Transaction::write(vec![0xa0, 0x02]),
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x00]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x70, 0x50]),
+            spi::Transaction::write(vec![0xa0, 0x02]),
+            spi::Transaction::write(vec![0xa0, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_channels_synced(&[
+            (Channel::A, 0x10),
+            (Channel::B, 0x20),
+            (Channel::C, 0x30),
+            (Channel::D, 0x40),
+        ])
+        .unwrap();
+        dac.set_channels_synced(&[]).unwrap();
+        assert!(matches!(
+            dac.set_channels_synced(&[(Channel::A, 1), (Channel::B, 4096)]),
+            Err(Error::OobMax(4095))
+        ));
+        dac.configure(Ad5328Config {
+            ldac: LDAC::LdacLow,
+            ..Default::default()
+        })
+        .unwrap();
+        dac.set_channels_synced(&[(Channel::H, 0x50)]).unwrap();
+        assert!(matches!(dac.stage_channel(Channel::A, 1), Err(Error::Ldac)));
+        spi.done();
+    }
 }
@@ -0,0 +1,61 @@
+//! Linux host support through `linux-embedded-hal`, enabled with the `linux` feature.
+
+use std::path::Path;
+
+use linux_embedded_hal::{
+    gpio_cdev::{Chip, LineRequestFlags},
+    spidev::{SpiModeFlags, Spidev, SpidevOptions},
+    CdevPin, CdevPinError, SPIError, SpidevBus,
+};
+
+use crate::{eh1::SyncPin, Ad5328, Ad5328Config, Error};
+
+/// SPI clock used by [`Ad5328::open`]
+pub const SPI_SPEED_HZ: u32 = 1_000_000;
+
+/// GPIO chip used by [`Ad5328::open`] for the SYNC line
+pub const GPIO_CHIP: &str = "/dev/gpiochip0";
+
+/// The driver as returned by [`Ad5328::open`]
+pub type Ad5328Linux = Ad5328<SpidevBus, SyncPin<CdevPin>>;
+
+impl Ad5328<SpidevBus, SyncPin<CdevPin>> {
+    /// Open the DAC on a spidev bus with SYNC on line `cs_gpio_line` of [`GPIO_CHIP`], and configure it for the first time.
+    /// The bus runs in SPI mode 1 at [`SPI_SPEED_HZ`], with the kernel's own chip select disabled.
+    /// # Arguments
+    ///
+    /// * `spi_path` - spidev device, e.g. `/dev/spidev0.0`
+    /// * `cs_gpio_line` - GPIO line offset wired to SYNC
+    /// * `config` - The Ad5328 device configuration struct
+    pub fn open(
+        spi_path: impl AsRef<Path>,
+        cs_gpio_line: u32,
+        config: Ad5328Config,
+    ) -> Result<Self, Error<SPIError, CdevPinError>> {
+        Self::open_on_chip(spi_path, GPIO_CHIP, cs_gpio_line, config)
+    }
+
+    /// Like [`open`](Self::open), with SYNC on a line of `gpio_chip`
+    pub fn open_on_chip(
+        spi_path: impl AsRef<Path>,
+        gpio_chip: impl AsRef<Path>,
+        cs_gpio_line: u32,
+        config: Ad5328Config,
+    ) -> Result<Self, Error<SPIError, CdevPinError>> {
+        let mut spi = Spidev::open(spi_path).map_err(|e| Error::Spi(e.into()))?;
+        let options = SpidevOptions::new()
+            .bits_per_word(8)
+            .max_speed_hz(SPI_SPEED_HZ)
+            .mode(SpiModeFlags::SPI_MODE_1 | SpiModeFlags::SPI_NO_CS)
+            .build();
+        spi.configure(&options).map_err(|e| Error::Spi(e.into()))?;
+
+        let sync = Chip::new(gpio_chip)
+            .and_then(|mut chip| chip.get_line(cs_gpio_line))
+            .and_then(|line| line.request(LineRequestFlags::OUTPUT, 1, "ad5328-sync"))
+            .and_then(CdevPin::new)
+            .map_err(|e| Error::Pin(e.into()))?;
+
+        Self::new_on_bus(SpidevBus(spi), sync, config)
+    }
+}
@@ -0,0 +1,450 @@
+//! Closed-loop routines on outputs measured back through an ADC, e.g. outputs looped back to spare inputs of the
+//! microcontroller.
+
+use embedded_hal::blocking::delay::DelayUs;
+
+use crate::{
+    command, resolution::Resolution, scale::Calibration, Ad53xx, Channel, ChannelMap, Error, Group,
+    Transport, GAIN,
+};
+
+/// Measures the output of a channel, implemented by the application for the ADC its outputs are wired to
+pub trait Measure {
+    /// Error of a measurement
+    type Error;
+
+    /// Output of the logical `channel` in millivolts
+    fn measure_mv(&mut self, channel: Channel) -> Result<u32, Self::Error>;
+}
+
+/// Error of a routine that measures the outputs through a [`Measure`]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MeasureError<S, P, M> {
+    /// Error of the driver, [`Error::Conn`] for an output measured far from where it should be, e.g. with the
+    /// ADC input wired to another channel or not at all
+    Dac(Error<S, P>),
+    /// Error of the [`Measure`] implementation
+    Measure(M),
+}
+
+impl<S, P, M> From<Error<S, P>> for MeasureError<S, P, M> {
+    fn from(error: Error<S, P>) -> Self {
+        MeasureError::Dac(error)
+    }
+}
+
+impl<S, P, M> core::fmt::Display for MeasureError<S, P, M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MeasureError::Dac(error) => core::fmt::Display::fmt(error, f),
+            MeasureError::Measure(_) => f.write_str("measurement error"),
+        }
+    }
+}
+
+impl<S, P, M> core::error::Error for MeasureError<S, P, M>
+where
+    S: core::error::Error + 'static,
+    P: core::error::Error + 'static,
+    M: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            MeasureError::Dac(error) => core::error::Error::source(error),
+            MeasureError::Measure(error) => Some(error),
+        }
+    }
+}
+
+/// Result of a channel in a [`SelfTestReport`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelReport {
+    /// Whether every reading was within the tolerance
+    pub passed: bool,
+    /// Largest difference between a reading and the output expected for its code, in millivolts
+    pub worst_error_mv: u32,
+}
+
+/// Outcome of [`Ad53xx::self_test`], per logical channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SelfTestReport {
+    /// Result of each channel
+    pub channels: ChannelMap<ChannelReport>,
+}
+
+impl SelfTestReport {
+    /// Whether all eight channels passed
+    pub fn passed(&self) -> bool {
+        self.channels.0.iter().all(|channel| channel.passed)
+    }
+}
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Calibrate `channels` from their measured outputs. Each channel is set to a quarter and three quarters of
+    /// full scale, away from the ends where an offset clips, and measured `settle_us` after each write. The line
+    /// through the two readings gives the gain and offset that put the output of every code where it would be
+    /// on an ideal part, `vref_mv * gain * code / 2^bits` with the references of channels A to D and E to H as
+    /// for the pairs of [`Ad5328Config`](crate::Ad5328Config), which are installed as the
+    /// [calibrations](Self::set_calibration) of the channels and returned, e.g. for logging or to store as a
+    /// [`CalibrationSet`](crate::calibration::CalibrationSet).
+    ///
+    /// The test codes are written as they are, past the value pipeline, mutes and rate limits, and each channel
+    /// gets the code it had before again once measured, so the new calibration applies from its next write.
+    /// Powered down channels need to be powered up first. A reading more than an eighth of full scale off
+    /// fails with [`Error::Conn`], a reference of 0 with [`Error::Oob`]. Calibrations are only installed if all
+    /// channels succeed
+    pub fn auto_calibrate<M: Measure, D: DelayUs<u32>>(
+        &mut self,
+        channels: &[Channel],
+        measurer: &mut M,
+        delay: &mut D,
+        settle_us: u32,
+        vref_mv: (u32, u32),
+    ) -> Result<ChannelMap<Option<Calibration>>, MeasureError<S, P, M::Error>> {
+        let bits = self.resolution.bits();
+        let codes = [1 << (bits - 2), 3 << (bits - 2)];
+        let mut calibrations = ChannelMap::<Option<Calibration>>::default();
+        for &channel in channels {
+            let full_scale = self.full_scale_mv(channel, vref_mv);
+            if full_scale == 0 {
+                return Err(Error::Oob.into());
+            }
+            let readings = self.measure_codes(channel, codes, measurer, delay, settle_us)?;
+            for (code, mv) in codes.into_iter().zip(readings) {
+                let expected = (u64::from(code) * full_scale) >> bits;
+                if expected.abs_diff(u64::from(mv)) > full_scale / 8 {
+                    return Err(Error::Conn.into());
+                }
+            }
+            let span = i64::from(readings[1]) - i64::from(readings[0]);
+            if span <= 0 {
+                return Err(Error::Conn.into());
+            }
+            // readings of m1 and m2 mV at codes c1 and c2 correct by (c2 - c1) * full_scale / (2^bits * span)
+            // and an offset of c1 - m1 * (c2 - c1) / span, both rounded to nearest
+            let steps = i64::from(codes[1] - codes[0]);
+            let gain = (full_scale as i64 * steps * 1_000_000 + (span << (bits - 1)))
+                .div_euclid(span << bits);
+            let offset = (i64::from(codes[0]) * span - i64::from(readings[0]) * steps + span / 2)
+                .div_euclid(span);
+            calibrations[channel] = Some(Calibration {
+                offset: offset as i16,
+                gain_ppm: (gain - 1_000_000) as i32,
+            });
+        }
+        for (n, calibration) in calibrations.0.iter().enumerate() {
+            if let Some(calibration) = calibration {
+                self.pipeline.calibration[Channel::from(n as u8)] = *calibration;
+            }
+        }
+        Ok(calibrations)
+    }
+
+    /// Test all eight channels against their ideal outputs, e.g. at end of line. Each channel is set to 0, a
+    /// quarter, half, three quarters of full scale and the full-scale code, and measured `settle_us` after each
+    /// write. A reading passes if it is at most `tolerance_mv` from the output of its code on an ideal part,
+    /// `vref_mv * gain * code / 2^bits` rounded to nearest, with the references as for
+    /// [`auto_calibrate`](Self::auto_calibrate). Errors near the ends include the clipping of the output at the
+    /// rails.
+    ///
+    /// The test codes are written as they are, as for [`auto_calibrate`](Self::auto_calibrate). Powered down
+    /// channels are powered up for the test, then the codes and the power-down mask from before are restored,
+    /// also after an error of the bus or of `measurer` where possible. A reference of 0 fails with
+    /// [`Error::Oob`] before anything is written
+    pub fn self_test<M: Measure, D: DelayUs<u32>>(
+        &mut self,
+        measurer: &mut M,
+        delay: &mut D,
+        settle_us: u32,
+        vref_mv: (u32, u32),
+        tolerance_mv: u16,
+    ) -> Result<SelfTestReport, MeasureError<S, P, M::Error>> {
+        if vref_mv.0 == 0 || vref_mv.1 == 0 {
+            return Err(Error::Oob.into());
+        }
+        let bits = self.resolution.bits();
+        let codes = [
+            0,
+            1 << (bits - 2),
+            1 << (bits - 1),
+            3 << (bits - 2),
+            self.resolution.max_code(),
+        ];
+        let powered_down = self.powered_down;
+        let power_cycled = powered_down.contains(&true);
+        if power_cycled {
+            self.power_down([false; 8])?;
+        }
+        let mut report = SelfTestReport::default();
+        let tested = (0..8).try_for_each(|n| {
+            let channel = Channel::from(n);
+            let full_scale = self.full_scale_mv(channel, vref_mv);
+            let readings = self.measure_codes(channel, codes, measurer, delay, settle_us)?;
+            let worst_error_mv = codes
+                .into_iter()
+                .zip(readings)
+                .map(|(code, mv)| {
+                    let expected = (u64::from(code) * full_scale + (1 << (bits - 1))) >> bits;
+                    expected.abs_diff(u64::from(mv)).min(u64::from(u32::MAX)) as u32
+                })
+                .max()
+                .unwrap_or(0);
+            report.channels[channel] = ChannelReport {
+                passed: worst_error_mv <= u32::from(tolerance_mv),
+                worst_error_mv,
+            };
+            Ok::<_, MeasureError<S, P, M::Error>>(())
+        });
+        let restored = if power_cycled {
+            self.power_down(powered_down)
+        } else {
+            Ok(())
+        };
+        tested?;
+        restored?;
+        Ok(report)
+    }
+
+    /// Output of the full-scale code plus one LSB of `channel`, with the references of the two groups `vref_mv`
+    fn full_scale_mv(&self, channel: Channel, vref_mv: (u32, u32)) -> u64 {
+        let vref = match self.physical_channel(channel).group() {
+            Group::AToD => vref_mv.0,
+            Group::EToH => vref_mv.1,
+        };
+        u64::from(vref)
+            * match self.gain(channel) {
+                GAIN::Gain0Vref => 1,
+                GAIN::Gain02Vref => 2,
+            }
+    }
+
+    /// Measure the output of `channel` `settle_us` after writing each of `codes` as it is, then write the code
+    /// it had before again, also on an error
+    fn measure_codes<M: Measure, D: DelayUs<u32>, const N: usize>(
+        &mut self,
+        channel: Channel,
+        codes: [u16; N],
+        measurer: &mut M,
+        delay: &mut D,
+        settle_us: u32,
+    ) -> Result<[u32; N], MeasureError<S, P, M::Error>> {
+        let index = usize::from(u8::from(channel));
+        let previous = self.data[index];
+        let mut readings = [0; N];
+        let measured: Result<(), MeasureError<S, P, M::Error>> = readings
+            .iter_mut()
+            .zip(codes)
+            .try_for_each(|(reading, code)| {
+                let cmd =
+                    command::set_code(self.physical_channel(channel), code, self.resolution.bits())
+                        .ok_or(self.resolution.out_of_bounds())?;
+                self.put_unmuted(channel, cmd & command::MAX_VALUE)?;
+                delay.delay_us(settle_us);
+                *reading = measurer
+                    .measure_mv(channel)
+                    .map_err(MeasureError::Measure)?;
+                Ok(())
+            });
+        let restored = self.put_unmuted(channel, previous.unwrap_or(0));
+        self.data[index] = previous;
+        measured?;
+        restored?;
+        Ok(readings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Delays;
+    use crate::{Ad5328, Ad5328Config, NoEnable};
+    use embedded_hal::blocking::spi::Write;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// State of the outputs as left by the frames
+    #[derive(Default)]
+    struct Outputs {
+        codes: [u16; 8],
+        powered_down: u8,
+    }
+
+    type Codes = Rc<RefCell<Outputs>>;
+
+    struct Board(Codes);
+
+    impl Write<u8> for Board {
+        type Error = ();
+
+        fn write(&mut self, words: &[u8]) -> Result<(), ()> {
+            let word = u16::from_be_bytes([words[0], words[1]]);
+            let mut outputs = self.0.borrow_mut();
+            match word >> 14 {
+                0 | 1 => outputs.codes[usize::from(word >> 12)] = word & 0x0fff,
+                3 => outputs.powered_down = word as u8,
+                _ => {}
+            }
+            Ok(())
+        }
+    }
+
+    /// ADC reading the outputs with a gain error in ppm and an offset in mV per channel, at a 2500 mV reference,
+    /// and 0 mV for powered down channels
+    struct Adc {
+        codes: Codes,
+        errors: [(i64, i64); 8],
+        reads: usize,
+    }
+
+    impl Measure for Adc {
+        type Error = ();
+
+        fn measure_mv(&mut self, channel: Channel) -> Result<u32, ()> {
+            let n = usize::from(u8::from(channel));
+            let (gain_ppm, offset) = self.errors[n];
+            let outputs = self.codes.borrow();
+            self.reads += 1;
+            if outputs.powered_down & 1 << n != 0 {
+                return Ok(0);
+            }
+            let code = i64::from(outputs.codes[n]);
+            let uv = code * 2_500_000 * (1_000_000 + gain_ppm) / 4096 / 1_000_000 + offset * 1000;
+            Ok(((uv + 500) / 1000).max(0) as u32)
+        }
+    }
+
+    fn board(errors: [(i64, i64); 8]) -> (Ad5328<Board, NoEnable>, Adc) {
+        let codes = Codes::default();
+        let dac =
+            Ad5328::init_without_enable(Board(codes.clone()), Ad5328Config::default()).unwrap();
+        let adc = Adc {
+            codes,
+            errors,
+            reads: 0,
+        };
+        (dac, adc)
+    }
+
+    #[test]
+    fn calibrated_outputs_land_on_the_ideal_voltage() {
+        let mut errors = [(0, 0); 8];
+        errors[0] = (10_000, 5);
+        errors[5] = (-20_000, -12);
+        let (mut dac, mut adc) = board(errors);
+        let mut delay = Delays::default();
+        dac.set_channel(Channel::F, 100).unwrap();
+        let calibrations = dac
+            .auto_calibrate(
+                &[Channel::A, Channel::F],
+                &mut adc,
+                &mut delay,
+                10,
+                (2500, 2500),
+            )
+            .unwrap();
+        assert_eq!(
+            calibrations[Channel::A],
+            Some(Calibration {
+                offset: -7,
+                gain_ppm: -10_293
+            })
+        );
+        assert_eq!(calibrations[Channel::B], None);
+        assert_eq!(
+            dac.calibrations()[Channel::A],
+            calibrations[Channel::A].unwrap()
+        );
+        assert_eq!(delay.0, [10; 4]);
+        // the outputs are left as they were
+        assert_eq!(dac.last_code(Channel::A), None);
+        assert_eq!(dac.last_code(Channel::F), Some(100));
+        assert_eq!(adc.codes.borrow().codes[0], 0);
+        assert_eq!(adc.codes.borrow().codes[5], 100);
+
+        for (channel, value) in [(Channel::A, 2048), (Channel::F, 1024), (Channel::F, 3800)] {
+            dac.set_channel(channel, value).unwrap();
+            let ideal = i64::from(value) * 2500 / 4096;
+            let mv = i64::from(adc.measure_mv(channel).unwrap());
+            assert!((mv - ideal).abs() <= 1, "{} mV for {} mV", mv, ideal);
+        }
+    }
+
+    #[test]
+    fn readings_far_off_fail_without_installing_anything() {
+        let mut errors = [(0, 0); 8];
+        errors[0] = (10_000, 5);
+        errors[2] = (-1_000_000, 0);
+        let (mut dac, mut adc) = board(errors);
+        dac.set_channel(Channel::C, 1000).unwrap();
+        let result = dac.auto_calibrate(
+            &[Channel::A, Channel::C],
+            &mut adc,
+            &mut Delays::default(),
+            0,
+            (2500, 2500),
+        );
+        assert!(matches!(result, Err(MeasureError::Dac(Error::Conn))));
+        assert_eq!(dac.calibrations()[Channel::A], Calibration::default());
+        assert_eq!(adc.codes.borrow().codes[2], 1000);
+
+        let result = dac.auto_calibrate(
+            &[Channel::E],
+            &mut adc,
+            &mut Delays::default(),
+            0,
+            (2500, 0),
+        );
+        assert!(matches!(result, Err(MeasureError::Dac(Error::Oob))));
+        assert_eq!(adc.reads, 4);
+    }
+
+    #[test]
+    fn self_test_reports_each_channel_and_restores_the_outputs() {
+        let mut errors = [(0, 0); 8];
+        errors[1] = (0, 3);
+        errors[3] = (-1_000_000, 0);
+        errors[6] = (2_000, 0);
+        let (mut dac, mut adc) = board(errors);
+        dac.set_channel(Channel::C, 500).unwrap();
+        dac.power_down([false, false, false, false, false, false, false, true])
+            .unwrap();
+        let mut delay = Delays::default();
+        let report = dac
+            .self_test(&mut adc, &mut delay, 20, (2500, 2500), 3)
+            .unwrap();
+        let worst = report.channels.0.map(|channel| channel.worst_error_mv);
+        // 0.2 % high is 5 mV off at full scale, D is dead
+        assert_eq!(worst, [0, 3, 0, 2499, 0, 0, 5, 0]);
+        let passed = report.channels.0.map(|channel| channel.passed);
+        assert_eq!(passed, [true, true, true, false, true, true, false, true]);
+        assert!(!report.passed());
+        assert_eq!(delay.0, [20; 40]);
+        assert_eq!(adc.reads, 40);
+
+        let outputs = adc.codes.borrow();
+        assert_eq!(outputs.codes, [0, 0, 500, 0, 0, 0, 0, 0]);
+        assert_eq!(outputs.powered_down, 0x80);
+        assert_eq!(dac.last_code(Channel::C), Some(500));
+        assert_eq!(dac.last_code(Channel::A), None);
+    }
+
+    #[test]
+    fn self_test_needs_both_references() {
+        let (mut dac, mut adc) = board([(0, 0); 8]);
+        let result = dac.self_test(&mut adc, &mut Delays::default(), 0, (2500, 0), 10);
+        assert!(matches!(result, Err(MeasureError::Dac(Error::Oob))));
+        assert_eq!(adc.reads, 0);
+        let report = dac
+            .self_test(&mut adc, &mut Delays::default(), 0, (2500, 2500), 0)
+            .unwrap();
+        assert!(report.passed());
+    }
+}
@@ -0,0 +1,135 @@
+//! Muting of channels to a fixed code, with the codes written meanwhile held for the unmute.
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, Transport};
+
+/// Mute state of a muted channel
+#[derive(Clone, Copy)]
+pub(crate) struct Mute {
+    /// Data bits to restore, the last code before the mute or written since, `None` if there is none
+    pub(crate) held: Option<u16>,
+}
+
+impl<SPI, EN, R, L> Ad53xx<SPI, EN, R, L> {
+    /// Whether `channel` is muted, see [`mute`](Self::mute)
+    pub fn is_muted(&self, channel: Channel) -> bool {
+        self.pipeline.muted[channel].is_some()
+    }
+
+    /// The code [`mute`](Self::mute) writes to `channel`, 0 unless set
+    pub fn mute_code(&self, channel: Channel) -> u16 {
+        self.pipeline.mute_code[channel]
+    }
+}
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Set the code [`mute`](Self::mute) writes to `channel`, e.g. midscale for a bipolar output. It is written
+    /// as it is, without the value pipeline, and needs to be in bounds. A channel muted already keeps its code
+    pub fn set_mute_code(&mut self, channel: Channel, code: u16) -> Result<(), Error<S, P>> {
+        if code > self.resolution.max_code() {
+            return Err(self.resolution.out_of_bounds());
+        }
+        self.pipeline.mute_code[channel] = code;
+        Ok(())
+    }
+
+    /// Write the mute code of each of `channels`, keeping its [last code](Self::last_code) for
+    /// [`unmute`](Self::unmute). Until then the codes written to a muted channel are held instead of written, so
+    /// the unmute restores the latest one. Muting a muted channel does nothing.
+    ///
+    /// On an error the channels before the failed one are muted
+    pub fn mute(&mut self, channels: impl IntoIterator<Item = Channel>) -> Result<(), Error<S, P>> {
+        let shift = 12 - self.resolution.bits();
+        for channel in channels {
+            if self.is_muted(channel) {
+                continue;
+            }
+            let held = self.data[usize::from(u8::from(channel))];
+            self.put_unmuted(channel, self.pipeline.mute_code[channel] << shift)?;
+            self.pipeline.muted[channel] = Some(Mute { held });
+        }
+        Ok(())
+    }
+
+    /// Write the held code of each of `channels` that is muted again, or leave it at its mute code if it had none.
+    /// Unmuting a channel that isn't muted does nothing.
+    ///
+    /// On an error the failed channel and the ones after it stay muted
+    pub fn unmute(
+        &mut self,
+        channels: impl IntoIterator<Item = Channel>,
+    ) -> Result<(), Error<S, P>> {
+        for channel in channels {
+            let Some(mute) = self.pipeline.muted[channel] else {
+                continue;
+            };
+            if let Some(held) = mute.held {
+                self.put_unmuted(channel, held)?;
+            }
+            self.pipeline.muted[channel] = None;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Ad5308, Ad5328, Ad5328Config, Channel, Error};
+    use embedded_hal_mock::eh0::spi;
+
+    #[test]
+    fn unmute_restores_the_codes_before_and_during_the_mute() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x03, 0x20]),
+            spi::Transaction::write(vec![0x13, 0x84]),
+            // muted, B at midscale
+            spi::Transaction::write(vec![0x00, 0x00]),
+            spi::Transaction::write(vec![0x18, 0x00]),
+            // C isn't muted
+            spi::Transaction::write(vec![0x20, 0x05]),
+            // restored, B to its code written while muted
+            spi::Transaction::write(vec![0x03, 0x20]),
+            spi::Transaction::write(vec![0x10, 0x07]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_mute_code(Channel::B, 2048).unwrap();
+        dac.set_channel(Channel::A, 800).unwrap();
+        dac.set_channel(Channel::B, 900).unwrap();
+        dac.mute([Channel::A, Channel::B]).unwrap();
+        dac.mute([Channel::A]).unwrap();
+        assert!(dac.is_muted(Channel::A));
+        assert_eq!(dac.last_code(Channel::B), Some(2048));
+        dac.set_channel(Channel::C, 5).unwrap();
+        dac.set_channel(Channel::B, 7).unwrap();
+        dac.unmute([Channel::A, Channel::B, Channel::C]).unwrap();
+        dac.unmute([Channel::A]).unwrap();
+        assert!(!dac.is_muted(Channel::A));
+        assert_eq!(dac.snapshot().0[..3], [800, 7, 5]);
+        spi.done();
+    }
+
+    #[test]
+    fn channels_without_a_code_stay_at_their_mute_code() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x7f, 0xf0]),
+        ]);
+        let mut dac = Ad5308::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        assert!(matches!(
+            dac.set_mute_code(Channel::H, 256),
+            Err(Error::OobMax(255))
+        ));
+        dac.set_mute_code(Channel::H, 255).unwrap();
+        assert_eq!(dac.mute_code(Channel::H), 255);
+        dac.mute([Channel::H]).unwrap();
+        dac.unmute([Channel::H]).unwrap();
+        assert_eq!(dac.last_code(Channel::H), Some(255));
+        spi.done();
+    }
+}
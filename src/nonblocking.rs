@@ -0,0 +1,247 @@
+//! Non-blocking driver over `nb`-style SPI peripherals.
+
+use embedded_hal::{digital::v2::OutputPin, spi::FullDuplex};
+
+use crate::{command, Ad5328Config, Channel, Error};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Idle,
+    Send(usize),
+    Read(usize),
+    Release,
+}
+
+/// AD5328 driver over a `FullDuplex` SPI peripheral and an enable pin wired to SYNC.
+///
+/// The `_nb` methods advance a frame as far as the peripheral allows and return `WouldBlock` otherwise.
+/// Keep calling the same method with the same arguments until it returns `Ok` or an error. If it is called
+/// for a different frame while one is still in flight, the pending frame is finished first and `WouldBlock`
+/// is returned for the new one. On an SPI error mid-frame SYNC is released before the error is returned.
+pub struct Ad5328Nb<SPI, EN> {
+    spi: SPI,
+    enable: EN,
+    frame: [u8; 2],
+    step: Step,
+}
+
+impl<SPI, EN, S, P> Ad5328Nb<SPI, EN>
+where
+    SPI: FullDuplex<u8, Error = S>,
+    EN: OutputPin<Error = P>,
+{
+    /// Initialize a new Ad5328Nb instance, blocking until it is configured for the first time
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal compatible `FullDuplex` SPI instance
+    /// * `enable` - embedded-hal compatible GPIO pin
+    /// * `config` - The Ad5328 device configuration struct
+    pub fn init(spi: SPI, enable: EN, config: Ad5328Config) -> Result<Self, Error<S, P>> {
+        let mut ad5328 = Self {
+            spi,
+            enable,
+            frame: [0; 2],
+            step: Step::Idle,
+        };
+        for cmd in config.as_commands() {
+            nb::block!(ad5328.poll(cmd))?;
+        }
+        Ok(ad5328)
+    }
+
+    fn poll(&mut self, cmd: u16) -> nb::Result<(), Error<S, P>> {
+        let frame = command::to_bytes(cmd);
+        let pending = self.step != Step::Idle && self.frame != frame;
+        if self.step == Step::Idle {
+            self.frame = frame;
+        }
+        self.advance()?;
+        if pending {
+            Err(nb::Error::WouldBlock)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn advance(&mut self) -> nb::Result<(), Error<S, P>> {
+        loop {
+            self.step = match self.step {
+                Step::Idle => {
+                    self.enable.set_low().map_err(Error::Pin)?;
+                    Step::Send(0)
+                }
+                Step::Send(n) => {
+                    self.spi.send(self.frame[n]).map_err(|e| self.abort(e))?;
+                    Step::Read(n)
+                }
+                Step::Read(n) => {
+                    self.spi.read().map_err(|e| self.abort(e))?;
+                    if n == 0 {
+                        Step::Send(1)
+                    } else {
+                        Step::Release
+                    }
+                }
+                Step::Release => {
+                    self.step = Step::Idle;
+                    self.enable.set_high().map_err(Error::Pin)?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn abort(&mut self, e: nb::Error<S>) -> nb::Error<Error<S, P>> {
+        match e {
+            nb::Error::WouldBlock => nb::Error::WouldBlock,
+            nb::Error::Other(e) => {
+                self.step = Step::Idle;
+                // the SPI error is the one worth reporting
+                let _ = self.enable.set_high();
+                nb::Error::Other(Error::Spi(e))
+            }
+        }
+    }
+
+    /// Reset all DAC data. A full reset will also reset all control data
+    pub fn reset_nb(&mut self, full_reset: bool) -> nb::Result<(), Error<S, P>> {
+        self.poll(command::reset(full_reset))
+    }
+
+    /// Power down the channels that are set to true in their respective position
+    /// Channel A -> 0, ..., Channel H -> 7
+    pub fn power_down_nb(&mut self, channels: [bool; 8]) -> nb::Result<(), Error<S, P>> {
+        self.poll(command::power_down(channels))
+    }
+
+    /// Set the value for a DAC channel. Max value is 4095
+    pub fn set_channel_nb(&mut self, channel: Channel, value: u16) -> nb::Result<(), Error<S, P>> {
+        let cmd = command::set_channel(channel, value).ok_or(Error::OobMax(command::MAX_VALUE))?;
+        self.poll(cmd)
+    }
+
+    /// Release the SPI instance and enable pin
+    pub fn release(self) -> (SPI, EN) {
+        (self.spi, self.enable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Event, Log, LogPin};
+
+    /// Returns `WouldBlock` before every successful call, and fails the send of `fail_on`
+    struct SlowSpi {
+        log: Log,
+        ready: bool,
+        fail_on: Option<u8>,
+    }
+
+    impl SlowSpi {
+        fn poll(&mut self) -> nb::Result<(), ()> {
+            self.ready = !self.ready;
+            if self.ready {
+                Err(nb::Error::WouldBlock)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl FullDuplex<u8> for SlowSpi {
+        type Error = ();
+
+        fn send(&mut self, word: u8) -> nb::Result<(), ()> {
+            self.poll()?;
+            if self.fail_on == Some(word) {
+                return Err(nb::Error::Other(()));
+            }
+            self.log.push(Event::Bytes(vec![word]));
+            Ok(())
+        }
+
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            self.poll()?;
+            self.log.push(Event::Read);
+            Ok(0)
+        }
+    }
+
+    fn dac(fail_on: Option<u8>) -> (Ad5328Nb<SlowSpi, LogPin>, Log) {
+        let log = Log::default();
+        let spi = SlowSpi {
+            log: log.clone(),
+            ready: false,
+            fail_on,
+        };
+        let dac = Ad5328Nb::init(spi, LogPin(log.clone()), Ad5328Config::default()).unwrap();
+        log.events().clear();
+        (dac, log)
+    }
+
+    #[test]
+    fn would_block_resumes_the_frame() {
+        let (mut dac, log) = dac(None);
+        let mut polls = 0;
+        while let Err(nb::Error::WouldBlock) = dac.set_channel_nb(Channel::C, 0x123) {
+            polls += 1;
+        }
+        assert_eq!(polls, 4);
+        assert_eq!(
+            *log.events(),
+            [
+                Event::Sync(false),
+                Event::Bytes(vec![0x21]),
+                Event::Read,
+                Event::Bytes(vec![0x23]),
+                Event::Read,
+                Event::Sync(true),
+            ]
+        );
+    }
+
+    #[test]
+    fn pending_frame_finishes_before_a_new_one() {
+        let (mut dac, log) = dac(None);
+        assert!(matches!(
+            dac.set_channel_nb(Channel::A, 1),
+            Err(nb::Error::WouldBlock)
+        ));
+        let mut polls = 0;
+        while let Err(nb::Error::WouldBlock) = dac.reset_nb(false) {
+            polls += 1;
+        }
+        let sent: Vec<_> = log
+            .events()
+            .iter()
+            .filter_map(|event| match event {
+                Event::Bytes(b) => Some(b[0]),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sent, [0x00, 0x01, 0xe0, 0x00]);
+        // three more steps of the channel write, then one `WouldBlock` for its completion and four for the reset
+        assert_eq!(polls, 3 + 1 + 4);
+    }
+
+    #[test]
+    fn error_mid_frame_releases_sync() {
+        let (mut dac, log) = dac(Some(0x23));
+        let result = nb::block!(dac.set_channel_nb(Channel::C, 0x123));
+        assert!(matches!(result, Err(Error::Spi(()))));
+        assert_eq!(
+            *log.events(),
+            [
+                Event::Sync(false),
+                Event::Bytes(vec![0x21]),
+                Event::Read,
+                Event::Sync(true),
+            ]
+        );
+        assert!(matches!(
+            dac.set_channel_nb(Channel::H, 4096),
+            Err(nb::Error::Other(Error::OobMax(4095)))
+        ));
+    }
+}
@@ -0,0 +1,292 @@
+//! Low-frequency oscillators in fixed point, for LFOs without tables in flash.
+//!
+//! The phase is a 32-bit accumulator as for the [`WavetablePlayer`](crate::wavetable::WavetablePlayer), a full
+//! turn being one period, and the waveforms are bipolar in Q15 before [`Lfo::set_depth`] and
+//! [`Lfo::set_offset`] scale them to codes. All shapes are at their center at phase 0 and rise through it.
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, Transport};
+
+/// Full scale of the waveforms, `+1.0` in Q15
+const ONE: i32 = 1 << 15;
+
+/// First quarter of a sine, `sin(n / 64 * π / 2)` in Q15
+const SINE: [u16; 65] = [
+    0, 804, 1608, 2411, 3212, 4011, 4808, 5602, 6393, 7180, 7962, 8740, 9512, 10279, 11039, 11793,
+    12540, 13279, 14010, 14733, 15447, 16151, 16846, 17531, 18205, 18868, 19520, 20160, 20788,
+    21403, 22006, 22595, 23170, 23732, 24279, 24812, 25330, 25833, 26320, 26791, 27246, 27684,
+    28106, 28511, 28899, 29269, 29622, 29957, 30274, 30572, 30853, 31114, 31357, 31581, 31786,
+    31972, 32138, 32286, 32413, 32522, 32610, 32679, 32729, 32758, 32768,
+];
+
+/// Waveform of an [`Lfo`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Shape {
+    /// Sine, interpolated from a quarter-wave table
+    Sine,
+    /// Triangle, at its peak a quarter turn in and at its trough three quarters in
+    Triangle,
+    /// Rising sawtooth, dropping from its peak to its trough half a turn in
+    Saw,
+}
+
+impl Shape {
+    /// Value at `phase`, in Q15
+    fn wave(self, phase: u32) -> i32 {
+        match self {
+            Shape::Sine => {
+                let x = phase & ((1 << 30) - 1);
+                match phase >> 30 {
+                    0 => quarter_sine(x),
+                    1 => quarter_sine((1 << 30) - x),
+                    2 => -quarter_sine(x),
+                    _ => -quarter_sine((1 << 30) - x),
+                }
+            }
+            Shape::Triangle => {
+                // phase from the trough, rising for the first half
+                let from_trough = phase.wrapping_add(1 << 30);
+                if from_trough < 1 << 31 {
+                    (from_trough >> 15) as i32 - ONE
+                } else {
+                    ONE - ((from_trough - (1 << 31)) >> 15) as i32
+                }
+            }
+            Shape::Saw => (phase as i32) >> 16,
+        }
+    }
+
+    /// Whether the value rises after `phase`
+    fn rising(self, phase: u32) -> bool {
+        self == Shape::Saw || phase.wrapping_add(1 << 30) < 1 << 31
+    }
+
+    /// Phase where the shape is at `value`, on the rising side if `rising` and the shape has a falling one
+    fn phase_of(self, value: i32, rising: bool) -> u32 {
+        let phase = match self {
+            Shape::Sine => i64::from(value.signum()) * i64::from(quarter_asin(value.abs())),
+            Shape::Triangle => i64::from(value) << 15,
+            Shape::Saw => return (i64::from(value.min(ONE - 1)) << 16) as u32,
+        };
+        if rising {
+            phase as u32
+        } else {
+            ((1 << 31) - phase) as u32
+        }
+    }
+}
+
+/// Sine over the first quarter turn, `x` in `0..=2^30`
+fn quarter_sine(x: u32) -> i32 {
+    let index = (x >> 24) as usize;
+    let low = i64::from(SINE[index]);
+    let Some(&high) = SINE.get(index + 1) else {
+        return low as i32;
+    };
+    let fraction = i64::from(x & 0xff_ffff);
+    (low + (((i64::from(high) - low) * fraction + (1 << 23)) >> 24)) as i32
+}
+
+/// Inverse of [`quarter_sine`], `value` in `0..=ONE`
+fn quarter_asin(value: i32) -> u32 {
+    let index = SINE
+        .partition_point(|&s| i32::from(s) <= value)
+        .saturating_sub(1);
+    let Some(&high) = SINE.get(index + 1) else {
+        return 1 << 30;
+    };
+    let low = i32::from(SINE[index]);
+    let fraction = ((value - low) as u64) << 24;
+    ((index as u32) << 24) + (fraction / u64::from(high - SINE[index])) as u32
+}
+
+/// Low-frequency oscillator, one code per [`next_code`](Self::next_code) or [`tick_into`](Self::tick_into).
+/// The code is `offset + depth * wave`, rounded to nearest and saturated at 0 and the max code, so no depth
+/// and offset can take it out of range
+#[derive(Debug, Clone, Copy)]
+pub struct Lfo {
+    shape: Shape,
+    phase: u32,
+    increment: u32,
+    depth: u16,
+    offset: u16,
+    max: u16,
+}
+
+impl Lfo {
+    /// Oscillator of `shape` for codes up to `max`, e.g. 4095 for the AD5328, starting at phase 0 and standing
+    /// still until [`set_frequency`](Self::set_frequency). It swings over the whole range around midscale
+    pub fn new(shape: Shape, max: u16) -> Self {
+        let mid = max / 2 + 1;
+        Self {
+            shape,
+            phase: 0,
+            increment: 0,
+            depth: mid,
+            offset: mid,
+            max,
+        }
+    }
+
+    /// Advance the phase by `increment` every code, a turn being `2^32`, so at a tick rate of `f_tick` the
+    /// frequency is `f_tick * increment / 2^32`
+    pub fn set_frequency(&mut self, increment: u32) {
+        self.increment = increment;
+    }
+
+    /// Swing `depth` codes up and down from the offset
+    pub fn set_depth(&mut self, depth: u16) {
+        self.depth = depth;
+    }
+
+    /// Swing around the code `offset`
+    pub fn set_offset(&mut self, offset: u16) {
+        self.offset = offset;
+    }
+
+    /// The waveform
+    pub fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    /// Switch to `shape` at the point of it with the value of the next code, rising or falling as the current
+    /// shape does where it can, so the output carries on without a jump
+    pub fn set_shape(&mut self, shape: Shape) {
+        let value = self.shape.wave(self.phase);
+        let rising = self.shape.rising(self.phase);
+        self.phase = shape.phase_of(value, rising);
+        self.shape = shape;
+    }
+
+    /// Phase of the next code, a full turn is `2^32`
+    pub fn phase(&self) -> u32 {
+        self.phase
+    }
+
+    /// Move to `phase`, e.g. 0 to retrigger the oscillator
+    pub fn set_phase(&mut self, phase: u32) {
+        self.phase = phase;
+    }
+
+    /// The code at the current phase, advancing the phase
+    pub fn next_code(&mut self) -> u16 {
+        let code = self.code();
+        self.phase = self.phase.wrapping_add(self.increment);
+        code
+    }
+
+    /// Write the next code to `channel` through [`Ad53xx::set_channel`]. On an error the phase stays, so the
+    /// next tick writes the same code again
+    pub fn tick_into<SPI, EN, R, L, S, P>(
+        &mut self,
+        dac: &mut Ad53xx<SPI, EN, R, L>,
+        channel: Channel,
+    ) -> Result<(), Error<S, P>>
+    where
+        SPI: Transport<EN, SpiError = S, PinError = P>,
+        R: Resolution,
+    {
+        dac.set_channel(channel, self.code())?;
+        self.phase = self.phase.wrapping_add(self.increment);
+        Ok(())
+    }
+
+    fn code(&self) -> u16 {
+        let swing = i64::from(self.depth) * i64::from(self.shape.wave(self.phase));
+        let code = i64::from(self.offset) + ((swing + i64::from(ONE / 2)) >> 15);
+        code.clamp(0, i64::from(self.max)) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ad5328, Ad5328Config};
+    use embedded_hal_mock::eh0::spi;
+
+    const SHAPES: [Shape; 3] = [Shape::Sine, Shape::Triangle, Shape::Saw];
+
+    #[test]
+    fn shapes_hit_their_quarter_points() {
+        for (shape, expected) in [
+            (Shape::Sine, [2048, 4095, 2048, 0]),
+            (Shape::Triangle, [2048, 4095, 2048, 0]),
+            (Shape::Saw, [2048, 3072, 0, 1024]),
+        ] {
+            let mut lfo = Lfo::new(shape, 4095);
+            lfo.set_frequency(1 << 30);
+            let codes = [(); 4].map(|_| lfo.next_code());
+            assert_eq!(codes, expected, "{shape:?}");
+        }
+        let mut lfo = Lfo::new(Shape::Sine, 4095);
+        lfo.set_depth(1000);
+        lfo.set_phase(0x1555_5555);
+        // sin(30°)
+        assert_eq!(lfo.next_code(), 2548);
+        lfo.set_phase(0xd555_5555);
+        assert_eq!(lfo.next_code(), 2048 - 866);
+    }
+
+    #[test]
+    fn codes_saturate_for_any_depth_and_offset() {
+        for shape in SHAPES {
+            for (depth, offset) in [(u16::MAX, 0), (u16::MAX, u16::MAX), (3000, 4000), (0, 5000)] {
+                let mut lfo = Lfo::new(shape, 4095);
+                lfo.set_depth(depth);
+                lfo.set_offset(offset);
+                lfo.set_frequency(0x0123_4567);
+                let codes: Vec<u16> = (0..1000).map(|_| lfo.next_code()).collect();
+                assert!(codes.iter().all(|&code| code <= 4095));
+                if depth == u16::MAX {
+                    assert!(codes.contains(&4095));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shape_switches_carry_on_from_the_next_code() {
+        let increment = 1 << 24;
+        for from in SHAPES {
+            for to in SHAPES {
+                for start in (0..256u32).map(|n| n << 24 | 0x5a_5a5a) {
+                    let mut lfo = Lfo::new(from, 4095);
+                    lfo.set_frequency(increment);
+                    lfo.set_phase(start);
+                    let next = { lfo }.next_code();
+                    lfo.set_shape(to);
+                    let first = lfo.next_code();
+                    let second = lfo.next_code();
+                    assert!(
+                        first.abs_diff(next) <= 1,
+                        "{from:?} to {to:?} at {start:#x}"
+                    );
+                    // the steepest shape, the sine, moves 2π * 2048 / 256 per code, unless the saw drops
+                    let dropped = to == Shape::Saw && second < first;
+                    assert!(
+                        dropped || first.abs_diff(second) <= 51,
+                        "{from:?} to {to:?} at {start:#x}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tick_into_writes_the_codes() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x38, 0x00]),
+            spi::Transaction::write(vec![0x3f, 0xff]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        let mut lfo = Lfo::new(Shape::Triangle, 4095);
+        lfo.set_frequency(1 << 30);
+        lfo.tick_into(&mut dac, Channel::D).unwrap();
+        lfo.tick_into(&mut dac, Channel::D).unwrap();
+        assert_eq!(lfo.phase(), 1 << 31);
+        spi.done();
+    }
+}
@@ -0,0 +1,129 @@
+//! Blocking playback of pre-rendered samples out of one channel, paced by a delay.
+
+use embedded_hal::blocking::delay::DelayUs;
+
+use crate::{resolution::Resolution, Ad53xx, Channel, StreamError, Transport};
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Play `samples` out of `channel`, one frame every `sample_period_us`, the first one right away.
+    /// All samples are checked as for [`set_channel`](Self::set_channel) before the first frame; the
+    /// [`StreamError::index`] of a bus error is the sample that failed, where playback can be resumed
+    pub fn play_samples<D: DelayUs<u32>>(
+        &mut self,
+        channel: Channel,
+        samples: &[u16],
+        sample_period_us: u32,
+        delay: &mut D,
+    ) -> Result<(), StreamError<S, P>> {
+        self.play_samples_with(channel, samples, sample_period_us, delay, || true)?;
+        Ok(())
+    }
+
+    /// As [`play_samples`](Self::play_samples), calling `between` after each wait for the next sample, e.g. to
+    /// feed a watchdog or poll a cancel flag. Returning `false` stops playback before that sample is written.
+    /// Returns the number of samples played, `samples.len()` unless `between` stopped it
+    pub fn play_samples_with<D, F>(
+        &mut self,
+        channel: Channel,
+        samples: &[u16],
+        sample_period_us: u32,
+        delay: &mut D,
+        mut between: F,
+    ) -> Result<usize, StreamError<S, P>>
+    where
+        D: DelayUs<u32>,
+        F: FnMut() -> bool,
+    {
+        for (index, &sample) in samples.iter().enumerate() {
+            self.set_command::<S, P>(channel, sample)
+                .map_err(|error| StreamError { index, error })?;
+        }
+        for (index, &sample) in samples.iter().enumerate() {
+            if index > 0 {
+                delay.delay_us(sample_period_us);
+                if !between() {
+                    return Ok(index);
+                }
+            }
+            self.set_channel(channel, sample)
+                .map_err(|error| StreamError { index, error })?;
+        }
+        Ok(samples.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{recorded, Delays};
+    use crate::{Channel, Error, StreamError};
+
+    #[test]
+    fn samples_are_paced_one_per_period() {
+        let mut dac = recorded(usize::MAX);
+        let mut delay = Delays::default();
+        dac.play_samples(Channel::B, &[0, 0x800, 0xfff], 125, &mut delay)
+            .unwrap();
+        assert_eq!(dac.release().0.frames, [0x1000, 0x1800, 0x1fff]);
+        assert_eq!(delay.0, [125, 125]);
+    }
+
+    #[test]
+    fn samples_are_checked_up_front() {
+        let mut dac = recorded(0);
+        let err = dac
+            .play_samples(Channel::A, &[1, 2, 4096, 3], 10, &mut Delays::default())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            StreamError {
+                index: 2,
+                error: Error::OobMax(4095)
+            }
+        ));
+    }
+
+    #[test]
+    fn bus_errors_report_the_sample_to_resume_from() {
+        let mut dac = recorded(2);
+        let samples = [10, 20, 30, 40];
+        let err = dac
+            .play_samples(Channel::A, &samples, 10, &mut Delays::default())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            StreamError {
+                index: 2,
+                error: Error::Spi(())
+            }
+        ));
+        dac.spi.budget = usize::MAX;
+        dac.play_samples(
+            Channel::A,
+            &samples[err.index..],
+            10,
+            &mut Delays::default(),
+        )
+        .unwrap();
+        assert_eq!(dac.release().0.frames, [10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn between_can_stop_playback() {
+        let mut dac = recorded(usize::MAX);
+        let mut delay = Delays::default();
+        let mut calls = 0;
+        let played = dac
+            .play_samples_with(Channel::A, &[1, 2, 3, 4], 10, &mut delay, || {
+                calls += 1;
+                calls < 2
+            })
+            .unwrap();
+        assert_eq!(played, 2);
+        assert_eq!(dac.release().0.frames, [1, 2]);
+        assert_eq!(delay.0, [10, 10]);
+    }
+}
@@ -0,0 +1,242 @@
+//! The quad AD5304, AD5314 and AD5324, which speak the protocol of the octal parts with channels A...D only.
+//!
+//! Their control word only has the first group of GAIN, BUF and VDD bits, the second group is sent as 0.
+//! Power-down masks have four entries and only channels [`QuadChannel`] can address are accepted.
+
+use crate::{
+    resolution::{AnyResolution, Bits10, Bits12, Bits8, Resolution},
+    Ad5328Config, Ad53xx, Channel, Error, MultiChannelDac, Transport, BUF, GAIN, LDAC, VDD,
+};
+
+/// The DAC channels of a quad part (A..D)
+#[repr(u8)]
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum QuadChannel {
+    /// DAC Channel A
+    A,
+    /// DAC Channel B
+    B,
+    /// DAC Channel C
+    C,
+    /// DAC Channel D
+    D,
+}
+
+impl From<QuadChannel> for Channel {
+    fn from(chan: QuadChannel) -> Self {
+        Channel::from(chan as u8)
+    }
+}
+
+/// Configures the GAIN, BUF and VDD bits of all channels as well as LDAC behavior, see [`Ad5328Config`] for the bits
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct QuadConfig {
+    pub gain: GAIN,
+    pub buf: BUF,
+    pub vdd: VDD,
+    pub ldac: LDAC,
+}
+
+impl Default for QuadConfig {
+    fn default() -> Self {
+        Self {
+            gain: GAIN::Gain0Vref,
+            buf: BUF::Buffered,
+            vdd: VDD::ExternalRef,
+            ldac: LDAC::LdacHigh,
+        }
+    }
+}
+
+impl QuadConfig {
+    fn as_octal(&self) -> Ad5328Config {
+        Ad5328Config {
+            gain: (self.gain, GAIN::Gain0Vref),
+            buf: (self.buf, BUF::Unbuffered),
+            vdd: (self.vdd, VDD::ExternalRef),
+            ldac: self.ldac,
+        }
+    }
+}
+
+fn octal_mask(channels: [bool; 4]) -> [bool; 8] {
+    let [a, b, c, d] = channels;
+    [a, b, c, d, false, false, false, false]
+}
+
+/// Driver for the AD5304, AD5314 and AD5324, usually named through the [`Ad5304`], [`Ad5314`] and [`Ad5324`] aliases.
+/// `SPI` and `EN` are the same as for [`Ad53xx`], e.g. an `OutputPin`, [`NoEnable`](crate::NoEnable) or
+/// an `SpiDevice` with [`DeviceCs`](crate::eh1::DeviceCs)
+pub struct Ad53x4<SPI, EN, R> {
+    dac: Ad53xx<SPI, EN, R>,
+}
+
+/// Driver for the 8-bit AD5304
+pub type Ad5304<SPI, EN> = Ad53x4<SPI, EN, Bits8>;
+/// Driver for the 10-bit AD5314
+pub type Ad5314<SPI, EN> = Ad53x4<SPI, EN, Bits10>;
+/// Driver for the 12-bit AD5324
+pub type Ad5324<SPI, EN> = Ad53x4<SPI, EN, Bits12>;
+
+impl<SPI, EN, R, S, P> Ad53x4<SPI, EN, R>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+{
+    /// Initialize a new driver, while configuring the part for the first time
+    /// # Arguments
+    ///
+    /// * `spi` - embedded-hal compatible SPI instance
+    /// * `enable` - embedded-hal compatible GPIO pin
+    /// * `config` - The device configuration struct
+    pub fn init(spi: SPI, enable: EN, config: QuadConfig) -> Result<Self, Error<S, P>>
+    where
+        R: Default,
+    {
+        Ok(Self {
+            dac: Ad53xx::init(spi, enable, config.as_octal())?,
+        })
+    }
+
+    /// (Re-)configure the already initialized part
+    pub fn configure(&mut self, config: QuadConfig) -> Result<(), Error<S, P>> {
+        self.dac.configure(config.as_octal())
+    }
+
+    /// Reset all DAC data. A full reset will also reset all control data
+    pub fn reset(&mut self, full_reset: bool) -> Result<(), Error<S, P>> {
+        self.dac.reset(full_reset)
+    }
+
+    /// Power down the channels that are set to true in their respective position
+    /// Channel A -> 0, ..., Channel D -> 3
+    pub fn power_down(&mut self, channels: [bool; 4]) -> Result<(), Error<S, P>> {
+        self.dac.power_down(octal_mask(channels))
+    }
+
+    /// Set the value for a DAC channel. Max value is the [`max_code`](Resolution::max_code) of `R`,
+    /// 255 for the AD5304, 1023 for the AD5314 and 4095 for the AD5324
+    pub fn set_channel(&mut self, channel: QuadChannel, value: u16) -> Result<(), Error<S, P>>
+    where
+        R: Resolution,
+    {
+        self.dac.set_channel(channel.into(), value)
+    }
+
+    /// Release the SPI instance and enable pin
+    pub fn release(self) -> (SPI, EN) {
+        self.dac.release()
+    }
+
+    /// Continue with the resolution picked at runtime, see [`Ad53xx::with_resolution`]
+    pub fn with_resolution(self, resolution: AnyResolution) -> Ad53x4<SPI, EN, AnyResolution> {
+        Ad53x4 {
+            dac: self.dac.with_resolution(resolution),
+        }
+    }
+}
+
+/// Channel indices 0..=3 are channels A..D, others are an [`Error::Address`]
+impl<SPI, EN, R, S, P> MultiChannelDac for Ad53x4<SPI, EN, R>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    type Error = Error<S, P>;
+
+    const CHANNELS: usize = 4;
+
+    fn set(&mut self, ch: u8, code: u16) -> Result<(), Error<S, P>> {
+        if ch >= 4 {
+            return Err(Error::Address);
+        }
+        self.dac.set(ch, code)
+    }
+
+    fn power(&mut self, ch: u8, on: bool) -> Result<(), Error<S, P>> {
+        if ch >= 4 {
+            return Err(Error::Address);
+        }
+        self.dac.power(ch, on)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoEnable;
+    use embedded_hal_mock::eh0::spi;
+
+    fn frames(bytes: &[[u8; 2]]) -> spi::Mock {
+        let expected: Vec<_> = bytes
+            .iter()
+            .map(|b| spi::Transaction::write(b.to_vec()))
+            .collect();
+        spi::Mock::new(&expected)
+    }
+
+    #[test]
+    fn control_word_only_carries_the_first_group() {
+        let config = QuadConfig {
+            gain: GAIN::Gain02Vref,
+            buf: BUF::Buffered,
+            vdd: VDD::VddAsRef,
+            ldac: LDAC::LdacLow,
+        };
+        let mut spi = frames(&[[0x80, 0x15], [0xa0, 0x00], [0x80, 0x04], [0xa0, 0x01]]);
+        let mut dac = Ad5324::init(spi.clone(), NoEnable, config).unwrap();
+        dac.configure(QuadConfig::default()).unwrap();
+        spi.done();
+    }
+
+    #[test]
+    fn power_down_mask_covers_channels_a_to_d() {
+        let mut spi = frames(&[
+            [0x80, 0x04],
+            [0xa0, 0x01],
+            [0xc0, 0x09],
+            [0xc0, 0x0b],
+            [0xc0, 0x0a],
+        ]);
+        let mut dac = Ad5324::init(spi.clone(), NoEnable, QuadConfig::default()).unwrap();
+        dac.power_down([true, false, false, true]).unwrap();
+        dac.power(1, false).unwrap();
+        dac.power(0, true).unwrap();
+        assert!(matches!(dac.power(4, false), Err(Error::Address)));
+        spi.done();
+    }
+
+    #[test]
+    fn codes_follow_the_resolution_of_the_part() {
+        let mut spi = frames(&[
+            [0x80, 0x04],
+            [0xa0, 0x01],
+            [0x3f, 0xff],
+            [0x3f, 0xf0],
+            [0x1f, 0xfc],
+        ]);
+        let mut dac = Ad5324::init(spi.clone(), NoEnable, QuadConfig::default()).unwrap();
+        dac.set_channel(QuadChannel::D, 4095).unwrap();
+        assert!(matches!(dac.set(4, 0), Err(Error::Address)));
+        let (bus, _) = dac.release();
+        let mut dac = Ad5304 {
+            dac: Ad53xx::new(bus, NoEnable),
+        };
+        dac.set_channel(QuadChannel::D, 255).unwrap();
+        assert!(matches!(
+            dac.set_channel(QuadChannel::A, 256),
+            Err(Error::OobMax(255))
+        ));
+        let (bus, _) = dac.release();
+        let mut dac = Ad5314 {
+            dac: Ad53xx::new(bus, NoEnable),
+        };
+        dac.set(1, 1023).unwrap();
+        spi.done();
+    }
+}
@@ -0,0 +1,194 @@
+//! Deferred writes, queued from several places during a control cycle and sent to the bus in one go, e.g. at
+//! the end of an interrupt handler chain so the time-critical sections never touch SPI.
+//!
+//! ```
+//! # use ad5328::{Ad5328, Ad5328Config, Channel};
+//! # use embedded_hal_mock::eh0::spi;
+//! # let spi = spi::Mock::new(&[
+//! #     spi::Transaction::write(vec![0x80, 0x0c]),
+//! #     spi::Transaction::write(vec![0xa0, 0x01]),
+//! #     spi::Transaction::write(vec![0x10, 0x0a]),
+//! #     spi::Transaction::write(vec![0x00, 0x0c]),
+//! # ]);
+//! # let mut spi_check = spi.clone();
+//! let dac = Ad5328::init_without_enable(spi, Ad5328Config::default()).unwrap();
+//! let mut dac = dac.deferred::<8>();
+//! dac.enqueue_set(Channel::A, 11).unwrap();
+//! dac.enqueue_set(Channel::B, 10).unwrap();
+//! dac.enqueue_set(Channel::A, 12).unwrap();
+//! assert_eq!(dac.flush().unwrap(), 2);
+//! # spi_check.done();
+//! ```
+
+use heapless::Vec;
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, Transport, Written};
+
+/// Queued operation
+#[derive(Clone, Copy)]
+enum Op {
+    Set(Channel, u16),
+    PowerDown([bool; 8]),
+}
+
+/// Driver that queues up to `N` operations until [`flush`](Self::flush), see [`Ad53xx::deferred`].
+/// The queue is for a single context, it isn't shared with interrupts
+pub struct Deferred<SPI, EN, R, L, const N: usize> {
+    dac: Ad53xx<SPI, EN, R, L>,
+    ops: Vec<Op, N>,
+}
+
+impl<SPI, EN, R, L> Ad53xx<SPI, EN, R, L> {
+    /// Queue writes for a later [`flush`](Deferred::flush) instead of sending them right away, in a queue
+    /// of `N` operations
+    pub fn deferred<const N: usize>(self) -> Deferred<SPI, EN, R, L, N> {
+        Deferred {
+            dac: self,
+            ops: Vec::new(),
+        }
+    }
+}
+
+impl<SPI, EN, R, L, const N: usize> Deferred<SPI, EN, R, L, N> {
+    /// The driver, e.g. for its state or for writes that can't wait for the flush
+    pub fn dac(&mut self) -> &mut Ad53xx<SPI, EN, R, L> {
+        &mut self.dac
+    }
+
+    /// Number of queued operations
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Whether no operation is queued
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Drop all queued operations without writing them
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+
+    /// Continue with the driver writing right away, dropping the queued operations, see
+    /// [`flush`](Self::flush) to write them first
+    pub fn into_inner(self) -> Ad53xx<SPI, EN, R, L> {
+        self.dac
+    }
+}
+
+impl<SPI, EN, R, L, S, P, const N: usize> Deferred<SPI, EN, R, L, N>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Queue a [`set_channel`](Ad53xx::set_channel) of `channel` to `value`. The value is checked here, and a
+    /// write queued earlier for the channel is replaced, so the flush writes only the last value, at the
+    /// position of the last write. Fails with [`Error::QueueFull`] if there is no room left
+    pub fn enqueue_set(&mut self, channel: Channel, value: u16) -> Result<(), Error<S, P>> {
+        self.dac.set_command::<S, P>(channel, value)?;
+        self.push(
+            Op::Set(channel, value),
+            |op| matches!(op, Op::Set(queued, _) if u8::from(*queued) == u8::from(channel)),
+        )
+    }
+
+    /// Queue a [`power_down`](Ad53xx::power_down) of the channels set to true, replacing one queued earlier
+    /// as each sets all channels. Fails with [`Error::QueueFull`] if there is no room left
+    pub fn enqueue_power_down(&mut self, channels: [bool; 8]) -> Result<(), Error<S, P>> {
+        self.push(Op::PowerDown(channels), |op| matches!(op, Op::PowerDown(_)))
+    }
+
+    /// Append `op`, removing the queued operation it `replaces` if there is one
+    fn push(&mut self, op: Op, replaces: impl Fn(&Op) -> bool) -> Result<(), Error<S, P>> {
+        if let Some(index) = self.ops.iter().position(replaces) {
+            self.ops.remove(index);
+        }
+        self.ops.push(op).map_err(|_| Error::QueueFull)
+    }
+
+    /// Write the queued operations in order, emptying the queue, and return the number of frames sent: one
+    /// per power-down and one per channel write that isn't skipped, held or left pending, not counting
+    /// the writes to mirror replicas. On an error the failed operation and those after it stay queued
+    pub fn flush(&mut self) -> Result<usize, Error<S, P>> {
+        let mut frames = 0;
+        for index in 0..self.ops.len() {
+            let result = match self.ops[index] {
+                Op::Set(channel, value) => self
+                    .dac
+                    .update_channel(channel, value)
+                    .map(|written| usize::from(written == Written::Sent)),
+                Op::PowerDown(channels) => self.dac.power_down(channels).map(|()| 1),
+            };
+            match result {
+                Ok(sent) => frames += sent,
+                Err(error) => {
+                    self.ops = self.ops[index..].iter().copied().collect();
+                    return Err(error);
+                }
+            }
+        }
+        self.ops.clear();
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Ad5328, Ad5328Config, Channel, Error};
+    use embedded_hal_mock::eh0::spi;
+
+    #[test]
+    fn flush_writes_in_order_with_the_last_value_per_channel() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x20, 0x03]),
+            spi::Transaction::write(vec![0xc0, 0x02]),
+            spi::Transaction::write(vec![0x00, 0x02]),
+        ]);
+        let dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        let mut dac = dac.deferred::<3>();
+        dac.enqueue_set(Channel::A, 1).unwrap();
+        dac.enqueue_set(Channel::C, 3).unwrap();
+        dac.enqueue_power_down([true; 8]).unwrap();
+        dac.enqueue_power_down([false, true, false, false, false, false, false, false])
+            .unwrap();
+        dac.enqueue_set(Channel::A, 2).unwrap();
+        assert_eq!(dac.len(), 3);
+        assert!(matches!(
+            dac.enqueue_set(Channel::B, 4),
+            Err(Error::QueueFull)
+        ));
+        assert!(matches!(
+            dac.enqueue_set(Channel::D, 4096),
+            Err(Error::OobMax(4095))
+        ));
+        assert_eq!(dac.len(), 3);
+
+        assert_eq!(dac.flush().unwrap(), 3);
+        assert!(dac.is_empty());
+        assert_eq!(dac.flush().unwrap(), 0);
+        spi.done();
+    }
+
+    #[test]
+    fn skipped_writes_are_not_counted() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x00, 0x05]),
+        ]);
+        let dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        let mut dac = dac.with_dedup(true).deferred::<4>();
+        dac.enqueue_set(Channel::A, 5).unwrap();
+        assert_eq!(dac.flush().unwrap(), 1);
+        dac.enqueue_set(Channel::A, 5).unwrap();
+        assert_eq!(dac.flush().unwrap(), 0);
+        dac.enqueue_set(Channel::B, 5).unwrap();
+        dac.clear();
+        assert_eq!(dac.flush().unwrap(), 0);
+        let _ = dac.into_inner();
+        spi.done();
+    }
+}
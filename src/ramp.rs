@@ -0,0 +1,235 @@
+//! Blocking ramps of a channel between two values, e.g. for power sequencing an analog stage.
+
+use embedded_hal::blocking::delay::DelayUs;
+
+use crate::{resolution::Resolution, Ad5328Config, Ad53xx, Channel, Error, Transport};
+
+/// Ramp of all channels up from 0 after configuring, see [`Ad53xx::init_soft_start`]
+#[derive(Clone, Copy)]
+pub struct SoftStart {
+    /// Value each channel ramps to, channels with a target of 0 are not written
+    pub target: [u16; 8],
+    /// Number of LDAC updates of the ramp, 0 or 1 is a single jump to the targets
+    pub steps: u16,
+    /// Time between two updates, in microseconds
+    pub step_delay_us: u32,
+}
+
+/// Error of a ramp, together with the value the channel was last set to
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RampError<S, P> {
+    /// Value of the last frame written, `None` if the ramp failed before its first
+    pub last: Option<u16>,
+    /// The underlying error
+    pub error: Error<S, P>,
+}
+
+impl<S, P> core::fmt::Display for RampError<S, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.last {
+            Some(last) => write!(f, "{} after ramping to {}", self.error, last),
+            None => write!(f, "{} before the ramp started", self.error),
+        }
+    }
+}
+
+impl<S, P> core::error::Error for RampError<S, P>
+where
+    S: core::error::Error + 'static,
+    P: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        core::error::Error::source(&self.error)
+    }
+}
+
+/// Value of step `step` of `steps` from `from` to `to`, rounded to nearest with halves away from `from`
+pub(crate) fn ramp_value(from: u16, to: u16, step: u16, steps: u16) -> u16 {
+    let delta = (i32::from(to) - i32::from(from)) * i32::from(step);
+    let steps = i32::from(steps);
+    (i32::from(from) + (delta + delta.signum() * (steps / 2)) / steps) as u16
+}
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Ramp `channel` from `from`, where it is now, to `to` in `steps` frames, waiting `step_delay_us` between
+    /// two frames, so the ramp takes `(steps - 1) * step_delay_us`. Step `k` writes `from + (to - from) * k / steps`
+    /// rounded to nearest, so the last frame is always exactly `to`. A `steps` of 0 or 1 is a single jump to `to`.
+    ///
+    /// Both ends are checked before the first frame as for [`set_channel`](Self::set_channel), and every frame
+    /// goes through it. On an error [`RampError::last`] is the value of the last frame written
+    pub fn ramp_channel<D: DelayUs<u32>>(
+        &mut self,
+        channel: Channel,
+        from: u16,
+        to: u16,
+        steps: u16,
+        delay: &mut D,
+        step_delay_us: u32,
+    ) -> Result<(), RampError<S, P>> {
+        for value in [from, to] {
+            self.set_command::<S, P>(channel, value)
+                .map_err(|error| RampError { last: None, error })?;
+        }
+        let steps = steps.max(1);
+        let mut last = None;
+        for step in 1..=steps {
+            if step > 1 {
+                delay.delay_us(step_delay_us);
+            }
+            let value = ramp_value(from, to, step, steps);
+            self.set_channel(channel, value)
+                .map_err(|error| RampError { last, error })?;
+            last = Some(value);
+        }
+        Ok(())
+    }
+}
+
+impl<SPI, EN, R, S, P> Ad53xx<SPI, EN, R>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution + Default,
+{
+    /// Initialize a new instance as [`init`](Self::init) does, then ramp every channel with a nonzero target
+    /// from 0, where the outputs are after power-up, to its target in lockstep, so the outputs don't jump to
+    /// their operating values all at once. Each step sets all ramping channels through
+    /// [`set_channels_synced`](Self::set_channels_synced), with step `k` at `target * k / steps` rounded to
+    /// nearest as for [`ramp_channel`](Self::ramp_channel).
+    ///
+    /// All targets are checked before the first frame, so an out of bounds target fails without writing anything
+    pub fn init_soft_start<D: DelayUs<u32>>(
+        spi: SPI,
+        enable: EN,
+        config: Ad5328Config,
+        delay: &mut D,
+        soft_start: SoftStart,
+    ) -> Result<Self, Error<S, P>> {
+        let mut ad5328 = Self::new(spi, enable);
+        for (n, &target) in soft_start.target.iter().enumerate() {
+            ad5328.set_command::<S, P>(Channel::from(n as u8), target)?;
+        }
+        ad5328.configure(config)?;
+        let steps = soft_start.steps.max(1);
+        let mut values = [(Channel::A, 0); 8];
+        for step in 1..=steps {
+            if step > 1 {
+                delay.delay_us(soft_start.step_delay_us);
+            }
+            let mut len = 0;
+            for (n, &target) in soft_start.target.iter().enumerate() {
+                if target != 0 {
+                    values[len] = (Channel::from(n as u8), ramp_value(0, target, step, steps));
+                    len += 1;
+                }
+            }
+            ad5328.set_channels_synced(&values[..len])?;
+        }
+        Ok(ad5328)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{recorded, Delays, Frames};
+    use crate::{Ad5328, Ad5328Config, NoEnable};
+
+    fn ramp(from: u16, to: u16, steps: u16) -> (Vec<u16>, Vec<u32>) {
+        let mut dac = recorded(usize::MAX);
+        let mut delay = Delays::default();
+        dac.ramp_channel(Channel::C, from, to, steps, &mut delay, 250)
+            .unwrap();
+        (dac.release().0.values(), delay.0)
+    }
+
+    #[test]
+    fn ramps_end_exactly_on_the_target() {
+        assert_eq!(ramp(0, 10, 3), (vec![3, 7, 10], vec![250, 250]));
+        assert_eq!(ramp(10, 0, 3), (vec![7, 3, 0], vec![250, 250]));
+        assert_eq!(ramp(100, 4095, 1), (vec![4095], vec![]));
+        assert_eq!(ramp(100, 4095, 0), (vec![4095], vec![]));
+        assert_eq!(ramp(5, 5, 2), (vec![5, 5], vec![250]));
+        let (values, delays) = ramp(4095, 1, 1000);
+        assert_eq!(values.len(), 1000);
+        assert_eq!(delays.len(), 999);
+        assert_eq!(values.last(), Some(&1));
+        assert!(values.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn errors_report_the_last_value_written() {
+        let mut delay = Delays::default();
+        let mut dac = recorded(2);
+        let err = dac
+            .ramp_channel(Channel::C, 0, 400, 4, &mut delay, 1)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RampError {
+                last: Some(200),
+                error: Error::Spi(())
+            }
+        ));
+
+        let err = dac
+            .ramp_channel(Channel::C, 0, 4096, 4, &mut delay, 1)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RampError {
+                last: None,
+                error: Error::OobMax(4095)
+            }
+        ));
+    }
+
+    #[test]
+    fn soft_start_ramps_nonzero_targets_in_lockstep() {
+        let spi = Frames::default();
+        let mut delay = Delays::default();
+        let soft_start = SoftStart {
+            target: [100, 0, 0, 0, 0, 0, 0, 4000],
+            steps: 4,
+            step_delay_us: 500,
+        };
+        let dac = Ad5328::init_soft_start(
+            spi,
+            NoEnable,
+            Ad5328Config::default(),
+            &mut delay,
+            soft_start,
+        )
+        .unwrap();
+        assert_eq!(
+            dac.release().0.values(),
+            [25, 1000, 50, 2000, 75, 3000, 100, 4000]
+        );
+        assert_eq!(delay.0, [500, 500, 500]);
+    }
+
+    #[test]
+    fn soft_start_checks_targets_before_writing() {
+        let spi = Frames {
+            budget: 0,
+            ..Frames::default()
+        };
+        let soft_start = SoftStart {
+            target: [0, 0, 4096, 0, 0, 0, 0, 0],
+            steps: 4,
+            step_delay_us: 500,
+        };
+        let result = Ad5328::init_soft_start(
+            spi,
+            NoEnable,
+            Ad5328Config::default(),
+            &mut Delays::default(),
+            soft_start,
+        );
+        assert!(matches!(result, Err(Error::OobMax(4095))));
+    }
+}
@@ -0,0 +1,185 @@
+//! Random CV, sample-and-hold of random codes, drawn from a caller-supplied [`RngCore`].
+
+use core::ops::RangeInclusive;
+
+use rand_core::RngCore;
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, Transport};
+
+/// New random code on a channel at every [`trigger`](Self::trigger), uniformly distributed over a range
+#[derive(Clone, Copy)]
+pub struct RandomCv {
+    channel: Channel,
+    min: u16,
+    max: u16,
+    step: u16,
+    slew: bool,
+}
+
+impl RandomCv {
+    /// Random codes in `range` on `channel`, both ends included, `None` if the range is empty or ends over
+    /// `max_code`, e.g. 4095 for the AD5328. A range of a single code holds it constant
+    pub fn new(channel: Channel, range: RangeInclusive<u16>, max_code: u16) -> Option<Self> {
+        let (min, max) = range.into_inner();
+        if min > max || max > max_code {
+            return None;
+        }
+        Some(Self {
+            channel,
+            min,
+            max,
+            step: 1,
+            slew: false,
+        })
+    }
+
+    /// Quantize the codes to `min + n * step`, never past the end of the range. A `step` of 0 is as 1
+    pub fn with_step(self, step: u16) -> Self {
+        Self {
+            step: step.max(1),
+            ..self
+        }
+    }
+
+    /// Write the codes through [`Ad53xx::set_target`] instead of [`Ad53xx::set_channel`], so the channel
+    /// slews to each new code as set with [`Ad53xx::set_slew`]
+    pub fn with_slew(self, slew: bool) -> Self {
+        Self { slew, ..self }
+    }
+
+    /// The channel the codes are written to
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// Draw a new code from `rng` and write it, returning the code
+    pub fn trigger<SPI, EN, R, L, S, P>(
+        &self,
+        dac: &mut Ad53xx<SPI, EN, R, L>,
+        rng: &mut impl RngCore,
+    ) -> Result<u16, Error<S, P>>
+    where
+        SPI: Transport<EN, SpiError = S, PinError = P>,
+        R: Resolution,
+    {
+        let code = self.draw(rng);
+        if self.slew {
+            dac.set_target(self.channel, code)?;
+        } else {
+            dac.set_channel(self.channel, code)?;
+        }
+        Ok(code)
+    }
+
+    /// Uniform code of the range, rejecting the draws past the largest multiple of the number of codes
+    fn draw(&self, rng: &mut impl RngCore) -> u16 {
+        let codes = u64::from((self.max - self.min) / self.step) + 1;
+        let limit = (1 << 32) - (1 << 32) % codes;
+        loop {
+            let draw = u64::from(rng.next_u32());
+            if draw < limit {
+                return self.min + (draw % codes) as u16 * self.step;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Frames;
+    use crate::{Ad5328, Ad5328Config};
+
+    /// xorshift32, enough for a seeded test
+    struct XorShift(u32);
+
+    impl RngCore for XorShift {
+        fn next_u32(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            rand_core::impls::next_u64_via_u32(self)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    fn dac() -> Ad5328<Frames, crate::NoEnable> {
+        Ad5328::init_without_enable(Frames::default(), Ad5328Config::default()).unwrap()
+    }
+
+    /// Codes of `triggers` triggers, checking they are the ones written
+    fn draw(random: &RandomCv, triggers: usize) -> Vec<u16> {
+        let mut dac = dac();
+        let mut rng = XorShift(0x1234_5678);
+        let codes: Vec<u16> = (0..triggers)
+            .map(|_| random.trigger(&mut dac, &mut rng).unwrap())
+            .collect();
+        assert_eq!(dac.release().0.values(), codes);
+        codes
+    }
+
+    #[test]
+    fn ranges_are_checked() {
+        assert!(RandomCv::new(Channel::A, 0..=4096, 4095).is_none());
+        assert!(RandomCv::new(Channel::A, 0..=256, 255).is_none());
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 10..=9;
+        assert!(RandomCv::new(Channel::A, empty, 4095).is_none());
+        assert!(RandomCv::new(Channel::A, 0..=4095, 4095).is_some());
+    }
+
+    #[test]
+    fn codes_cover_the_inclusive_range() {
+        let random = RandomCv::new(Channel::B, 100..=107, 4095).unwrap();
+        let codes = draw(&random, 4000);
+        assert!(codes.iter().all(|code| (100..=107).contains(code)));
+        for code in 100..=107 {
+            let count = codes.iter().filter(|&&c| c == code).count();
+            assert!((400..600).contains(&count), "{code} drawn {count} times");
+        }
+
+        let random = RandomCv::new(Channel::B, 4095..=4095, 4095).unwrap();
+        assert_eq!(draw(&random, 10), [4095; 10]);
+    }
+
+    #[test]
+    fn codes_are_quantized_within_the_range() {
+        let random = RandomCv::new(Channel::C, 1000..=2010, 4095)
+            .unwrap()
+            .with_step(250);
+        let codes = draw(&random, 1000);
+        for code in [1000, 1250, 1500, 1750, 2000] {
+            assert!(codes.contains(&code));
+        }
+        assert!(codes
+            .iter()
+            .all(|code| (code - 1000) % 250 == 0 && *code <= 2000));
+    }
+
+    #[test]
+    fn slewed_codes_wait_for_the_tick() {
+        let mut dac = dac();
+        let mut rng = XorShift(7);
+        dac.set_slew(Channel::D, 4095);
+        let random = RandomCv::new(Channel::D, 0..=4095, 4095)
+            .unwrap()
+            .with_slew(true);
+        let first = random.trigger(&mut dac, &mut rng).unwrap();
+        let second = random.trigger(&mut dac, &mut rng).unwrap();
+        assert_eq!(dac.spi.values(), [first]);
+        dac.tick().unwrap();
+        assert_eq!(dac.release().0.values(), [first, second]);
+    }
+}
@@ -0,0 +1,149 @@
+//! Background refresh of the input registers from the codes last written, one channel at a time.
+
+use crate::{Ad53xx, Channel, Error, Transport};
+
+/// Rewrites one channel per [`refresh_step`](Self::refresh_step), cycling from A to H, from the codes the driver
+/// last wrote, see [`Ad53xx::last_code`]. Channels not written yet and powered down channels are skipped
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Refresher {
+    /// Channel to look at first in the next step
+    next: u8,
+    stale: [bool; 8],
+}
+
+impl Refresher {
+    /// Refresher starting with channel A
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark all channels as stale, e.g. after a suspected upset, so the next steps rewrite them before carrying
+    /// on with the cycle. Channels skipped for now stay stale until they are written
+    pub fn force_full(&mut self) {
+        self.stale = [true; 8];
+    }
+
+    /// Whether `channel` is still waiting for its rewrite after [`force_full`](Self::force_full)
+    pub fn is_stale(&self, channel: Channel) -> bool {
+        self.stale[usize::from(u8::from(channel))]
+    }
+
+    /// Rewrite the next channel due, returning the logical channel refreshed or `None` if there is none to
+    /// refresh. With the LDAC mode latched the code reaches the output on the next update, as for any write.
+    /// On an error the same channel is tried again by the next step
+    pub fn refresh_step<SPI, EN, R, L, S, P>(
+        &mut self,
+        dac: &mut Ad53xx<SPI, EN, R, L>,
+    ) -> Result<Option<Channel>, Error<S, P>>
+    where
+        SPI: Transport<EN, SpiError = S, PinError = P>,
+    {
+        let due = |n: usize| dac.data[n].is_some() && !dac.powered_down[n];
+        let order = (0..8).map(|k| (usize::from(self.next) + k) % 8);
+        let Some(n) = order
+            .clone()
+            .find(|&n| self.stale[n] && due(n))
+            .or_else(|| order.clone().find(|&n| due(n)))
+        else {
+            return Ok(None);
+        };
+        let channel = Channel::from(n as u8);
+        dac.rewrite(channel)?;
+        self.stale[n] = false;
+        self.next = (n as u8 + 1) % 8;
+        Ok(Some(channel))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Frames;
+    use crate::{Ad5328, Ad5328Config, NoEnable};
+
+    fn dac() -> Ad5328<Frames, NoEnable> {
+        Ad5328::init_without_enable(Frames::default(), Ad5328Config::default()).unwrap()
+    }
+
+    /// Channels of `steps` refresh steps, 8 for none
+    fn steps(
+        refresher: &mut Refresher,
+        dac: &mut Ad5328<Frames, NoEnable>,
+        steps: usize,
+    ) -> Vec<u8> {
+        (0..steps)
+            .map(|_| refresher.refresh_step(dac).unwrap().map_or(8, u8::from))
+            .collect()
+    }
+
+    #[test]
+    fn cycles_through_the_written_powered_up_channels() {
+        let mut dac = dac();
+        let mut refresher = Refresher::new();
+        assert_eq!(steps(&mut refresher, &mut dac, 1), [8]);
+
+        dac.set_channel(Channel::B, 100).unwrap();
+        dac.set_channel(Channel::E, 200).unwrap();
+        dac.set_channel(Channel::H, 300).unwrap();
+        dac.power_down([false, false, false, false, true, false, false, false])
+            .unwrap();
+        dac.spi.frames.clear();
+        assert_eq!(steps(&mut refresher, &mut dac, 5), [1, 7, 1, 7, 1]);
+        assert_eq!(dac.spi.frames, [0x1064, 0x712c, 0x1064, 0x712c, 0x1064]);
+    }
+
+    #[test]
+    fn rewrites_the_codes_after_the_pipeline() {
+        let mut dac = dac();
+        dac.set_inverted(Channel::A, true);
+        dac.set_channel_map([
+            Channel::C,
+            Channel::B,
+            Channel::A,
+            Channel::D,
+            Channel::E,
+            Channel::F,
+            Channel::G,
+            Channel::H,
+        ])
+        .unwrap();
+        dac.mirror(Channel::A, Channel::D).unwrap();
+        dac.set_channel(Channel::A, 95).unwrap();
+        assert_eq!(dac.last_code(Channel::A), Some(4000));
+        assert_eq!(dac.last_code(Channel::D), Some(4000));
+        let mut refresher = Refresher::new();
+        assert_eq!(steps(&mut refresher, &mut dac, 2), [0, 3]);
+        assert_eq!(dac.spi.frames[2..], [0x2fa0, 0x3fa0]);
+    }
+
+    #[test]
+    fn stale_channels_come_first() {
+        let mut dac = dac();
+        for n in 0..8 {
+            dac.set_channel(Channel::from(n), u16::from(n)).unwrap();
+        }
+        dac.power_down([false, false, false, false, false, false, true, false])
+            .unwrap();
+        let mut refresher = Refresher::new();
+        assert_eq!(steps(&mut refresher, &mut dac, 3), [0, 1, 2]);
+        refresher.force_full();
+        assert_eq!(
+            steps(&mut refresher, &mut dac, 9),
+            [3, 4, 5, 7, 0, 1, 2, 3, 4]
+        );
+        assert!(refresher.is_stale(Channel::G));
+        dac.power_down([false; 8]).unwrap();
+        assert_eq!(steps(&mut refresher, &mut dac, 2), [6, 7]);
+        assert!(!refresher.is_stale(Channel::G));
+    }
+
+    #[test]
+    fn resets_leave_nothing_to_refresh() {
+        let mut dac = dac();
+        dac.set_channel(Channel::A, 1).unwrap();
+        dac.reset(false).unwrap();
+        assert_eq!(dac.last_code(Channel::A), None);
+        let mut refresher = Refresher::new();
+        assert_eq!(steps(&mut refresher, &mut dac, 1), [8]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Code widths of the AD5308, AD5318 and AD5328, which share everything but the data bits.
+//!
+//! Codes are left-aligned in the 12 data bits of a frame, DB11 is always the MSB of the code
+//! and the unused low bits of the 8-bit and 10-bit parts are don't-care, sent as 0.
+//! The width is either fixed by a marker type, or picked at runtime with [`AnyResolution`].
+
+use crate::Error;
+
+/// Width of the DAC codes of a part, see [`Ad53xx`](crate::Ad53xx)
+pub trait Resolution {
+    /// Number of code bits
+    fn bits(&self) -> u32;
+
+    /// Full-scale code
+    fn max_code(&self) -> u16 {
+        (1 << self.bits()) - 1
+    }
+
+    #[doc(hidden)]
+    fn out_of_bounds<S, P>(&self) -> Error<S, P> {
+        Error::OobMax(self.max_code())
+    }
+}
+
+/// 8-bit codes of the AD5308, in DB11...DB4
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bits8;
+
+impl Resolution for Bits8 {
+    fn bits(&self) -> u32 {
+        8
+    }
+}
+
+/// 10-bit codes of the AD5318, in DB11...DB2
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bits10;
+
+impl Resolution for Bits10 {
+    fn bits(&self) -> u32 {
+        10
+    }
+}
+
+/// 12-bit codes of the AD5328, in DB11...DB0
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bits12;
+
+impl Resolution for Bits12 {
+    fn bits(&self) -> u32 {
+        12
+    }
+}
+
+/// Resolution picked at runtime, e.g. from a board revision, see [`Ad53xx::with_resolution`](crate::Ad53xx::with_resolution).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AnyResolution {
+    /// 8-bit codes of the AD5308
+    Bits8,
+    /// 10-bit codes of the AD5318
+    Bits10,
+    /// 12-bit codes of the AD5328
+    Bits12,
+}
+
+impl Resolution for AnyResolution {
+    fn bits(&self) -> u32 {
+        match self {
+            AnyResolution::Bits8 => 8,
+            AnyResolution::Bits10 => 10,
+            AnyResolution::Bits12 => 12,
+        }
+    }
+}
@@ -0,0 +1,882 @@
+//! Conversions from physical and normalized values to DAC codes, for the `set_channel_*` variants of the driver.
+//!
+//! All functions take the code width `bits` of the part, see [`Resolution::bits`](crate::resolution::Resolution::bits),
+//! and return codes in `0..2^bits`.
+
+use crate::GAIN;
+#[cfg(feature = "fixed")]
+use fixed::types::{I1F15, U0F16};
+
+/// How a conversion rounds a value between two codes, see [`Ad53xx::set_rounding`](crate::Ad53xx::set_rounding)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rounding {
+    /// The nearest code. A value exactly halfway between two codes rounds up to the higher one, also below
+    /// midscale for the bipolar conversions, so halves are not symmetric around midscale
+    #[default]
+    Nearest,
+    /// The code below, or the code itself for a value exactly on it
+    Floor,
+    /// The code above, or the code itself for a value exactly on it
+    Ceil,
+    /// The code closer to the code of a value of 0: as [`Floor`](Self::Floor) for the unipolar conversions,
+    /// where that is code 0, and towards the midscale code `2^(bits - 1)` for the bipolar ones, e.g. to
+    /// never overshoot 0 V
+    TowardZero,
+}
+
+impl Rounding {
+    /// `n / d` for a positive `d`, rounded in this mode, with `zero` the code of a value of 0
+    const fn div(self, n: u64, d: u64, zero: u64) -> u64 {
+        match self {
+            Rounding::Nearest => (n + d / 2) / d,
+            Rounding::Ceil => n.div_ceil(d),
+            Rounding::TowardZero if n < zero * d => n.div_ceil(d),
+            Rounding::Floor | Rounding::TowardZero => n / d,
+        }
+    }
+}
+
+/// Code for an output of `mv` millivolts, rounded to nearest, with a reference of `vref_mv` and `gain`.
+/// `None` if the code is over full scale, i.e. `mv` is at least `vref_mv * gain * (2^bits - 0.5) / 2^bits`,
+/// or if `vref_mv` is 0. Integer-only, for cores without an FPU
+pub fn millivolts_to_code(mv: u32, vref_mv: u32, gain: GAIN, bits: u32) -> Option<u16> {
+    millivolts_to_code_rounded(mv, vref_mv, gain, bits, Rounding::Nearest)
+}
+
+/// As [`millivolts_to_code`], rounded by `rounding`. The bound of full scale moves with it: `None` from an
+/// exact code of `2^bits` on with [`Rounding::Floor`] and [`Rounding::TowardZero`], and from just over
+/// `2^bits - 1` on with [`Rounding::Ceil`]
+pub fn millivolts_to_code_rounded(
+    mv: u32,
+    vref_mv: u32,
+    gain: GAIN,
+    bits: u32,
+    rounding: Rounding,
+) -> Option<u16> {
+    let full_scale = u64::from(vref_mv)
+        * match gain {
+            GAIN::Gain0Vref => 1,
+            GAIN::Gain02Vref => 2,
+        };
+    if full_scale == 0 {
+        return None;
+    }
+    let code = rounding.div(u64::from(mv) << bits, full_scale, 0);
+    if code >= 1 << bits {
+        return None;
+    }
+    Some(code as u16)
+}
+
+/// Code for a `ratio` of full scale, 0.0 for code 0 and 1.0 for the full-scale code, rounded to nearest.
+/// Ratios outside `0.0..=1.0`, e.g. from float noise, are clamped; `None` for NaN.
+/// [`scaled_u16_to_code`] is the integer-only counterpart, with 65535 as the ratio 1
+#[cfg(feature = "float")]
+pub fn ratio_to_code(ratio: f32, bits: u32) -> Option<u16> {
+    ratio_to_code_rounded(ratio, bits, Rounding::Nearest)
+}
+
+/// As [`ratio_to_code`], rounded by `rounding`
+#[cfg(feature = "float")]
+pub fn ratio_to_code_rounded(ratio: f32, bits: u32, rounding: Rounding) -> Option<u16> {
+    if ratio.is_nan() {
+        return None;
+    }
+    let max = ((1u32 << bits) - 1) as f32;
+    let code = ratio.clamp(0.0, 1.0) * max;
+    // no `f32::round` in `core`, the scaled ratio is never negative, so the casts truncate down
+    Some(match rounding {
+        Rounding::Nearest => (code + 0.5) as u16,
+        Rounding::Floor | Rounding::TowardZero => code as u16,
+        Rounding::Ceil if f32::from(code as u16) < code => code as u16 + 1,
+        Rounding::Ceil => code as u16,
+    })
+}
+
+/// Code for a full-range 16-bit `value`, e.g. a setpoint written for a 16-bit DAC, rounded to nearest:
+/// 0 maps to code 0 and 65535 to the full-scale code, unlike a plain shift, which biases low
+pub fn scaled_u16_to_code(value: u16, bits: u32) -> u16 {
+    scaled_u16_to_code_rounded(value, bits, Rounding::Nearest)
+}
+
+/// As [`scaled_u16_to_code`], rounded by `rounding`. The value is scaled by `(2^bits - 1) / 65535`, an odd
+/// denominator, so no value is exactly halfway between two codes
+pub fn scaled_u16_to_code_rounded(value: u16, bits: u32, rounding: Rounding) -> u16 {
+    scaled(value, bits, rounding, 0)
+}
+
+/// Code for `value` scaled from `0..=65535` to the code range, with `zero` the code of a value of 0
+fn scaled(value: u16, bits: u32, rounding: Rounding, zero: u64) -> u16 {
+    let max = (1u64 << bits) - 1;
+    rounding.div(u64::from(value) * max, 0xffff, zero) as u16
+}
+
+/// Code for a bipolar `value` around midscale: `i16::MIN` maps to code 0, 0 to the midscale code `2^(bits - 1)`
+/// and `i16::MAX` to the full-scale code. The value is offset to `0..=65535` and rounded to nearest as in
+/// [`scaled_u16_to_code`], so all codes are equally wide, -1 is just below midscale and no value is out of bounds
+pub fn i16_to_code(value: i16, bits: u32) -> u16 {
+    i16_to_code_rounded(value, bits, Rounding::Nearest)
+}
+
+/// As [`i16_to_code`], rounded by `rounding`
+pub fn i16_to_code_rounded(value: i16, bits: u32, rounding: Rounding) -> u16 {
+    scaled((value as u16) ^ 0x8000, bits, rounding, 1 << (bits - 1))
+}
+
+/// Code for a fraction of the reference, as in `Vout = Vref * code / 2^bits`, rounded to nearest and saturated
+/// at the full-scale code, which the fractions from `1 - 0.5 / 2^bits` up to just below 1 would exceed
+#[cfg(feature = "fixed")]
+pub const fn u0f16_to_code(value: U0F16, bits: u32) -> u16 {
+    u0f16_to_code_rounded(value, bits, Rounding::Nearest)
+}
+
+/// As [`u0f16_to_code`], rounded by `rounding`
+#[cfg(feature = "fixed")]
+pub const fn u0f16_to_code_rounded(value: U0F16, bits: u32, rounding: Rounding) -> u16 {
+    fraction_to_code(value.to_bits(), bits, rounding, 0)
+}
+
+/// Code for `value` with 16 fraction bits, saturated at the full-scale code, with `zero` the code of a value of 0
+#[cfg(feature = "fixed")]
+const fn fraction_to_code(value: u16, bits: u32, rounding: Rounding, zero: u64) -> u16 {
+    let code = rounding.div(value as u64, 1 << (16 - bits), zero);
+    let max = (1 << bits) - 1;
+    if code > max {
+        max as u16
+    } else {
+        code as u16
+    }
+}
+
+/// Code for a bipolar fraction around midscale: -1 maps to code 0, 0 to the midscale code `2^(bits - 1)`,
+/// and the fraction is offset to `0..1` and converted as in [`u0f16_to_code`], saturating just below 1
+#[cfg(feature = "fixed")]
+pub const fn i1f15_to_code(value: I1F15, bits: u32) -> u16 {
+    i1f15_to_code_rounded(value, bits, Rounding::Nearest)
+}
+
+/// As [`i1f15_to_code`], rounded by `rounding`
+#[cfg(feature = "fixed")]
+pub const fn i1f15_to_code_rounded(value: I1F15, bits: u32, rounding: Rounding) -> u16 {
+    fraction_to_code(
+        (value.to_bits() as u16) ^ 0x8000,
+        bits,
+        rounding,
+        1 << (bits - 1),
+    )
+}
+
+/// Code for the 1 V/oct pitch of MIDI `note`, `(note - zero_note) * 1000 / 12` millivolts, with a reference of
+/// `vref_mv` and `gain`. The code is rounded to nearest from the exact pitch rather than from whole millivolts,
+/// so adjacent semitones only share a code if they are less than a code apart.
+/// `None` below `zero_note`, over full scale as for [`millivolts_to_code`], or if `vref_mv` is 0
+pub fn note_to_code(note: u8, zero_note: u8, vref_mv: u32, gain: GAIN, bits: u32) -> Option<u16> {
+    let semitones = u64::from(note.checked_sub(zero_note)?);
+    let full_scale = 12
+        * u64::from(vref_mv)
+        * match gain {
+            GAIN::Gain0Vref => 1,
+            GAIN::Gain02Vref => 2,
+        };
+    if full_scale == 0 {
+        return None;
+    }
+    let code = (((semitones * 1000) << bits) + full_scale / 2) / full_scale;
+    if code >= 1 << bits {
+        return None;
+    }
+    Some(code as u16)
+}
+
+/// Per-octave correction of 1 V/oct codes, e.g. for the gain error of the output stage driving a VCO.
+/// Breakpoint `k` is a signed code offset for the note `k` octaves above the zero note. Offsets between two
+/// breakpoints are interpolated linearly by semitone and rounded to nearest, halves away from zero;
+/// from the last breakpoint on its offset holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawVoctCorrection"))]
+pub struct VoctCorrection {
+    offsets: [i16; VoctCorrection::MAX_BREAKPOINTS],
+    len: u8,
+}
+
+impl VoctCorrection {
+    /// Most breakpoints of a correction, octaves 0 to 10 cover all MIDI notes
+    pub const MAX_BREAKPOINTS: usize = 11;
+
+    /// Correction with the offsets of octave 0, 1 and so on, `None` for no or more than 11 offsets.
+    /// `const`, so a correction can be a `static` for [`Ad53xx::set_voct_correction`](crate::Ad53xx::set_voct_correction)
+    pub const fn new(offsets: &[i16]) -> Option<Self> {
+        if offsets.is_empty() || offsets.len() > Self::MAX_BREAKPOINTS {
+            return None;
+        }
+        let mut correction = Self {
+            offsets: [0; Self::MAX_BREAKPOINTS],
+            len: offsets.len() as u8,
+        };
+        let mut n = 0;
+        // no `copy_from_slice` in `const fn` before Rust 1.87
+        while n < offsets.len() {
+            correction.offsets[n] = offsets[n];
+            n += 1;
+        }
+        Some(correction)
+    }
+
+    /// The offsets of octave 0, 1 and so on
+    pub fn breakpoints(&self) -> &[i16] {
+        &self.offsets[..usize::from(self.len)]
+    }
+
+    /// Offset for the note `semitones` above the zero note
+    pub fn offset(&self, semitones: u8) -> i16 {
+        let breakpoints = self.breakpoints();
+        let octave = usize::from(semitones / 12);
+        let (Some(&low), Some(&high)) = (breakpoints.get(octave), breakpoints.get(octave + 1))
+        else {
+            return breakpoints[breakpoints.len() - 1];
+        };
+        let delta = (i32::from(high) - i32::from(low)) * i32::from(semitones % 12);
+        // a step of at most 65535 over 11 semitones, within the two offsets and so within i16
+        (i32::from(low) + (delta + delta.signum() * 6) / 12) as i16
+    }
+
+    /// `code` of the note `semitones` above the zero note with the offset added, `None` if that leaves `0..2^bits`
+    pub fn apply(&self, code: u16, semitones: u8, bits: u32) -> Option<u16> {
+        let code = i32::from(code) + i32::from(self.offset(semitones));
+        if !(0..1 << bits).contains(&code) {
+            return None;
+        }
+        Some(code as u16)
+    }
+}
+
+/// Lookup table of the codes for evenly spaced values, `points[0]` for value 0 to the last point for the
+/// maximum value, e.g. to linearize a nonlinear output stage. Values in between are interpolated linearly,
+/// rounded to nearest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lut(&'static [u16]);
+
+impl Lut {
+    /// Table of `points`, `None` unless they are at least two, non-decreasing and at most `max`
+    pub fn new(points: &'static [u16], max: u16) -> Option<Self> {
+        let in_order = points.windows(2).all(|pair| pair[0] <= pair[1]);
+        match points.last() {
+            Some(&last) if points.len() >= 2 && in_order && last <= max => Some(Self(points)),
+            _ => None,
+        }
+    }
+
+    /// The points of the table
+    pub fn points(&self) -> &'static [u16] {
+        self.0
+    }
+
+    /// Code for `value` in `0..=max`
+    pub fn code(&self, value: u16, max: u16) -> u16 {
+        let (value, max) = (u32::from(value), u32::from(max));
+        let position = value * (self.0.len() as u32 - 1);
+        let index = (position / max) as usize;
+        let Some(&high) = self.0.get(index + 1) else {
+            return self.0[index];
+        };
+        let low = u32::from(self.0[index]);
+        // non-decreasing, so the step is never negative
+        let step = u32::from(high) - low;
+        (low + (step * (position % max) + max / 2) / max) as u16
+    }
+}
+
+/// Two-point gain and offset correction of the codes of a channel, e.g. from measuring its output at two codes.
+/// The corrected code is `code * (1 + gain_ppm / 10^6) + offset`, rounded to nearest and saturated at the ends
+/// of the code range. The default is no correction at all
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Calibration {
+    /// Codes added after the gain correction
+    pub offset: i16,
+    /// Gain error to correct, in parts per million
+    pub gain_ppm: i32,
+}
+
+impl Calibration {
+    /// Corrected `code` in `0..=max`
+    pub fn apply(&self, code: u16, max: u16) -> u16 {
+        if self.gain_ppm == 0 {
+            // skips the 64-bit division, e.g. for the default
+            return (i32::from(code) + i32::from(self.offset)).clamp(0, i32::from(max)) as u16;
+        }
+        let scaled = i64::from(code) * (1_000_000 + i64::from(self.gain_ppm));
+        // halves round up
+        let code = (scaled + 500_000).div_euclid(1_000_000) + i64::from(self.offset);
+        code.clamp(0, i64::from(max)) as u16
+    }
+}
+
+/// Linear mapping of a channel from engineering units to codes, e.g. milliamps through a V-to-I stage or
+/// the RPM of a motor drive. A value of `milli` thousandths of a unit maps to the code
+/// `offset_code + milli * code_per_unit / (1000 * 2^16)`, rounded to nearest, halves away from zero.
+/// The default is one code per unit from code 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnitScale {
+    /// Code of a value of 0, which may be outside the code range, e.g. for a range that starts above 0 units
+    pub offset_code: i32,
+    /// Codes per unit with 16 fraction bits, [`UnitScale::ONE`] for one code per unit, negative for
+    /// outputs that fall as the value rises
+    pub code_per_unit: i32,
+}
+
+impl Default for UnitScale {
+    fn default() -> Self {
+        Self {
+            offset_code: 0,
+            code_per_unit: Self::ONE,
+        }
+    }
+}
+
+impl UnitScale {
+    /// One code per unit as [`code_per_unit`](Self::code_per_unit)
+    pub const ONE: i32 = 1 << 16;
+
+    /// Thousandths of a unit per code per unit
+    const DIVISOR: i64 = 1000 << 16;
+
+    /// Code of `milli` thousandths of a unit, which may be outside the code range or negative. The
+    /// intermediate product is 64-bit, so no `milli` and scale overflow
+    pub fn code(&self, milli: i32) -> i64 {
+        let scaled = i64::from(milli) * i64::from(self.code_per_unit);
+        i64::from(self.offset_code) + div_round(scaled, Self::DIVISOR)
+    }
+
+    /// Thousandths of a unit of `code`, rounded to nearest, halves away from zero, and saturated at the ends
+    /// of `i32`. `None` with a `code_per_unit` of 0, which maps every value to the offset code
+    pub fn milli_units(&self, code: u16) -> Option<i32> {
+        if self.code_per_unit == 0 {
+            return None;
+        }
+        let scaled = (i64::from(code) - i64::from(self.offset_code)) * Self::DIVISOR;
+        let (scaled, per) = if self.code_per_unit < 0 {
+            (-scaled, -i64::from(self.code_per_unit))
+        } else {
+            (scaled, i64::from(self.code_per_unit))
+        };
+        Some(div_round(scaled, per).clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32)
+    }
+}
+
+/// `n / d` for a positive `d`, rounded to nearest, halves away from zero
+fn div_round(n: i64, d: i64) -> i64 {
+    (n + n.signum() * (d / 2)) / d
+}
+
+/// Deserialized form of a [`VoctCorrection`], checked before use
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawVoctCorrection {
+    offsets: [i16; VoctCorrection::MAX_BREAKPOINTS],
+    len: u8,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawVoctCorrection> for VoctCorrection {
+    type Error = &'static str;
+
+    fn try_from(raw: RawVoctCorrection) -> Result<Self, &'static str> {
+        if raw.len == 0 || usize::from(raw.len) > Self::MAX_BREAKPOINTS {
+            return Err("1 to 11 breakpoints");
+        }
+        Ok(Self {
+            offsets: raw.offsets,
+            len: raw.len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn millivolts_round_to_nearest() {
+        assert_eq!(millivolts_to_code(0, 2500, GAIN::Gain0Vref, 12), Some(0));
+        assert_eq!(millivolts_to_code(1, 2500, GAIN::Gain0Vref, 12), Some(2));
+        assert_eq!(
+            millivolts_to_code(1250, 2500, GAIN::Gain0Vref, 12),
+            Some(2048)
+        );
+        assert_eq!(
+            millivolts_to_code(1250, 2500, GAIN::Gain02Vref, 12),
+            Some(1024)
+        );
+        assert_eq!(
+            millivolts_to_code(1250, 2500, GAIN::Gain0Vref, 8),
+            Some(128)
+        );
+        assert_eq!(millivolts_to_code(1, 0, GAIN::Gain0Vref, 12), None);
+    }
+
+    #[test]
+    fn millivolts_over_full_scale_have_no_code() {
+        assert_eq!(
+            millivolts_to_code(4095, 4096, GAIN::Gain0Vref, 12),
+            Some(4095)
+        );
+        assert_eq!(millivolts_to_code(4096, 4096, GAIN::Gain0Vref, 12), None);
+        assert_eq!(
+            millivolts_to_code(4095, 2048, GAIN::Gain02Vref, 12),
+            Some(4095)
+        );
+        assert_eq!(millivolts_to_code(4096, 2048, GAIN::Gain02Vref, 12), None);
+        // 2499 mV is 4094.4 LSB, 2500 mV would be 4096
+        assert_eq!(
+            millivolts_to_code(2499, 2500, GAIN::Gain0Vref, 12),
+            Some(4094)
+        );
+        assert_eq!(
+            millivolts_to_code(u32::MAX, 2500, GAIN::Gain02Vref, 12),
+            None
+        );
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn ratios_span_the_code_range() {
+        assert_eq!(ratio_to_code(0.0, 12), Some(0));
+        assert_eq!(ratio_to_code(0.5, 12), Some(2048));
+        assert_eq!(ratio_to_code(1.0, 12), Some(4095));
+        assert_eq!(ratio_to_code(1.0, 8), Some(255));
+        assert_eq!(ratio_to_code(0.4999 / 4095.0, 12), Some(0));
+        assert_eq!(ratio_to_code(0.5001 / 4095.0, 12), Some(1));
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn ratios_out_of_range_are_clamped_but_nan_is_rejected() {
+        assert_eq!(ratio_to_code(-0.0001, 12), Some(0));
+        assert_eq!(ratio_to_code(-0.0, 12), Some(0));
+        assert_eq!(ratio_to_code(1.0001, 12), Some(4095));
+        assert_eq!(ratio_to_code(f32::INFINITY, 12), Some(4095));
+        assert_eq!(ratio_to_code(f32::NAN, 12), None);
+    }
+
+    #[test]
+    fn scaled_u16_rounds_at_the_midpoints() {
+        assert_eq!(scaled_u16_to_code(0, 12), 0);
+        assert_eq!(scaled_u16_to_code(65535, 12), 4095);
+        // code 0.5 is at 8.002, code 4094.5 at 65527.0
+        assert_eq!(scaled_u16_to_code(8, 12), 0);
+        assert_eq!(scaled_u16_to_code(9, 12), 1);
+        assert_eq!(scaled_u16_to_code(65526, 12), 4094);
+        assert_eq!(scaled_u16_to_code(65527, 12), 4095);
+        assert_eq!(scaled_u16_to_code(32767, 12), 2047);
+        assert_eq!(scaled_u16_to_code(32768, 12), 2048);
+        assert_eq!(scaled_u16_to_code(32767, 8), 127);
+        assert_eq!(scaled_u16_to_code(32768, 8), 128);
+        assert_eq!(scaled_u16_to_code(65535, 10), 1023);
+    }
+
+    #[test]
+    fn i16_anchors_are_min_mid_and_full_scale() {
+        for (bits, mid, max) in [(8, 128, 255), (10, 512, 1023), (12, 2048, 4095)] {
+            assert_eq!(i16_to_code(i16::MIN, bits), 0);
+            assert_eq!(i16_to_code(0, bits), mid);
+            assert_eq!(i16_to_code(i16::MAX, bits), max);
+        }
+        assert_eq!(i16_to_code(-1, 12), 2047);
+    }
+
+    #[test]
+    fn i16_codes_are_monotonic_in_steps_of_one() {
+        for bits in [8, 10, 12] {
+            let mut last = 0;
+            for value in i16::MIN..=i16::MAX {
+                let code = i16_to_code(value, bits);
+                assert!(code == last || code == last + 1, "{value} -> {code}");
+                last = code;
+            }
+        }
+    }
+
+    #[cfg(feature = "fixed")]
+    #[test]
+    fn unsigned_fractions_round_and_saturate() {
+        assert_eq!(u0f16_to_code(U0F16::ZERO, 12), 0);
+        assert_eq!(u0f16_to_code(U0F16::from_num(0.5), 12), 2048);
+        assert_eq!(u0f16_to_code(U0F16::from_bits(7), 12), 0);
+        assert_eq!(u0f16_to_code(U0F16::from_bits(8), 12), 1);
+        assert_eq!(u0f16_to_code(U0F16::from_bits(0xffe7), 12), 4094);
+        assert_eq!(u0f16_to_code(U0F16::from_bits(0xffe8), 12), 4095);
+        assert_eq!(u0f16_to_code(U0F16::MAX, 12), 4095);
+        assert_eq!(u0f16_to_code(U0F16::MAX, 8), 255);
+        assert_eq!(u0f16_to_code(U0F16::from_num(0.25), 10), 256);
+    }
+
+    #[cfg(feature = "fixed")]
+    #[test]
+    fn signed_fractions_are_centered_on_midscale() {
+        const MID: u16 = i1f15_to_code(I1F15::ZERO, 12);
+        assert_eq!(MID, 2048);
+        assert_eq!(i1f15_to_code(I1F15::NEG_ONE, 12), 0);
+        assert_eq!(i1f15_to_code(I1F15::from_num(-0.5), 12), 1024);
+        assert_eq!(i1f15_to_code(I1F15::MAX, 12), 4095);
+        assert_eq!(i1f15_to_code(I1F15::ZERO, 8), 128);
+    }
+
+    const MODES: [Rounding; 4] = [
+        Rounding::Nearest,
+        Rounding::Floor,
+        Rounding::Ceil,
+        Rounding::TowardZero,
+    ];
+
+    /// `n / d` rounded by `mode` from the quotient and remainder in 128 bits, with `zero` the code of 0
+    fn reference(n: u128, d: u128, mode: Rounding, zero: u128) -> u128 {
+        let (quotient, remainder) = (n / d, n % d);
+        let up = match mode {
+            Rounding::Nearest => 2 * remainder >= d,
+            Rounding::Floor => false,
+            Rounding::Ceil => remainder != 0,
+            Rounding::TowardZero => remainder != 0 && quotient < zero,
+        };
+        quotient + u128::from(up)
+    }
+
+    #[test]
+    fn rounding_modes_pin_halfway_values() {
+        let mv =
+            |mv| MODES.map(|mode| millivolts_to_code_rounded(mv, 8192, GAIN::Gain0Vref, 12, mode));
+        // half a code per millivolt
+        assert_eq!(mv(1), [Some(1), Some(0), Some(1), Some(0)]);
+        assert_eq!(mv(3), [Some(2), Some(1), Some(2), Some(1)]);
+        assert_eq!(mv(4), [Some(2); 4]);
+        assert_eq!(mv(8189), [Some(4095), Some(4094), Some(4095), Some(4094)]);
+        assert_eq!(mv(8190), [Some(4095); 4]);
+        assert_eq!(mv(8191), [None, Some(4095), None, Some(4095)]);
+
+        let scaled = |value| MODES.map(|mode| scaled_u16_to_code_rounded(value, 12, mode));
+        assert_eq!(scaled(1), [0, 0, 1, 0]);
+        assert_eq!(scaled(0xffff), [4095; 4]);
+
+        // 2047.47, 2047.53 and 2047.59 are all below midscale, so round up towards zero
+        let bipolar = |value| MODES.map(|mode| i16_to_code_rounded(value, 12, mode));
+        assert_eq!(bipolar(-1), [2047, 2047, 2048, 2048]);
+        assert_eq!(bipolar(0), [2048, 2047, 2048, 2048]);
+        assert_eq!(bipolar(1), [2048, 2047, 2048, 2048]);
+        assert_eq!(bipolar(i16::MIN), [0; 4]);
+        assert_eq!(bipolar(i16::MAX), [4095; 4]);
+    }
+
+    #[test]
+    fn integer_conversions_match_a_wide_reference() {
+        for bits in [8, 10, 12] {
+            let max = (1u128 << bits) - 1;
+            for mode in MODES {
+                for value in 0..=u16::MAX {
+                    let expected = reference(u128::from(value) * max, 0xffff, mode, 0);
+                    assert_eq!(
+                        u128::from(scaled_u16_to_code_rounded(value, bits, mode)),
+                        expected
+                    );
+                    let bipolar = (value ^ 0x8000) as i16;
+                    let expected =
+                        reference(u128::from(value) * max, 0xffff, mode, 1 << (bits - 1));
+                    assert_eq!(
+                        u128::from(i16_to_code_rounded(bipolar, bits, mode)),
+                        expected
+                    );
+                }
+                for (vref_mv, gain, factor) in [
+                    (2048, GAIN::Gain0Vref, 1),
+                    (2500, GAIN::Gain0Vref, 1),
+                    (3300, GAIN::Gain02Vref, 2),
+                    (u32::MAX, GAIN::Gain02Vref, 2),
+                ] {
+                    let full_scale = u128::from(vref_mv) * factor;
+                    for mv in (0..=7000).chain([u32::MAX - 1, u32::MAX]) {
+                        let expected = reference(u128::from(mv) << bits, full_scale, mode, 0);
+                        let expected = (expected <= max).then_some(expected as u16);
+                        assert_eq!(
+                            millivolts_to_code_rounded(mv, vref_mv, gain, bits, mode),
+                            expected
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "fixed")]
+    #[test]
+    fn fractions_match_a_wide_reference() {
+        for bits in [8, 10, 12] {
+            let max = (1u128 << bits) - 1;
+            for mode in MODES {
+                for raw in 0..=u16::MAX {
+                    let expected = reference(u128::from(raw), 1 << (16 - bits), mode, 0).min(max);
+                    assert_eq!(
+                        u128::from(u0f16_to_code_rounded(U0F16::from_bits(raw), bits, mode)),
+                        expected
+                    );
+                    let expected =
+                        reference(u128::from(raw), 1 << (16 - bits), mode, 1 << (bits - 1));
+                    let bipolar = I1F15::from_bits((raw ^ 0x8000) as i16);
+                    assert_eq!(
+                        u128::from(i1f15_to_code_rounded(bipolar, bits, mode)),
+                        expected.min(max)
+                    );
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn ratios_round_by_mode() {
+        let ratio = |ratio| MODES.map(|mode| ratio_to_code_rounded(ratio, 12, mode));
+        // 2047.5
+        assert_eq!(ratio(0.5), [Some(2048), Some(2047), Some(2048), Some(2047)]);
+        assert_eq!(ratio(1.0), [Some(4095); 4]);
+        assert_eq!(ratio(f32::NAN), [None; 4]);
+    }
+
+    #[cfg(feature = "fixed")]
+    #[test]
+    fn fractions_round_by_mode() {
+        let unsigned =
+            |raw| MODES.map(|mode| u0f16_to_code_rounded(U0F16::from_bits(raw), 12, mode));
+        // 0.5 and 4095.5 codes
+        assert_eq!(unsigned(8), [1, 0, 1, 0]);
+        assert_eq!(unsigned(0xfff8), [4095; 4]);
+        assert_eq!(unsigned(0xffff), [4095; 4]);
+        let signed = |raw| MODES.map(|mode| i1f15_to_code_rounded(I1F15::from_bits(raw), 12, mode));
+        // 2047.5 and 2048.5 codes, halves round up on both sides of midscale
+        assert_eq!(signed(-8), [2048, 2047, 2048, 2048]);
+        assert_eq!(signed(8), [2049, 2048, 2049, 2048]);
+        assert_eq!(signed(0), [2048; 4]);
+    }
+
+    #[test]
+    fn notes_cover_the_midi_range() {
+        // 11 V full scale, 1000 / 12 mV per semitone is 31.03 codes
+        #[rustfmt::skip]
+        const CODES: [u16; 128] = [
+            0, 31, 62, 93, 124, 155, 186, 217, 248, 279, 310, 341,
+            372, 403, 434, 465, 496, 528, 559, 590, 621, 652, 683, 714,
+            745, 776, 807, 838, 869, 900, 931, 962, 993, 1024, 1055, 1086,
+            1117, 1148, 1179, 1210, 1241, 1272, 1303, 1334, 1365, 1396, 1427, 1458,
+            1489, 1520, 1552, 1583, 1614, 1645, 1676, 1707, 1738, 1769, 1800, 1831,
+            1862, 1893, 1924, 1955, 1986, 2017, 2048, 2079, 2110, 2141, 2172, 2203,
+            2234, 2265, 2296, 2327, 2358, 2389, 2420, 2451, 2482, 2513, 2544, 2576,
+            2607, 2638, 2669, 2700, 2731, 2762, 2793, 2824, 2855, 2886, 2917, 2948,
+            2979, 3010, 3041, 3072, 3103, 3134, 3165, 3196, 3227, 3258, 3289, 3320,
+            3351, 3382, 3413, 3444, 3475, 3506, 3537, 3568, 3600, 3631, 3662, 3693,
+            3724, 3755, 3786, 3817, 3848, 3879, 3910, 3941,
+        ];
+        for (note, code) in CODES.into_iter().enumerate() {
+            assert_eq!(
+                note_to_code(note as u8, 0, 5500, GAIN::Gain02Vref, 12),
+                Some(code)
+            );
+        }
+    }
+
+    #[test]
+    fn notes_out_of_range_have_no_code() {
+        assert_eq!(note_to_code(127, 0, 2500, GAIN::Gain0Vref, 12), None);
+        // 2.5 V reach up to note 29, 2.4167 V
+        assert_eq!(note_to_code(29, 0, 2500, GAIN::Gain0Vref, 12), Some(3959));
+        assert_eq!(note_to_code(30, 0, 2500, GAIN::Gain0Vref, 12), None);
+        assert_eq!(note_to_code(59, 60, 2500, GAIN::Gain0Vref, 12), None);
+        assert_eq!(note_to_code(60, 60, 2500, GAIN::Gain0Vref, 12), Some(0));
+        assert_eq!(note_to_code(72, 60, 0, GAIN::Gain0Vref, 12), None);
+    }
+
+    #[test]
+    fn adjacent_semitones_get_distinct_codes() {
+        for vref_mv in [1000, 2048, 2500, 3300, 5000] {
+            for bits in [8, 10, 12] {
+                let codes = (0..=127)
+                    .map_while(|note| note_to_code(note, 0, vref_mv, GAIN::Gain0Vref, bits));
+                let codes: Vec<_> = codes.collect();
+                assert!(codes.windows(2).all(|pair| pair[0] < pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn voct_offsets_interpolate_between_octaves() {
+        let correction = VoctCorrection::new(&[0, 12, -12]).unwrap();
+        assert_eq!(correction.offset(0), 0);
+        assert_eq!(correction.offset(5), 5);
+        assert_eq!(correction.offset(12), 12);
+        // 12 - 24 * 3 / 12 and 12 - 24 * 9 / 12
+        assert_eq!(correction.offset(15), 6);
+        assert_eq!(correction.offset(21), -6);
+        let correction = VoctCorrection::new(&[0, 1, -1]).unwrap();
+        // halves round away from zero: 0.5 and 1 - 1.0, 1 - 1.5
+        assert_eq!(correction.offset(6), 1);
+        assert_eq!(correction.offset(18), 0);
+        assert_eq!(correction.offset(21), -1);
+    }
+
+    #[test]
+    fn voct_offsets_hold_from_the_last_breakpoint() {
+        let correction = VoctCorrection::new(&[3, 7]).unwrap();
+        assert_eq!(correction.offset(12), 7);
+        assert_eq!(correction.offset(13), 7);
+        assert_eq!(correction.offset(127), 7);
+        let single = VoctCorrection::new(&[-2]).unwrap();
+        assert_eq!(single.offset(0), -2);
+        assert_eq!(single.offset(127), -2);
+        let full = VoctCorrection::new(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 10]).unwrap();
+        assert_eq!(full.offset(114), 5);
+        assert_eq!(full.offset(127), 10);
+    }
+
+    #[test]
+    fn voct_corrections_keep_codes_in_range() {
+        assert!(VoctCorrection::new(&[]).is_none());
+        assert!(VoctCorrection::new(&[0; 12]).is_none());
+        let correction = VoctCorrection::new(&[-1, i16::MAX, i16::MIN]).unwrap();
+        assert_eq!(correction.apply(0, 0, 12), None);
+        assert_eq!(correction.apply(1, 0, 12), Some(0));
+        assert_eq!(correction.apply(0, 12, 12), None);
+        // 32767 - 32767.5
+        assert_eq!(correction.offset(18), -1);
+    }
+
+    #[test]
+    fn luts_are_checked() {
+        assert!(Lut::new(&[], 4095).is_none());
+        assert!(Lut::new(&[0], 4095).is_none());
+        assert!(Lut::new(&[0, 2, 1], 4095).is_none());
+        assert!(Lut::new(&[0, 4096], 4095).is_none());
+        assert!(Lut::new(&[0, 256], 255).is_none());
+        assert!(Lut::new(&[0, 0, 255], 255).is_some());
+    }
+
+    #[test]
+    fn luts_interpolate_between_points() {
+        let identity = Lut::new(&[0, 4095], 4095).unwrap();
+        for value in [0, 1, 2047, 4094, 4095] {
+            assert_eq!(identity.code(value, 4095), value);
+        }
+        // points at values 0, 1365, 2730 and 4095
+        let lut = Lut::new(&[100, 100, 1000, 4000], 4095).unwrap();
+        assert_eq!(lut.code(0, 4095), 100);
+        assert_eq!(lut.code(1365, 4095), 100);
+        assert_eq!(lut.code(2730, 4095), 1000);
+        assert_eq!(lut.code(4095, 4095), 4000);
+        assert_eq!(lut.code(2048, 4095), 550);
+        // 2 * 1023 / 4095 is just under a half, 2 * 1024 / 4095 just over
+        let lut = Lut::new(&[0, 2], 4095).unwrap();
+        assert_eq!(lut.code(1023, 4095), 0);
+        assert_eq!(lut.code(1024, 4095), 1);
+        let lut = Lut::new(&[0, 10, 255], 255).unwrap();
+        assert_eq!(lut.code(64, 255), 5);
+        assert_eq!(lut.code(255, 255), 255);
+    }
+
+    #[test]
+    fn calibration_rounds_and_saturates() {
+        let none = Calibration::default();
+        for code in [0, 1, 2048, 4095] {
+            assert_eq!(none.apply(code, 4095), code);
+        }
+        let gain = Calibration {
+            offset: 0,
+            gain_ppm: -250,
+        };
+        // 2000 * 0.99975 = 1999.5 and 1998 * 0.99975 = 1997.5005
+        assert_eq!(gain.apply(2000, 4095), 2000);
+        assert_eq!(gain.apply(1998, 4095), 1998);
+        assert_eq!(gain.apply(1999, 4095), 1999);
+        let low = Calibration {
+            offset: -10,
+            gain_ppm: 0,
+        };
+        assert_eq!(low.apply(5, 4095), 0);
+        let high = Calibration {
+            offset: 10,
+            gain_ppm: 10_000,
+        };
+        assert_eq!(high.apply(4000, 4095), 4050);
+        assert_eq!(high.apply(4080, 4095), 4095);
+        assert_eq!(high.apply(250, 255), 255);
+        let inverted = Calibration {
+            offset: 0,
+            gain_ppm: -2_000_000,
+        };
+        assert_eq!(inverted.apply(4095, 4095), 0);
+    }
+
+    #[test]
+    fn unit_scales_round_half_away_from_zero() {
+        // 4095 codes for 20 mA
+        let ma = UnitScale {
+            offset_code: 0,
+            code_per_unit: 4095 * UnitScale::ONE / 20,
+        };
+        assert_eq!(ma.code(0), 0);
+        assert_eq!(ma.code(20_000), 4095);
+        // 2047.5
+        assert_eq!(ma.code(10_000), 2048);
+        assert_eq!(ma.milli_units(4095), Some(20_000));
+        assert_eq!(ma.milli_units(0), Some(0));
+
+        let codes = UnitScale::default();
+        assert_eq!(codes.code(1_499), 1);
+        assert_eq!(codes.code(1_500), 2);
+        assert_eq!(codes.code(-1_500), -2);
+        assert_eq!(codes.milli_units(3), Some(3_000));
+
+        let falling = UnitScale {
+            offset_code: 4000,
+            code_per_unit: -UnitScale::ONE / 2,
+        };
+        assert_eq!(falling.code(999), 4000);
+        // -0.5
+        assert_eq!(falling.code(1_000), 3999);
+        assert_eq!(falling.code(8_000_000), 0);
+        assert_eq!(falling.milli_units(0), Some(8_000_000));
+        assert_eq!(falling.milli_units(4001), Some(-2_000));
+    }
+
+    #[test]
+    fn unit_scales_dont_overflow() {
+        let steep = UnitScale {
+            offset_code: i32::MAX,
+            code_per_unit: i32::MAX,
+        };
+        assert_eq!(
+            steep.code(i32::MAX),
+            i64::from(i32::MAX)
+                + (i64::from(i32::MAX) * i64::from(i32::MAX) + 32_768_000) / 65_536_000
+        );
+        assert!(steep.code(i32::MIN) < 0);
+        let shallow = UnitScale {
+            offset_code: i32::MIN,
+            code_per_unit: 1,
+        };
+        assert_eq!(shallow.milli_units(4095), Some(i32::MAX));
+        let flat = UnitScale {
+            offset_code: 100,
+            code_per_unit: 0,
+        };
+        assert_eq!(flat.code(i32::MIN), 100);
+        assert_eq!(flat.milli_units(100), None);
+        let min = UnitScale {
+            offset_code: 0,
+            code_per_unit: i32::MIN,
+        };
+        // -32768 codes per unit
+        assert_eq!(min.milli_units(4095), Some(-125));
+    }
+}
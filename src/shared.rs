@@ -0,0 +1,264 @@
+//! Driver shared between execution contexts, enabled with the `critical-section` feature.
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+
+use crate::{Ad5328, Ad5328Config, Channel, DacChannel, Error, Transport};
+
+/// Owns an [`Ad5328`] behind a `critical_section::Mutex` so it can be driven from several contexts,
+/// e.g. the main loop and an interrupt handler.
+///
+/// Each call on a [`SharedHandle`] takes the critical section only for the frames that call emits,
+/// so frames from different contexts never interleave and no context can hold the driver across calls.
+pub struct Ad5328Shared<SPI, EN> {
+    dac: Mutex<RefCell<Ad5328<SPI, EN>>>,
+}
+
+impl<SPI, EN> Ad5328Shared<SPI, EN> {
+    /// Take ownership of an initialized driver
+    pub const fn new(dac: Ad5328<SPI, EN>) -> Self {
+        Self {
+            dac: Mutex::new(RefCell::new(dac)),
+        }
+    }
+
+    /// Get a cheap, copyable handle to the shared driver
+    pub fn handle(&self) -> SharedHandle<'_, SPI, EN> {
+        SharedHandle { shared: self }
+    }
+
+    /// Split the driver into one [`DacOutput`] per channel, A first.
+    /// Once all outputs are dropped, [`into_inner`](Self::into_inner) joins them back into the driver
+    pub fn split(&self) -> [DacOutput<'_, SPI, EN>; 8] {
+        core::array::from_fn(|n| DacOutput {
+            handle: self.handle(),
+            channel: Channel::from(n as u8),
+        })
+    }
+
+    /// Give back the driver once no handles are left
+    pub fn into_inner(self) -> Ad5328<SPI, EN> {
+        self.dac.into_inner().into_inner()
+    }
+}
+
+/// Handle to an [`Ad5328Shared`], see there for the locking behavior
+pub struct SharedHandle<'a, SPI, EN> {
+    shared: &'a Ad5328Shared<SPI, EN>,
+}
+
+impl<SPI, EN> Clone for SharedHandle<'_, SPI, EN> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<SPI, EN> Copy for SharedHandle<'_, SPI, EN> {}
+
+impl<SPI, EN, S, P> SharedHandle<'_, SPI, EN>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+{
+    fn with<R>(&self, f: impl FnOnce(&mut Ad5328<SPI, EN>) -> R) -> R {
+        critical_section::with(|cs| f(&mut self.shared.dac.borrow_ref_mut(cs)))
+    }
+
+    /// See [`Ad5328::configure`]. Both configuration frames are sent within one critical section
+    pub fn configure(&self, config: Ad5328Config) -> Result<(), Error<S, P>> {
+        self.with(|dac| dac.configure(config))
+    }
+
+    /// See [`Ad5328::power_down`]
+    pub fn power_down(&self, channels: [bool; 8]) -> Result<(), Error<S, P>> {
+        self.with(|dac| dac.power_down(channels))
+    }
+
+    /// See [`Ad5328::set_channel`]
+    pub fn set_channel(&self, channel: Channel, value: u16) -> Result<(), Error<S, P>> {
+        self.with(|dac| dac.set_channel(channel, value))
+    }
+}
+
+/// A single channel of an [`Ad5328Shared`], as handed out by [`split`](Ad5328Shared::split)
+pub struct DacOutput<'a, SPI, EN> {
+    handle: SharedHandle<'a, SPI, EN>,
+    channel: Channel,
+}
+
+impl<SPI, EN> DacOutput<'_, SPI, EN> {
+    /// The channel this output drives
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+}
+
+impl<SPI, EN, S, P> DacChannel for DacOutput<'_, SPI, EN>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+{
+    type Error = Error<S, P>;
+
+    fn set_raw(&mut self, value: u16) -> Result<(), Error<S, P>> {
+        self.handle.set_channel(self.channel, value)
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<SPI, EN, S, P> embedded_hal_1::pwm::ErrorType for DacOutput<'_, SPI, EN>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    S: core::fmt::Debug,
+    P: core::fmt::Debug,
+{
+    type Error = Error<S, P>;
+}
+
+/// Lets PWM-style control code drive the output, with the full 12-bit range as duty cycle.
+/// Duty cycles above 4095 are rejected with [`Error::OobMax`], like any other out of bounds value
+#[cfg(feature = "eh1")]
+impl<SPI, EN, S, P> embedded_hal_1::pwm::SetDutyCycle for DacOutput<'_, SPI, EN>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    S: core::fmt::Debug,
+    P: core::fmt::Debug,
+{
+    fn max_duty_cycle(&self) -> u16 {
+        crate::command::MAX_VALUE
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Error<S, P>> {
+        self.set_raw(duty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Event, Log, LogPin};
+    use core::convert::Infallible;
+    use embedded_hal::blocking::spi::Write;
+
+    struct SlowSpi(Log);
+
+    impl Write<u8> for SlowSpi {
+        type Error = Infallible;
+
+        fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+            for &word in words {
+                self.0.push(Event::Bytes(vec![word]));
+                std::thread::yield_now();
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn frames_from_two_contexts_never_interleave() {
+        let log = Log::default();
+        let dac = Ad5328::init(
+            SlowSpi(log.clone()),
+            LogPin(log.clone()),
+            Ad5328Config::default(),
+        )
+        .unwrap();
+        let shared = Ad5328Shared::new(dac);
+        let handle = shared.handle();
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for n in 0..500 {
+                    handle.set_channel(Channel::A, n).unwrap();
+                }
+            });
+            s.spawn(move || {
+                for n in 0..500 {
+                    handle.set_channel(Channel::H, n).unwrap();
+                }
+            });
+        });
+
+        let log = log.events();
+        assert_eq!(log.len(), (2 + 1000) * 4);
+        for frame in log.chunks(4) {
+            assert!(matches!(
+                frame,
+                [
+                    Event::Sync(false),
+                    Event::Bytes(_),
+                    Event::Bytes(_),
+                    Event::Sync(true)
+                ]
+            ));
+        }
+        let _ = shared.into_inner();
+    }
+
+    fn drive<C: DacChannel>(output: &mut C, value: u16) -> Result<(), C::Error> {
+        output.set_raw(value)
+    }
+
+    #[test]
+    fn split_outputs_drive_their_own_channel() {
+        let log = Log::default();
+        let dac = Ad5328::init(
+            SlowSpi(log.clone()),
+            LogPin(log.clone()),
+            Ad5328Config::default(),
+        )
+        .unwrap();
+        let shared = Ad5328Shared::new(dac);
+        let [_, mut b, .., mut h] = shared.split();
+        drive(&mut b, 0x123).unwrap();
+        drive(&mut h, 0xfff).unwrap();
+        assert!(matches!(drive(&mut h, 0x1000), Err(Error::OobMax(4095))));
+
+        let _dac = shared.into_inner();
+        let bytes: Vec<_> = log
+            .events()
+            .iter()
+            .filter_map(|event| match event {
+                Event::Bytes(b) => Some(b[0]),
+                _ => None,
+            })
+            .skip(4)
+            .collect();
+        assert_eq!(bytes, [0x11, 0x23, 0x7f, 0xff]);
+    }
+
+    #[cfg(feature = "eh1")]
+    #[test]
+    fn outputs_drive_pwm_style_code() {
+        use embedded_hal_1::pwm::SetDutyCycle;
+
+        fn drive<T: SetDutyCycle>(t: &mut T) -> Result<(), T::Error> {
+            t.set_duty_cycle_fully_off()?;
+            t.set_duty_cycle_percent(50)?;
+            t.set_duty_cycle_fully_on()
+        }
+
+        let log = Log::default();
+        let dac = Ad5328::init(
+            SlowSpi(log.clone()),
+            LogPin(log.clone()),
+            Ad5328Config::default(),
+        )
+        .unwrap();
+        let shared = Ad5328Shared::new(dac);
+        let [mut a, ..] = shared.split();
+        assert_eq!(a.max_duty_cycle(), 4095);
+        drive(&mut a).unwrap();
+        assert!(matches!(a.set_duty_cycle(4096), Err(Error::OobMax(4095))));
+
+        let bytes: Vec<_> = log
+            .events()
+            .iter()
+            .filter_map(|event| match event {
+                Event::Bytes(b) => Some(b[0]),
+                _ => None,
+            })
+            .skip(4)
+            .collect();
+        assert_eq!(bytes, [0x00, 0x00, 0x07, 0xff, 0x0f, 0xff]);
+    }
+}
@@ -0,0 +1,124 @@
+//! Async driver shared between tasks, enabled with the `embassy-sync` feature.
+
+use core::convert::Infallible;
+
+use embassy_sync::{blocking_mutex::raw::RawMutex, mutex::Mutex};
+use embedded_hal_async::spi::SpiDevice;
+
+use crate::{asynch::Ad5328Async, Ad5328Config, Channel, Error};
+
+/// Owns an [`Ad5328Async`] behind an `embassy_sync::mutex::Mutex` so several tasks can each drive their own channels.
+///
+/// Each call on an [`AsyncSharedHandle`] holds the lock only while its frames are written,
+/// so a slow task can't keep the driver from the others between calls.
+pub struct Ad5328AsyncShared<M: RawMutex, SPI> {
+    dac: Mutex<M, Ad5328Async<SPI>>,
+}
+
+impl<M: RawMutex, SPI> Ad5328AsyncShared<M, SPI> {
+    /// Take ownership of an initialized driver
+    pub const fn new(dac: Ad5328Async<SPI>) -> Self {
+        Self {
+            dac: Mutex::new(dac),
+        }
+    }
+
+    /// Get a cheap, copyable handle to the shared driver
+    pub fn handle(&self) -> AsyncSharedHandle<'_, M, SPI> {
+        AsyncSharedHandle { shared: self }
+    }
+
+    /// Give back the driver once no handles are left
+    pub fn into_inner(self) -> Ad5328Async<SPI> {
+        self.dac.into_inner()
+    }
+}
+
+/// Handle to an [`Ad5328AsyncShared`], see there for the locking behavior
+pub struct AsyncSharedHandle<'a, M: RawMutex, SPI> {
+    shared: &'a Ad5328AsyncShared<M, SPI>,
+}
+
+impl<M: RawMutex, SPI> Clone for AsyncSharedHandle<'_, M, SPI> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M: RawMutex, SPI> Copy for AsyncSharedHandle<'_, M, SPI> {}
+
+impl<M: RawMutex, SPI> AsyncSharedHandle<'_, M, SPI>
+where
+    SPI: SpiDevice<u8>,
+{
+    /// See [`Ad5328Async::configure`]. Both configuration frames are sent under one lock
+    pub async fn configure(
+        &self,
+        config: Ad5328Config,
+    ) -> Result<(), Error<SPI::Error, Infallible>> {
+        self.shared.dac.lock().await.configure(config).await
+    }
+
+    /// See [`Ad5328Async::power_down`]
+    pub async fn power_down(
+        &self,
+        channels: [bool; 8],
+    ) -> Result<(), Error<SPI::Error, Infallible>> {
+        self.shared.dac.lock().await.power_down(channels).await
+    }
+
+    /// See [`Ad5328Async::set_channel`]
+    pub async fn set_channel(
+        &self,
+        channel: Channel,
+        value: u16,
+    ) -> Result<(), Error<SPI::Error, Infallible>> {
+        self.shared
+            .dac
+            .lock()
+            .await
+            .set_channel(channel, value)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+    use embedded_hal_mock::eh1::spi::{Mock, Transaction};
+    use futures::{executor::block_on, future::join};
+
+    fn frame(bytes: [u8; 2]) -> [Transaction<u8>; 3] {
+        [
+            Transaction::transaction_start(),
+            Transaction::write_vec(bytes.to_vec()),
+            Transaction::transaction_end(),
+        ]
+    }
+
+    fn assert_send<T: Send>(t: T) -> T {
+        t
+    }
+
+    #[test]
+    fn concurrent_set_channel_calls_both_complete() {
+        let expectations: Vec<_> = [[0x80, 0x0c], [0xa0, 0x01], [0x00, 0x01], [0x70, 0x02]]
+            .into_iter()
+            .flat_map(frame)
+            .collect();
+        let mut spi = Mock::new(&expectations);
+        block_on(async {
+            let dac = Ad5328Async::init(spi.clone(), Ad5328Config::default())
+                .await
+                .unwrap();
+            let shared = Ad5328AsyncShared::<CriticalSectionRawMutex, _>::new(dac);
+            let a = assert_send(shared.handle());
+            let h = a;
+            let (ra, rh) = join(a.set_channel(Channel::A, 1), h.set_channel(Channel::H, 2)).await;
+            ra.unwrap();
+            rh.unwrap();
+        });
+        spi.done();
+    }
+}
@@ -0,0 +1,517 @@
+//! Register-level model of the AD5328 for host-side tests, enabled with the `sim` feature.
+
+use core::fmt;
+
+use embedded_hal::{blocking::spi::Write, digital::v2::OutputPin};
+
+use crate::{
+    command::{self, Decoded},
+    Ad5328Config, Channel, BUF, GAIN, LDAC, VDD,
+};
+
+/// Voltages supplied to the chip, in millivolts
+#[derive(Debug, Clone, Copy)]
+pub struct Supplies {
+    /// Reference input of channels A...D
+    pub vref_ad_mv: u32,
+    /// Reference input of channels E...H
+    pub vref_eh_mv: u32,
+    /// Supply voltage
+    pub vdd_mv: u32,
+}
+
+/// Maximum number of faults pending on an [`Ad5328Model`] at once
+pub const MAX_FAULTS: usize = 8;
+
+/// Misbehaviour of the bus, injected with [`Ad5328Model::inject`].
+///
+/// `n` counts the calls of `write` on the model from 0, whether they failed, were dropped or went through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail the `n`th write with [`InjectedError`], before any of its bytes reach the chip
+    SpiErrorAt(usize),
+    /// Accept the `n`th write but discard its bytes, as the chip does for a frame with a glitch on SYNC
+    DropFrame(usize),
+}
+
+/// Error returned by an injected fault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InjectedError;
+
+impl fmt::Display for InjectedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("injected fault")
+    }
+}
+
+impl core::error::Error for InjectedError {}
+
+/// Stands in for the SPI bus of an [`Ad5328`](crate::Ad5328) and keeps the state the written frames leave the chip in.
+///
+/// Bytes are decoded as 16-bit frames MSB first, exactly as the chip shifts them in.
+/// SYNC is not observed, so pair the model with an enable pin that does nothing, e.g. [`NoEnable`](crate::NoEnable):
+///
+/// ```
+/// use ad5328::{sim::Ad5328Model, Ad5328, Ad5328Config, Channel, LDAC};
+///
+/// let config = Ad5328Config {
+///     ldac: LDAC::LdacLow,
+///     ..Default::default()
+/// };
+/// let mut dac = Ad5328::init_without_enable(Ad5328Model::new(), config).unwrap();
+/// dac.set_channel(Channel::C, 0x123).unwrap();
+/// let (model, _) = dac.release();
+/// assert_eq!(model.channel_code(Channel::C), 0x123);
+/// assert_eq!(model.dac_code(Channel::C), 0x123);
+/// assert!(model.is_powered(Channel::C));
+/// ```
+pub struct Ad5328Model {
+    input: [u16; 8],
+    dac: [u16; 8],
+    gain: (bool, bool),
+    buf: (bool, bool),
+    vdd: (bool, bool),
+    ldac: u16,
+    power_down: u8,
+    pending: Option<u8>,
+    writes: usize,
+    faults: [Option<Fault>; MAX_FAULTS],
+    fired: [Option<Fault>; MAX_FAULTS],
+}
+
+impl Default for Ad5328Model {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ad5328Model {
+    /// A chip right after power-on: all registers and control bits cleared
+    pub fn new() -> Self {
+        Self {
+            input: [0; 8],
+            dac: [0; 8],
+            gain: (false, false),
+            buf: (false, false),
+            vdd: (false, false),
+            ldac: 0,
+            power_down: 0,
+            pending: None,
+            writes: 0,
+            faults: [None; MAX_FAULTS],
+            fired: [None; MAX_FAULTS],
+        }
+    }
+
+    /// Make the model misbehave on a future write. Panics if [`MAX_FAULTS`] faults are already pending
+    pub fn inject(&mut self, fault: Fault) {
+        let slot = self.faults.iter_mut().find(|slot| slot.is_none());
+        *slot.expect("too many pending faults") = Some(fault);
+    }
+
+    /// The last [`MAX_FAULTS`] injected faults that fired, in the order they did
+    pub fn fired(&self) -> impl Iterator<Item = Fault> + '_ {
+        self.fired.iter().map_while(|fault| *fault)
+    }
+
+    fn take_fault(&mut self, n: usize) -> Option<Fault> {
+        let slot = self.faults.iter_mut().find(
+            |slot| matches!(slot, Some(Fault::SpiErrorAt(at) | Fault::DropFrame(at)) if *at == n),
+        )?;
+        let fault = slot.take()?;
+        // only the latest ones are kept once the record is full
+        if let Some(free) = self.fired.iter_mut().find(|slot| slot.is_none()) {
+            *free = Some(fault);
+        } else {
+            self.fired.rotate_left(1);
+            self.fired[MAX_FAULTS - 1] = Some(fault);
+        }
+        Some(fault)
+    }
+
+    fn apply(&mut self, cmd: u16) {
+        match command::decode(cmd) {
+            Decoded::SetChannel { channel, value } => {
+                let n = usize::from(u8::from(channel));
+                self.input[n] = value;
+                if self.ldac == 0 {
+                    self.dac[n] = value;
+                }
+            }
+            Decoded::Control { gain, buf, vdd } => {
+                self.gain = gain;
+                self.buf = buf;
+                self.vdd = vdd;
+            }
+            // the reserved mode is ignored by the chip
+            Decoded::Ldac(0b11) => {}
+            Decoded::Ldac(mode) => {
+                self.ldac = mode;
+                if mode != 1 {
+                    self.pulse_ldac();
+                }
+            }
+            Decoded::PowerDown(channels) => self.power_down = channels,
+            Decoded::Reset { full } => {
+                if full {
+                    *self = Self {
+                        pending: self.pending,
+                        writes: self.writes,
+                        faults: self.faults,
+                        fired: self.fired,
+                        ..Self::new()
+                    };
+                } else {
+                    self.input = [0; 8];
+                    self.dac = [0; 8];
+                }
+            }
+        }
+    }
+
+    /// Transfer all input registers to the DAC registers, as a pulse on the LDAC pin would
+    pub fn pulse_ldac(&mut self) {
+        self.dac = self.input;
+    }
+
+    /// Input register of `channel`, the last value written to it
+    pub fn channel_code(&self, channel: Channel) -> u16 {
+        self.input[usize::from(u8::from(channel))]
+    }
+
+    /// DAC register of `channel`, the value currently driving its output
+    pub fn dac_code(&self, channel: Channel) -> u16 {
+        self.dac[usize::from(u8::from(channel))]
+    }
+
+    /// Whether `channel` is powered up
+    pub fn is_powered(&self, channel: Channel) -> bool {
+        self.power_down & (1 << u8::from(channel)) == 0
+    }
+
+    /// Ideal output voltage of `channel` in millivolts, rounded down.
+    ///
+    /// With the VDD bit of the channel's group set, the reference is VDD and the output range 0 V to VDD,
+    /// regardless of the GAIN and BUF bits. Otherwise the range is 0 V to Vref or 2*Vref depending on GAIN.
+    /// The output can't swing above VDD, and is 0 V while the channel is powered down.
+    pub fn output_millivolts(&self, channel: Channel, supplies: &Supplies) -> u32 {
+        if !self.is_powered(channel) {
+            return 0;
+        }
+        let second_group = u8::from(channel) >= 4;
+        let pick = |(ad, eh): (bool, bool)| if second_group { eh } else { ad };
+        let (vref, gain) = if pick(self.vdd) {
+            (supplies.vdd_mv, 1)
+        } else if second_group {
+            (supplies.vref_eh_mv, 1 + u32::from(pick(self.gain)))
+        } else {
+            (supplies.vref_ad_mv, 1 + u32::from(pick(self.gain)))
+        };
+        let out = u32::from(self.dac_code(channel)) * vref * gain / 4096;
+        out.min(supplies.vdd_mv)
+    }
+
+    /// The LDAC mode last programmed. After [`LDAC::LdacSingleUpdate`] the DAC registers stay latched
+    /// until the next LDAC command or [`pulse_ldac`](Self::pulse_ldac)
+    pub fn ldac_mode(&self) -> LDAC {
+        match self.ldac {
+            0 => LDAC::LdacLow,
+            1 => LDAC::LdacHigh,
+            _ => LDAC::LdacSingleUpdate,
+        }
+    }
+
+    /// The programmed configuration, for channels A...D and E...H respectively
+    pub fn config(&self) -> Ad5328Config {
+        let gain = |b| if b { GAIN::Gain02Vref } else { GAIN::Gain0Vref };
+        let buf = |b| if b { BUF::Buffered } else { BUF::Unbuffered };
+        let vdd = |b| if b { VDD::VddAsRef } else { VDD::ExternalRef };
+        Ad5328Config {
+            gain: (gain(self.gain.0), gain(self.gain.1)),
+            buf: (buf(self.buf.0), buf(self.buf.1)),
+            vdd: (vdd(self.vdd.0), vdd(self.vdd.1)),
+            ldac: self.ldac_mode(),
+        }
+    }
+}
+
+impl Write<u8> for Ad5328Model {
+    type Error = InjectedError;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), InjectedError> {
+        let n = self.writes;
+        self.writes += 1;
+        match self.take_fault(n) {
+            Some(Fault::SpiErrorAt(_)) => return Err(InjectedError),
+            Some(Fault::DropFrame(_)) => {
+                self.pending = None;
+                return Ok(());
+            }
+            None => {}
+        }
+        for &byte in words {
+            match self.pending.take() {
+                Some(msb) => self.apply(u16::from_be_bytes([msb, byte])),
+                None => self.pending = Some(byte),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Enable pin that does nothing but fail on demand, to run an [`Ad5328`](crate::Ad5328) on both a model and a pin
+#[derive(Default)]
+pub struct SimPin {
+    calls: usize,
+    fail_at: Option<usize>,
+    fired: bool,
+}
+
+impl SimPin {
+    /// A pin that never fails
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A pin failing its `n`th call of `set_low` or `set_high`, counting from 0
+    pub fn failing_at(n: usize) -> Self {
+        Self {
+            fail_at: Some(n),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the injected failure fired
+    pub fn fired(&self) -> bool {
+        self.fired
+    }
+
+    fn call(&mut self) -> Result<(), InjectedError> {
+        let n = self.calls;
+        self.calls += 1;
+        if self.fail_at == Some(n) {
+            self.fired = true;
+            return Err(InjectedError);
+        }
+        Ok(())
+    }
+}
+
+impl OutputPin for SimPin {
+    type Error = InjectedError;
+
+    fn set_low(&mut self) -> Result<(), InjectedError> {
+        self.call()
+    }
+
+    fn set_high(&mut self) -> Result<(), InjectedError> {
+        self.call()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ad5328, Error, NoEnable};
+
+    fn dac(config: Ad5328Config) -> Ad5328<Ad5328Model, NoEnable> {
+        Ad5328::init_without_enable(Ad5328Model::new(), config).unwrap()
+    }
+
+    #[test]
+    fn ldac_high_latches_until_single_update() {
+        let mut dac = dac(Ad5328Config::default());
+        dac.set_channel(Channel::A, 100).unwrap();
+        dac.set_channel(Channel::H, 4095).unwrap();
+        let (mut model, _) = dac.release();
+        assert!(matches!(model.ldac_mode(), LDAC::LdacHigh));
+        assert_eq!(model.channel_code(Channel::H), 4095);
+        assert_eq!(model.dac_code(Channel::H), 0);
+
+        let mut dac = Ad5328::init_without_enable(
+            model,
+            Ad5328Config {
+                ldac: LDAC::LdacSingleUpdate,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        dac.set_channel(Channel::A, 200).unwrap();
+        (model, _) = dac.release();
+        assert_eq!(model.dac_code(Channel::A), 100);
+        assert_eq!(model.dac_code(Channel::H), 4095);
+        model.pulse_ldac();
+        assert_eq!(model.dac_code(Channel::A), 200);
+    }
+
+    #[test]
+    fn resets_clear_data_and_optionally_control() {
+        let config = Ad5328Config {
+            gain: (GAIN::Gain02Vref, GAIN::Gain0Vref),
+            vdd: (VDD::ExternalRef, VDD::VddAsRef),
+            ldac: LDAC::LdacLow,
+            ..Default::default()
+        };
+        let mut dac = dac(config);
+        dac.set_channel(Channel::D, 1234).unwrap();
+        dac.power_down([false, true, false, false, false, false, false, true])
+            .unwrap();
+        dac.reset(false).unwrap();
+        let (mut model, _) = dac.release();
+        assert_eq!(model.channel_code(Channel::D), 0);
+        assert_eq!(model.dac_code(Channel::D), 0);
+        assert!(!model.is_powered(Channel::B));
+        assert!(model.is_powered(Channel::C));
+        let config = model.config();
+        assert!(matches!(config.gain, (GAIN::Gain02Vref, GAIN::Gain0Vref)));
+        assert!(matches!(config.buf, (BUF::Buffered, BUF::Buffered)));
+        assert!(matches!(config.vdd, (VDD::ExternalRef, VDD::VddAsRef)));
+
+        model.write(&[0xf0, 0x00]).unwrap();
+        assert!(model.is_powered(Channel::B));
+        let config = model.config();
+        assert!(matches!(config.gain, (GAIN::Gain0Vref, GAIN::Gain0Vref)));
+        assert!(matches!(config.buf, (BUF::Unbuffered, BUF::Unbuffered)));
+        assert!(matches!(config.vdd, (VDD::ExternalRef, VDD::ExternalRef)));
+        assert!(matches!(config.ldac, LDAC::LdacLow));
+    }
+
+    #[test]
+    fn frames_may_be_split_across_writes() {
+        let mut model = Ad5328Model::new();
+        model.write(&[0x21]).unwrap();
+        assert_eq!(model.channel_code(Channel::C), 0);
+        model.write(&[0x23, 0x3f]).unwrap();
+        model.write(&[0xff]).unwrap();
+        assert_eq!(model.channel_code(Channel::C), 0x123);
+        assert_eq!(model.channel_code(Channel::D), 0xfff);
+    }
+
+    #[test]
+    fn output_follows_reference_gain_and_vdd_priority() {
+        let supplies = Supplies {
+            vref_ad_mv: 2048,
+            vref_eh_mv: 1024,
+            vdd_mv: 5000,
+        };
+        for gain in [GAIN::Gain0Vref, GAIN::Gain02Vref] {
+            for buf in [BUF::Unbuffered, BUF::Buffered] {
+                for vdd in [VDD::ExternalRef, VDD::VddAsRef] {
+                    let config = Ad5328Config {
+                        gain: (gain, gain),
+                        buf: (buf, buf),
+                        vdd: (vdd, VDD::ExternalRef),
+                        ldac: LDAC::LdacLow,
+                    };
+                    let mut dac = dac(config);
+                    dac.set_channel(Channel::B, 2048).unwrap();
+                    dac.set_channel(Channel::F, 2048).unwrap();
+                    let (model, _) = dac.release();
+                    let factor = match gain {
+                        GAIN::Gain0Vref => 1,
+                        GAIN::Gain02Vref => 2,
+                    };
+                    let expected_ad = match vdd {
+                        VDD::VddAsRef => 2500,
+                        VDD::ExternalRef => 1024 * factor,
+                    };
+                    assert_eq!(model.output_millivolts(Channel::B, &supplies), expected_ad);
+                    assert_eq!(model.output_millivolts(Channel::F, &supplies), 512 * factor);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn output_clips_at_vdd_and_drops_when_powered_down() {
+        let supplies = Supplies {
+            vref_ad_mv: 3000,
+            vref_eh_mv: 3000,
+            vdd_mv: 3300,
+        };
+        let mut dac = dac(Ad5328Config {
+            gain: (GAIN::Gain02Vref, GAIN::Gain0Vref),
+            ldac: LDAC::LdacLow,
+            ..Default::default()
+        });
+        dac.set_channel(Channel::A, 4095).unwrap();
+        dac.set_channel(Channel::E, 4095).unwrap();
+        dac.power_down([false, false, false, false, true, false, false, false])
+            .unwrap();
+        let (model, _) = dac.release();
+        assert_eq!(model.output_millivolts(Channel::A, &supplies), 3300);
+        assert_eq!(model.output_millivolts(Channel::E, &supplies), 0);
+    }
+
+    #[test]
+    fn retry_after_spi_fault_resends_the_failed_frame() {
+        let mut model = Ad5328Model::new();
+        // the two configuration frames are writes 0 and 1
+        model.inject(Fault::SpiErrorAt(2));
+        let mut dac = Ad5328::init_without_enable(model, Ad5328Config::default()).unwrap();
+        assert!(matches!(
+            dac.set_channel(Channel::A, 100),
+            Err(Error::Spi(InjectedError))
+        ));
+        dac.set_channel(Channel::A, 100).unwrap();
+        dac.set_channel(Channel::B, 200).unwrap();
+        let (model, _) = dac.release();
+        assert_eq!(model.channel_code(Channel::A), 100);
+        assert_eq!(model.channel_code(Channel::B), 200);
+        assert!(model.fired().eq([Fault::SpiErrorAt(2)]));
+    }
+
+    #[test]
+    fn dropped_frames_leave_the_registers_untouched() {
+        let mut model = Ad5328Model::new();
+        model.inject(Fault::DropFrame(3));
+        model.inject(Fault::SpiErrorAt(10));
+        let mut dac = Ad5328::init_without_enable(model, Ad5328Config::default()).unwrap();
+        dac.set_channel(Channel::A, 100).unwrap();
+        dac.set_channel(Channel::B, 200).unwrap();
+        dac.reset(true).unwrap();
+        dac.set_channel(Channel::C, 300).unwrap();
+        let (model, _) = dac.release();
+        assert_eq!(model.channel_code(Channel::B), 0);
+        assert_eq!(model.channel_code(Channel::C), 300);
+        assert!(model.fired().eq([Fault::DropFrame(3)]));
+    }
+
+    #[test]
+    fn pin_fault_surfaces_as_pin_error() {
+        let mut dac = Ad5328::init(
+            Ad5328Model::new(),
+            SimPin::failing_at(4),
+            Ad5328Config::default(),
+        )
+        .unwrap();
+        assert!(matches!(
+            dac.set_channel(Channel::A, 1),
+            Err(Error::Pin(InjectedError))
+        ));
+        let (model, pin) = dac.release();
+        assert!(pin.fired());
+        assert_eq!(model.channel_code(Channel::A), 0);
+        assert_eq!(model.fired().count(), 0);
+    }
+
+    #[test]
+    fn commit_updates_staged_outputs_together() {
+        let mut dac = dac(Ad5328Config::default());
+        dac.stage_channel(Channel::A, 100).unwrap();
+        dac.stage_channel(Channel::H, 200).unwrap();
+        let (model, _) = dac.release();
+        assert_eq!(model.dac_code(Channel::A), 0);
+        assert_eq!(model.dac_code(Channel::H), 0);
+
+        let mut dac = Ad5328::init_without_enable(model, Ad5328Config::default()).unwrap();
+        dac.stage_channel(Channel::A, 100).unwrap();
+        dac.stage_channel(Channel::H, 200).unwrap();
+        dac.commit().unwrap();
+        dac.stage_channel(Channel::A, 300).unwrap();
+        let (model, _) = dac.release();
+        assert_eq!(model.dac_code(Channel::A), 100);
+        assert_eq!(model.dac_code(Channel::H), 200);
+        assert_eq!(model.channel_code(Channel::A), 300);
+    }
+}
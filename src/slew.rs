@@ -0,0 +1,143 @@
+//! Slew-rate limiting of channel values, advanced by [`Ad53xx::tick`].
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, Transport};
+
+/// Slew state of a channel
+#[derive(Clone, Copy)]
+pub(crate) struct Slew {
+    max_delta: u16,
+    /// Value last written through [`Ad53xx::set_target`] or a tick, `None` before the first
+    current: Option<u16>,
+    target: u16,
+}
+
+impl Slew {
+    /// Next value, at most `max_delta` closer to the target
+    fn step(&self, current: u16) -> u16 {
+        if self.target > current {
+            current + (self.target - current).min(self.max_delta)
+        } else {
+            current - (current - self.target).min(self.max_delta)
+        }
+    }
+}
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Move `channel` towards the values given to [`set_target`](Self::set_target) by at most `max_delta` per
+    /// [`tick`](Self::tick), keeping a target already set. A `max_delta` of 0 is as [`clear_slew`](Self::clear_slew)
+    pub fn set_slew(&mut self, channel: Channel, max_delta: u16) {
+        if max_delta == 0 {
+            return self.clear_slew(channel);
+        }
+        self.pipeline.slew[channel] = Some(match self.pipeline.slew[channel] {
+            Some(slew) => Slew { max_delta, ..slew },
+            None => Slew {
+                max_delta,
+                current: None,
+                target: 0,
+            },
+        });
+    }
+
+    /// Write the values of `channel` right away again, it stays where the last tick left it
+    pub fn clear_slew(&mut self, channel: Channel) {
+        self.pipeline.slew[channel] = None;
+    }
+
+    /// Set the value `channel` slews to, or without a slew set through [`set_slew`](Self::set_slew) write it
+    /// right away as [`set_channel`](Self::set_channel) does. The first target of a slewing channel is written
+    /// right away as well, as the driver doesn't know the value to start from. Out of bounds values are checked
+    /// here, by the [`ValuePolicy`](crate::ValuePolicy)
+    pub fn set_target(&mut self, channel: Channel, value: u16) -> Result<(), Error<S, P>> {
+        let value = self.in_bounds(value)?;
+        match self.pipeline.slew[channel] {
+            Some(slew) if slew.current.is_some() => {
+                self.pipeline.slew[channel] = Some(Slew {
+                    target: value,
+                    ..slew
+                });
+                Ok(())
+            }
+            Some(slew) => {
+                self.set_channel(channel, value)?;
+                self.pipeline.slew[channel] = Some(Slew {
+                    current: Some(value),
+                    target: value,
+                    ..slew
+                });
+                Ok(())
+            }
+            None => self.set_channel(channel, value),
+        }
+    }
+
+    /// Move every slewing channel, A first, towards its target by at most its `max_delta`, writing only the
+    /// channels that move. `true` while any channel is still short of its target after this tick.
+    /// On an error the failed channel keeps its previous value and the next tick tries it again
+    pub fn tick(&mut self) -> Result<bool, Error<S, P>> {
+        let mut moving = false;
+        for n in 0..8 {
+            let channel = Channel::from(n);
+            let Some(slew) = self.pipeline.slew[channel] else {
+                continue;
+            };
+            let Some(current) = slew.current.filter(|&current| current != slew.target) else {
+                continue;
+            };
+            let next = slew.step(current);
+            self.set_channel(channel, next)?;
+            self.pipeline.slew[channel] = Some(Slew {
+                current: Some(next),
+                ..slew
+            });
+            moving |= next != slew.target;
+        }
+        Ok(moving)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Ad5328, Ad5328Config, Channel};
+    use embedded_hal_mock::eh0::spi;
+
+    #[test]
+    fn tick_moves_by_at_most_the_delta_and_reports_motion() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            // first target and the channel without slew are immediate
+            spi::Transaction::write(vec![0x00, 0x64]),
+            spi::Transaction::write(vec![0x1f, 0xff]),
+            spi::Transaction::write(vec![0x01, 0x2c]),
+            spi::Transaction::write(vec![0x01, 0xf4]),
+            spi::Transaction::write(vec![0x02, 0x58]),
+            // descending
+            spi::Transaction::write(vec![0x01, 0x90]),
+            spi::Transaction::write(vec![0x00, 0xc8]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_slew(Channel::A, 200);
+        dac.set_target(Channel::A, 100).unwrap();
+        dac.set_target(Channel::B, 4095).unwrap();
+        assert!(!dac.tick().unwrap());
+
+        dac.set_target(Channel::A, 600).unwrap();
+        assert!(dac.tick().unwrap());
+        assert!(dac.tick().unwrap());
+        assert!(!dac.tick().unwrap());
+        assert!(!dac.tick().unwrap());
+
+        dac.set_target(Channel::A, 0).unwrap();
+        assert!(dac.set_target(Channel::A, 4096).is_err());
+        while dac.tick().unwrap() {}
+        dac.clear_slew(Channel::A);
+        assert!(!dac.tick().unwrap());
+        spi.done();
+    }
+}
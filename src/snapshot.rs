@@ -0,0 +1,370 @@
+//! Snapshots of the codes of all eight channels, e.g. for presets recalled as a whole.
+
+use embedded_hal::blocking::delay::DelayUs;
+
+use crate::{
+    command, ramp::ramp_value, resolution::Resolution, Ad53xx, Channel, Error, LdacPulse,
+    Transport, Written, LDAC,
+};
+
+/// Codes of channels A to H, as written after the value pipeline, see [`Ad53xx::snapshot`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot(pub [u16; 8]);
+
+impl Snapshot {
+    /// The codes of `other` that differ from this snapshot, e.g. to show what recalling it would change.
+    /// [`Ad53xx::apply`] then writes only those
+    pub fn diff(&self, other: &Snapshot) -> ChannelPatch {
+        ChannelPatch(core::array::from_fn(|n| {
+            Some(other.0[n]).filter(|&code| code != self.0[n])
+        }))
+    }
+}
+
+/// Codes of some of the channels, `patch.0[0]` for channel A to `patch.0[7]` for channel H, see
+/// [`Snapshot::diff`]. Iterates over the `(Channel, code)` pairs of the channels it has a code for, A first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelPatch(pub [Option<u16>; 8]);
+
+/// Iterator over the pairs of a [`ChannelPatch`]
+pub type PatchIter = core::iter::FilterMap<
+    core::iter::Enumerate<core::array::IntoIter<Option<u16>, 8>>,
+    fn((usize, Option<u16>)) -> Option<(Channel, u16)>,
+>;
+
+impl ChannelPatch {
+    /// Number of channels with a code
+    pub fn len(&self) -> usize {
+        self.0.iter().flatten().count()
+    }
+
+    /// Whether the patch has no code at all, so applying it writes nothing
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `(Channel, code)` pairs, A first
+    pub fn iter(&self) -> PatchIter {
+        self.into_iter()
+    }
+}
+
+impl IntoIterator for ChannelPatch {
+    type Item = (Channel, u16);
+    type IntoIter = PatchIter;
+
+    fn into_iter(self) -> PatchIter {
+        self.0
+            .into_iter()
+            .enumerate()
+            .filter_map(|(n, code)| Some((Channel::from(n as u8), code?)))
+    }
+}
+
+impl IntoIterator for &ChannelPatch {
+    type Item = (Channel, u16);
+    type IntoIter = PatchIter;
+
+    fn into_iter(self) -> PatchIter {
+        (*self).into_iter()
+    }
+}
+
+impl<SPI, EN, R, L> Ad53xx<SPI, EN, R, L>
+where
+    R: Resolution,
+{
+    /// The code last written to every channel, see [`last_code`](Self::last_code), with 0, the power-on code,
+    /// for channels not written yet
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(core::array::from_fn(|n| {
+            self.last_code(Channel::from(n as u8)).unwrap_or(0)
+        }))
+    }
+}
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Write the codes of `snapshot` to channels A to H, A first. The codes are taken as they are, they were
+    /// written after the value pipeline, so lookup tables, calibration, limits and inversion don't apply again;
+    /// out of bounds codes are handled by the [`ValuePolicy`](crate::ValuePolicy), all checked before the first
+    /// frame. Powered down channels are written as well, their code taking effect when they power up
+    pub fn recall(&mut self, snapshot: &Snapshot) -> Result<(), Error<S, P>> {
+        let mut codes = snapshot.0;
+        for code in &mut codes {
+            *code = self.in_bounds(*code)?;
+        }
+        for (n, code) in codes.into_iter().enumerate() {
+            self.write_code(Channel::from(n as u8), code)?;
+        }
+        Ok(())
+    }
+
+    /// As [`recall`](Self::recall), with all outputs changing on the same LDAC update as for
+    /// [`set_channels_synced`](Self::set_channels_synced), so the outputs switch from one snapshot to the next
+    /// without passing through a mix of the two
+    pub fn recall_synced(&mut self, snapshot: &Snapshot) -> Result<(), Error<S, P>>
+    where
+        L: LdacPulse<P>,
+    {
+        for &code in &snapshot.0 {
+            self.in_bounds::<S, P>(code)?;
+        }
+        let restore = matches!(self.config.ldac, LDAC::LdacLow);
+        if restore {
+            self.set_ldac_mode(LDAC::LdacHigh)?;
+        }
+        self.recall(snapshot)?;
+        self.latch()?;
+        if restore {
+            self.set_ldac_mode(LDAC::LdacLow)?;
+        }
+        Ok(())
+    }
+
+    /// Write the codes of `patch`, A first, taking them as they are as [`recall`](Self::recall) does, all checked
+    /// before the first frame. An empty patch writes nothing
+    pub fn apply(&mut self, patch: &ChannelPatch) -> Result<(), Error<S, P>> {
+        let mut codes = patch.0;
+        for code in codes.iter_mut().flatten() {
+            *code = self.in_bounds(*code)?;
+        }
+        for (channel, code) in ChannelPatch(codes) {
+            self.write_code(channel, code)?;
+        }
+        Ok(())
+    }
+
+    /// Fade all channels linearly from the codes of `from` to those of `to` in `steps` steps, waiting
+    /// `step_delay_us` between two steps. Step `k` is at `from + (to - from) * k / steps` rounded to nearest, as
+    /// for [`ramp_channel`](Self::ramp_channel), so the last step lands exactly on `to`, and a `steps` of 0 or 1
+    /// is a single jump. Each step writes only the channels whose code differs from their
+    /// [`last_code`](Self::last_code), A first, taking the codes as they are as [`recall`](Self::recall) does.
+    ///
+    /// Both snapshots are checked before the first frame. Stopping at an error leaves every channel at a code
+    /// of the fade, which [`snapshot`](Self::snapshot) captures
+    pub fn crossfade<D: DelayUs<u32>>(
+        &mut self,
+        from: &Snapshot,
+        to: &Snapshot,
+        steps: u16,
+        delay: &mut D,
+        step_delay_us: u32,
+    ) -> Result<(), Error<S, P>> {
+        let (mut start, mut end) = (from.0, to.0);
+        for code in start.iter_mut().chain(end.iter_mut()) {
+            *code = self.in_bounds(*code)?;
+        }
+        let steps = steps.max(1);
+        for step in 1..=steps {
+            if step > 1 {
+                delay.delay_us(step_delay_us);
+            }
+            for n in 0..8 {
+                let channel = Channel::from(n as u8);
+                let code = ramp_value(start[n], end[n], step, steps);
+                if self.last_code(channel) != Some(code) {
+                    self.write_code(channel, code)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write `code` to the physical channel of `channel` as it is, recording it as its last code
+    fn write_code(&mut self, channel: Channel, code: u16) -> Result<Written, Error<S, P>> {
+        let cmd = command::set_code(self.physical_channel(channel), code, self.resolution.bits())
+            .ok_or(self.resolution.out_of_bounds())?;
+        self.pipeline.setpoint[channel] = None;
+        self.pipeline.millivolts[channel] = None;
+        self.put(channel, cmd & command::MAX_VALUE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{recorded, Delays};
+    use crate::{Ad5328, Ad5328Config, ValuePolicy};
+    use embedded_hal_mock::eh0::spi;
+
+    #[test]
+    fn snapshots_hold_the_codes_after_the_pipeline() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x1f, 0x9b]),
+            spi::Transaction::write(vec![0x40, 0x10]),
+            spi::Transaction::write(vec![0xc0, 0x10]),
+            // recalled, the powered down channel included
+            spi::Transaction::write(vec![0x00, 0x00]),
+            spi::Transaction::write(vec![0x1f, 0x9b]),
+            spi::Transaction::write(vec![0x20, 0x00]),
+            spi::Transaction::write(vec![0x30, 0x00]),
+            spi::Transaction::write(vec![0x40, 0x10]),
+            spi::Transaction::write(vec![0x50, 0x00]),
+            spi::Transaction::write(vec![0x60, 0x00]),
+            spi::Transaction::write(vec![0x70, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        assert_eq!(dac.snapshot(), Snapshot::default());
+        dac.set_inverted(Channel::B, true);
+        dac.set_channel(Channel::B, 100).unwrap();
+        dac.set_channel(Channel::E, 16).unwrap();
+        dac.power_down([false, false, false, false, true, false, false, false])
+            .unwrap();
+        let snapshot = dac.snapshot();
+        assert_eq!(snapshot, Snapshot([0, 3995, 0, 0, 16, 0, 0, 0]));
+        dac.recall(&snapshot).unwrap();
+        assert_eq!(dac.snapshot(), snapshot);
+        spi.done();
+    }
+
+    #[test]
+    fn recall_checks_all_codes_first() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        let snapshot = Snapshot([1, 2, 3, 4, 5, 6, 7, 4096]);
+        assert!(matches!(dac.recall(&snapshot), Err(Error::OobMax(4095))));
+        assert!(matches!(
+            dac.recall_synced(&snapshot),
+            Err(Error::OobMax(4095))
+        ));
+        spi.done();
+
+        let mut expected = vec![
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+        ];
+        expected.extend((0..7).map(|n| spi::Transaction::write(vec![n << 4, n + 1])));
+        expected.push(spi::Transaction::write(vec![0x7f, 0xff]));
+        let mut spi = spi::Mock::new(&expected);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default())
+            .unwrap()
+            .with_value_policy(ValuePolicy::Saturate);
+        dac.recall(&snapshot).unwrap();
+        assert_eq!(dac.last_code(Channel::H), Some(4095));
+        spi.done();
+    }
+
+    #[test]
+    fn recall_synced_updates_the_outputs_at_once() {
+        let mut expected = vec![
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x00]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+        ];
+        expected.extend((0..8).map(|n| spi::Transaction::write(vec![n << 4, 0x40])));
+        expected.extend([
+            spi::Transaction::write(vec![0xa0, 0x02]),
+            spi::Transaction::write(vec![0xa0, 0x00]),
+        ]);
+        let mut spi = spi::Mock::new(&expected);
+        let config = Ad5328Config {
+            ldac: LDAC::LdacLow,
+            ..Ad5328Config::default()
+        };
+        let mut dac = Ad5328::init_without_enable(spi.clone(), config).unwrap();
+        dac.recall_synced(&Snapshot([0x40; 8])).unwrap();
+        spi.done();
+    }
+
+    const FROM: Snapshot = Snapshot([0, 100, 4095, 7, 7, 7, 7, 7]);
+    const TO: Snapshot = Snapshot([4, 100, 4091, 7, 7, 7, 7, 3000]);
+
+    #[test]
+    fn crossfade_writes_only_changed_codes_and_lands_on_the_target() {
+        let mut dac = recorded(usize::MAX);
+        dac.recall(&FROM).unwrap();
+        dac.spi.frames.clear();
+        let mut delay = Delays::default();
+        dac.crossfade(&FROM, &TO, 4, &mut delay, 1000).unwrap();
+        assert_eq!(dac.snapshot(), TO);
+        assert_eq!(delay.0, [1000, 1000, 1000]);
+        assert_eq!(
+            dac.spi.frames,
+            [
+                0x0001, 0x2ffe, 0x72f3, // step 1
+                0x0002, 0x2ffd, 0x75e0, // step 2
+                0x0003, 0x2ffc, 0x78cc, // step 3
+                0x0004, 0x2ffb, 0x7bb8, // step 4
+            ]
+        );
+
+        // an unknown start writes every channel in the first step
+        let mut dac = recorded(usize::MAX);
+        dac.crossfade(&FROM, &TO, 1, &mut delay, 1000).unwrap();
+        assert_eq!(dac.spi.frames.len(), 8);
+        assert_eq!(dac.snapshot(), TO);
+    }
+
+    #[test]
+    fn interrupted_crossfades_leave_a_snapshot_of_the_fade() {
+        let mut dac = recorded(usize::MAX);
+        dac.recall(&FROM).unwrap();
+        dac.spi.budget = 4;
+        let err = dac.crossfade(&FROM, &TO, 4, &mut Delays::default(), 1);
+        assert!(matches!(err, Err(Error::Spi(()))));
+        assert_eq!(dac.snapshot(), Snapshot([2, 100, 4094, 7, 7, 7, 7, 755]));
+
+        let bad = Snapshot([4096; 8]);
+        dac.spi.budget = usize::MAX;
+        dac.spi.frames.clear();
+        let err = dac.crossfade(&FROM, &bad, 4, &mut Delays::default(), 1);
+        assert!(matches!(err, Err(Error::OobMax(4095))));
+        assert!(dac.spi.frames.is_empty());
+    }
+
+    #[test]
+    fn diffs_hold_only_the_changed_codes() {
+        let patch = FROM.diff(&TO);
+        assert_eq!(
+            patch,
+            ChannelPatch([
+                Some(4),
+                None,
+                Some(4091),
+                None,
+                None,
+                None,
+                None,
+                Some(3000)
+            ])
+        );
+        assert_eq!(patch.len(), 3);
+        let pairs: Vec<(u8, u16)> = patch
+            .iter()
+            .map(|(ch, code)| (u8::from(ch), code))
+            .collect();
+        assert_eq!(pairs, [(0, 4), (2, 4091), (7, 3000)]);
+        assert!(FROM.diff(&FROM).is_empty());
+        assert_eq!((&TO.diff(&FROM)).into_iter().count(), 3);
+    }
+
+    #[test]
+    fn apply_writes_just_the_patch() {
+        let mut dac = recorded(usize::MAX);
+        dac.recall(&FROM).unwrap();
+        dac.spi.frames.clear();
+        dac.apply(&FROM.diff(&TO)).unwrap();
+        assert_eq!(dac.spi.frames, [0x0004, 0x2ffb, 0x7bb8]);
+        assert_eq!(dac.snapshot(), TO);
+
+        dac.spi.frames.clear();
+        dac.spi.budget = 0;
+        dac.apply(&TO.diff(&TO)).unwrap();
+        let bad = ChannelPatch([Some(1), None, None, None, None, None, None, Some(4096)]);
+        assert!(matches!(dac.apply(&bad), Err(Error::OobMax(4095))));
+        assert!(dac.spi.frames.is_empty());
+    }
+}
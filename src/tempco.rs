@@ -0,0 +1,125 @@
+//! Temperature compensation of the gain of a channel's output stage, re-applied by [`Ad53xx::update_temperature`].
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, Transport};
+
+/// Gain drift of a channel
+#[derive(Clone, Copy)]
+pub(crate) struct Tempco {
+    ppm_per_degc: i32,
+    /// Temperature without drift, in tenths of a degree Celsius
+    reference: i16,
+}
+
+impl Tempco {
+    /// `code` divided by the gain at `temperature`, `1 + ppm_per_degc * (temperature - reference) / 10^6`,
+    /// rounded to nearest and saturated at the ends of `0..=max`
+    pub(crate) fn apply(&self, code: u16, max: u16, temperature: i16) -> u16 {
+        let drift =
+            i64::from(self.ppm_per_degc) * (i64::from(temperature) - i64::from(self.reference));
+        // in parts per ten million, as the temperatures are in tenths of a degree
+        let gain = 10_000_000 + drift;
+        if gain <= 0 {
+            return max;
+        }
+        let code = (i64::from(code) * 10_000_000 + gain / 2) / gain;
+        code.min(i64::from(max)) as u16
+    }
+}
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Compensate a gain drift of `ppm_per_degc` of the output stage of `channel`, e.g. -120 for a gain that
+    /// falls by 120 ppm per degree Celsius, relative to `reference_decidegc`, in tenths of a degree Celsius.
+    /// Once a temperature is given to [`update_temperature`](Self::update_temperature), the codes of the channel
+    /// are divided by the gain at that temperature after the [calibration](Self::set_calibration). The channel
+    /// isn't written here, the next write or temperature update applies the compensation
+    pub fn set_tempco(&mut self, channel: Channel, ppm_per_degc: i32, reference_decidegc: i16) {
+        self.pipeline.tempco[channel] = Some(Tempco {
+            ppm_per_degc,
+            reference: reference_decidegc,
+        });
+    }
+
+    /// Stop compensating `channel`, it keeps its code until the next write
+    pub fn clear_tempco(&mut self, channel: Channel) {
+        self.pipeline.tempco[channel] = None;
+    }
+
+    /// Temperature last given to [`update_temperature`](Self::update_temperature), in tenths of a degree Celsius
+    pub fn temperature(&self) -> Option<i16> {
+        self.pipeline.temperature
+    }
+
+    /// Compensate the channels with a [tempco](Self::set_tempco) for `decidegc`, in tenths of a degree Celsius,
+    /// and write them again, A first. Each is recomputed from the value last written to it through the value
+    /// pipeline, not from its compensated code, so repeated updates don't accumulate rounding errors. Channels
+    /// without a tempco, channels not written yet and channels last written a raw code, such as through
+    /// [`set_channel_raw`](Self::set_channel_raw) or a [snapshot](Self::recall), are left alone
+    pub fn update_temperature(&mut self, decidegc: i16) -> Result<(), Error<S, P>> {
+        self.pipeline.temperature = Some(decidegc);
+        for n in 0..8 {
+            let channel = Channel::from(n);
+            if self.pipeline.tempco[channel].is_none() {
+                continue;
+            }
+            let Some(value) = self.pipeline.setpoint[channel] else {
+                continue;
+            };
+            let mv = self.pipeline.millivolts[channel];
+            let cmd = self.pipeline_command(channel, value)?;
+            self.write_data(channel, Some(value), cmd)?;
+            self.pipeline.millivolts[channel] = mv;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Ad5328, Ad5328Config, Channel};
+    use embedded_hal_mock::eh0::spi;
+
+    #[test]
+    fn updates_recompute_from_the_uncompensated_value() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x08, 0x00]),
+            spi::Transaction::write(vec![0x18, 0x00]),
+            spi::Transaction::write(vec![0x2f, 0xff]),
+            // 25 °C, the reference
+            spi::Transaction::write(vec![0x08, 0x00]),
+            // 65 °C: 2048 / 0.9952 = 2057.88
+            spi::Transaction::write(vec![0x08, 0x0a]),
+            // -15 °C: 2048 / 1.0048 = 2038.22
+            spi::Transaction::write(vec![0x07, 0xf6]),
+            spi::Transaction::write(vec![0x08, 0x00]),
+            spi::Transaction::write(vec![0x07, 0xf6]),
+            // new values are compensated right away, 1000 / 0.9952 = 1004.82
+            spi::Transaction::write(vec![0x03, 0xed]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_tempco(Channel::A, -120, 250);
+        dac.set_tempco(Channel::C, -120, 250);
+        dac.set_channel(Channel::A, 2048).unwrap();
+        dac.set_channel(Channel::B, 2048).unwrap();
+        dac.set_channel_raw(Channel::C, 4095).unwrap();
+        assert_eq!(dac.temperature(), None);
+
+        dac.update_temperature(250).unwrap();
+        dac.update_temperature(650).unwrap();
+        dac.update_temperature(-150).unwrap();
+        dac.update_temperature(250).unwrap();
+        assert_eq!(dac.temperature(), Some(250));
+
+        dac.update_temperature(-150).unwrap();
+        dac.clear_tempco(Channel::A);
+        dac.update_temperature(650).unwrap();
+        dac.set_tempco(Channel::A, -120, 250);
+        dac.set_channel(Channel::A, 1000).unwrap();
+        spi.done();
+    }
+}
@@ -0,0 +1,78 @@
+//! `embedded-hal-mock` expectations for code driving an [`Ad5328`](crate::Ad5328), enabled with the `test-support` feature.
+//!
+//! The frames come from the encoder the driver itself uses, so the expectations follow any change to the encoding.
+//!
+//! ```
+//! use ad5328::{test_support, Ad5328, Ad5328Config, Channel};
+//! use embedded_hal_mock::eh0::{pin, spi};
+//!
+//! let config = Ad5328Config::default();
+//! let expected = test_support::expect_configure(&config)
+//!     .then(test_support::expect_set_channel(Channel::B, 2048));
+//! let mut spi = spi::Mock::new(&expected.spi);
+//! let mut enable = pin::Mock::new(&expected.enable);
+//!
+//! let mut dac = Ad5328::init(spi.clone(), enable.clone(), config).unwrap();
+//! dac.set_channel(Channel::B, 2048).unwrap();
+//!
+//! spi.done();
+//! enable.done();
+//! ```
+
+use embedded_hal_mock::eh0::{pin, spi};
+
+use crate::{command, Ad5328Config, Channel};
+
+/// SPI and enable pin transactions of one or more operations, in the order the driver issues them
+#[derive(Default)]
+pub struct Expectations {
+    /// One write per frame
+    pub spi: Vec<spi::Transaction>,
+    /// SYNC pulled low and released around every frame
+    pub enable: Vec<pin::Transaction>,
+}
+
+impl Expectations {
+    fn frames(cmds: &[u16]) -> Self {
+        let mut expected = Self::default();
+        for &cmd in cmds {
+            expected
+                .spi
+                .push(spi::Transaction::write(command::to_bytes(cmd).to_vec()));
+            expected.enable.extend([
+                pin::Transaction::set(pin::State::Low),
+                pin::Transaction::set(pin::State::High),
+            ]);
+        }
+        expected
+    }
+
+    /// Append the transactions of the operation issued after these
+    pub fn then(mut self, next: Expectations) -> Self {
+        self.spi.extend(next.spi);
+        self.enable.extend(next.enable);
+        self
+    }
+}
+
+/// Expect [`Ad5328::set_channel`](crate::Ad5328::set_channel). Panics for a value over 4095,
+/// which the driver rejects without touching the bus
+pub fn expect_set_channel(channel: Channel, value: u16) -> Expectations {
+    let cmd = command::set_channel(channel, value).expect("channel value over 4095");
+    Expectations::frames(&[cmd])
+}
+
+/// Expect [`Ad5328::configure`](crate::Ad5328::configure), which is also all [`Ad5328::init`](crate::Ad5328::init) sends
+pub fn expect_configure(config: &Ad5328Config) -> Expectations {
+    Expectations::frames(&config.as_commands())
+}
+
+/// Expect [`Ad5328::power_down`](crate::Ad5328::power_down) of the channels set to true, Channel A -> 0, ..., Channel H -> 7
+pub fn expect_power_down(channels: [bool; 8]) -> Expectations {
+    Expectations::frames(&[command::power_down(channels)])
+}
+
+/// Expect [`Ad5328::reset`](crate::Ad5328::reset)
+pub fn expect_reset(full_reset: bool) -> Expectations {
+    Expectations::frames(&[command::reset(full_reset)])
+}
@@ -0,0 +1,138 @@
+//! Test doubles shared by the unit tests.
+
+use core::convert::Infallible;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use embedded_hal::{
+    blocking::{delay::DelayUs, spi::Write},
+    digital::v2::OutputPin,
+};
+
+use crate::{Ad5328, Ad5328Config, NoEnable};
+
+/// Records the data frames, skipping the configuration and power-down frames, and fails once `budget` writes
+/// are used up
+pub(crate) struct Frames {
+    pub(crate) budget: usize,
+    pub(crate) frames: Vec<u16>,
+}
+
+impl Default for Frames {
+    fn default() -> Self {
+        Self {
+            budget: usize::MAX,
+            frames: Vec::new(),
+        }
+    }
+}
+
+impl Frames {
+    /// The codes of the recorded frames, without their channel
+    pub(crate) fn values(&self) -> Vec<u16> {
+        self.frames.iter().map(|frame| frame & 0x0fff).collect()
+    }
+}
+
+impl Write<u8> for Frames {
+    type Error = ();
+
+    fn write(&mut self, words: &[u8]) -> Result<(), ()> {
+        self.budget = self.budget.checked_sub(1).ok_or(())?;
+        let word = u16::from_be_bytes([words[0], words[1]]);
+        if word < 0x8000 {
+            self.frames.push(word);
+        }
+        Ok(())
+    }
+}
+
+/// Driver over [`Frames`], failing once `budget` frames follow the configuration
+pub(crate) fn recorded(budget: usize) -> Ad5328<Frames, NoEnable> {
+    let spi = Frames {
+        budget: budget.saturating_add(2),
+        frames: Vec::new(),
+    };
+    Ad5328::init_without_enable(spi, Ad5328Config::default()).unwrap()
+}
+
+/// Records the requested delays
+#[derive(Default)]
+pub(crate) struct Delays(pub(crate) Vec<u32>);
+
+impl DelayUs<u32> for Delays {
+    fn delay_us(&mut self, us: u32) {
+        self.0.push(us);
+    }
+}
+
+/// What happened on the bus and the pins around it, in order
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Event {
+    Sync(bool),
+    Bytes(Vec<u8>),
+    Read,
+    Assert(usize),
+    Deassert,
+    #[cfg(feature = "eh1")]
+    Ldac(bool),
+    #[cfg(feature = "eh1")]
+    Delay(u32),
+}
+
+/// Event log shared by the doubles of one test, also across threads
+#[derive(Clone, Default)]
+pub(crate) struct Log(Arc<Mutex<Vec<Event>>>);
+
+impl Log {
+    pub(crate) fn push(&self, event: Event) {
+        self.events().push(event);
+    }
+
+    pub(crate) fn events(&self) -> MutexGuard<'_, Vec<Event>> {
+        self.0.lock().unwrap()
+    }
+}
+
+/// SPI bus logging every write, or failing them all once `failing` is set
+pub(crate) struct LogSpi {
+    pub(crate) log: Log,
+    pub(crate) failing: bool,
+}
+
+impl LogSpi {
+    pub(crate) fn new(log: &Log) -> Self {
+        Self {
+            log: log.clone(),
+            failing: false,
+        }
+    }
+}
+
+impl Write<u8> for LogSpi {
+    type Error = ();
+
+    fn write(&mut self, words: &[u8]) -> Result<(), ()> {
+        if self.failing {
+            return Err(());
+        }
+        self.log.push(Event::Bytes(words.to_vec()));
+        Ok(())
+    }
+}
+
+/// Enable pin logging its levels
+pub(crate) struct LogPin(pub(crate) Log);
+
+impl OutputPin for LogPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.push(Event::Sync(false));
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.push(Event::Sync(true));
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+//! Per-channel rate limits against a tick count given by the application, flushed by [`Ad53xx::poll`].
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, Transport, Written};
+
+/// Rate limit state of a channel
+#[derive(Clone, Copy)]
+pub(crate) struct Throttle {
+    min_interval: u32,
+    /// Tick of the last frame sent, `None` before the first
+    last: Option<u32>,
+    /// Data bits of the latest write held back
+    pending: Option<u16>,
+}
+
+impl Throttle {
+    /// Whether `last` is less than `min_interval` ticks before `now`, across a wrap of the tick count
+    fn waiting(&self, now: u32) -> bool {
+        self.last
+            .is_some_and(|last| now.wrapping_sub(last) < self.min_interval)
+    }
+
+    /// Hold `code` back as the pending write if the interval hasn't elapsed at `now`
+    pub(crate) fn hold(&mut self, now: u32, code: u16) -> bool {
+        if !self.waiting(now) {
+            return false;
+        }
+        self.pending = Some(code);
+        true
+    }
+
+    /// Record a write that went through at `now`, replacing any pending one
+    pub(crate) fn written(&mut self, now: u32, written: Written) {
+        self.pending = None;
+        if written == Written::Sent {
+            self.last = Some(now);
+        }
+    }
+}
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Send frames to `channel` at most once per `min_interval_ticks`, of the count given to
+    /// [`set_now`](Self::set_now), e.g. for an actuator with a capped command rate. A write within the interval
+    /// is left pending, replacing an earlier pending write, and [`poll`](Self::poll) sends the latest once the
+    /// interval has elapsed. This covers all writes of channel values, including
+    /// [`force_channel`](Self::force_channel), and the replicas of a [mirrored](Self::mirror) channel follow it.
+    /// Writes skipped or held don't start a new interval. A `min_interval_ticks` of 0 is as
+    /// [`clear_rate_limit`](Self::clear_rate_limit)
+    pub fn set_rate_limit(&mut self, channel: Channel, min_interval_ticks: u32) {
+        if min_interval_ticks == 0 {
+            return self.clear_rate_limit(channel);
+        }
+        self.pipeline.throttle[channel] = Some(match self.pipeline.throttle[channel] {
+            Some(throttle) => Throttle {
+                min_interval: min_interval_ticks,
+                ..throttle
+            },
+            None => Throttle {
+                min_interval: min_interval_ticks,
+                last: None,
+                pending: None,
+            },
+        });
+    }
+
+    /// Write `channel` right away again, dropping a pending write, see [`poll`](Self::poll) to send it first
+    pub fn clear_rate_limit(&mut self, channel: Channel) {
+        self.pipeline.throttle[channel] = None;
+    }
+
+    /// Set the current time for the [rate limits](Self::set_rate_limit), e.g. from a systick counter. The count
+    /// may wrap, intervals are measured with wrapping arithmetic, so they need to be shorter than `2^31` ticks
+    /// and a channel idle for a whole wrap may wait one more interval
+    pub fn set_now(&mut self, ticks: u32) {
+        self.pipeline.now = ticks;
+    }
+
+    /// Channels with a write pending behind their [rate limit](Self::set_rate_limit), Channel A -> 0, ...,
+    /// Channel H -> 7
+    pub fn pending(&self) -> [bool; 8] {
+        core::array::from_fn(|n| {
+            self.pipeline.throttle.0[n].is_some_and(|throttle| throttle.pending.is_some())
+        })
+    }
+
+    /// Send the pending writes, A first, of the channels whose interval has elapsed at the time last given to
+    /// [`set_now`](Self::set_now), returning how many were written. On an error the failed channel stays pending
+    /// and the next poll tries it again
+    pub fn poll(&mut self) -> Result<usize, Error<S, P>> {
+        let now = self.pipeline.now;
+        let mut flushed = 0;
+        for n in 0..8 {
+            let channel = Channel::from(n);
+            let Some(throttle) = self.pipeline.throttle[channel] else {
+                continue;
+            };
+            let Some(code) = throttle.pending.filter(|_| !throttle.waiting(now)) else {
+                continue;
+            };
+            let written = self.send_data(channel, code)?;
+            if let Some(throttle) = &mut self.pipeline.throttle[channel] {
+                throttle.written(now, written);
+            }
+            flushed += 1;
+        }
+        Ok(flushed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Ad5328, Ad5328Config, Channel, Written};
+    use embedded_hal_mock::eh0::spi;
+
+    #[test]
+    fn writes_within_the_interval_coalesce_until_polled() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x00, 0x64]),
+            // the channel without a limit is unaffected
+            spi::Transaction::write(vec![0x10, 0x01]),
+            spi::Transaction::write(vec![0x10, 0x02]),
+            // only the latest pending value, once the interval has elapsed
+            spi::Transaction::write(vec![0x00, 0x66]),
+            spi::Transaction::write(vec![0x00, 0x67]),
+            spi::Transaction::write(vec![0x00, 0x67]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_rate_limit(Channel::A, 10);
+        dac.set_now(1000);
+        assert_eq!(dac.update_channel(Channel::A, 100).unwrap(), Written::Sent);
+        dac.set_now(1005);
+        assert_eq!(
+            dac.update_channel(Channel::A, 101).unwrap(),
+            Written::Pending
+        );
+        assert_eq!(
+            dac.update_channel(Channel::A, 102).unwrap(),
+            Written::Pending
+        );
+        dac.set_channel(Channel::B, 1).unwrap();
+        dac.set_channel(Channel::B, 2).unwrap();
+        assert_eq!(
+            dac.pending(),
+            [true, false, false, false, false, false, false, false]
+        );
+        assert_eq!(dac.poll().unwrap(), 0);
+        dac.set_now(1010);
+        assert_eq!(dac.poll().unwrap(), 1);
+        assert_eq!(dac.pending(), [false; 8]);
+        assert_eq!(dac.poll().unwrap(), 0);
+        assert_eq!(dac.last_code(Channel::A), Some(102));
+
+        dac.set_now(1019);
+        assert_eq!(
+            dac.update_channel(Channel::A, 103).unwrap(),
+            Written::Pending
+        );
+        dac.set_now(1020);
+        assert_eq!(dac.update_channel(Channel::A, 103).unwrap(), Written::Sent);
+        assert_eq!(dac.poll().unwrap(), 0);
+
+        // a forced write is held back by the limit as well
+        dac.set_now(1025);
+        assert_eq!(
+            dac.force_channel(Channel::A, 103).unwrap(),
+            Written::Pending
+        );
+        dac.set_now(1030);
+        assert_eq!(dac.poll().unwrap(), 1);
+        spi.done();
+    }
+
+    #[test]
+    fn intervals_span_a_wrap_of_the_tick_count() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x20, 0x01]),
+            spi::Transaction::write(vec![0x20, 0x03]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_rate_limit(Channel::C, 10);
+        dac.set_now(u32::MAX - 4);
+        dac.set_channel(Channel::C, 1).unwrap();
+        dac.set_now(4);
+        assert_eq!(dac.update_channel(Channel::C, 2).unwrap(), Written::Pending);
+        assert_eq!(dac.update_channel(Channel::C, 3).unwrap(), Written::Pending);
+        dac.set_now(5);
+        assert_eq!(dac.poll().unwrap(), 1);
+
+        dac.set_now(6);
+        dac.set_channel(Channel::C, 4).unwrap();
+        dac.clear_rate_limit(Channel::C);
+        assert_eq!(dac.pending(), [false; 8]);
+        assert_eq!(dac.poll().unwrap(), 0);
+        spi.done();
+    }
+}
@@ -0,0 +1,112 @@
+//! Setpoints in engineering units, mapped to codes by a [`UnitScale`] per channel.
+
+use crate::{resolution::Resolution, scale::UnitScale, Ad53xx, Channel, Error, Transport};
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Map the values of [`set_channel_units`](Self::set_channel_units) for `channel` to codes as
+    /// `offset_code + milli * code_per_unit / (1000 * 2^16)`, see [`UnitScale`], replacing an earlier scale
+    pub fn set_unit_scale(&mut self, channel: Channel, offset_code: i32, code_per_unit: i32) {
+        self.pipeline.units[channel] = UnitScale {
+            offset_code,
+            code_per_unit,
+        };
+    }
+
+    /// Map `channel` at one code per unit from code 0 again, the default
+    pub fn clear_unit_scale(&mut self, channel: Channel) {
+        self.pipeline.units[channel] = UnitScale::default();
+    }
+
+    /// Scale of `channel` set through [`set_unit_scale`](Self::set_unit_scale)
+    pub fn unit_scale(&self, channel: Channel) -> UnitScale {
+        self.pipeline.units[channel]
+    }
+
+    /// Set a DAC channel to `milli` thousandths of a unit of its [scale](Self::set_unit_scale), e.g. microamps
+    /// for a scale in milliamps. The code goes through [`set_channel`](Self::set_channel), so calibration and
+    /// limits still apply. A code below 0 or over the max value fails like an out of bounds code, or with
+    /// [`ValuePolicy::Saturate`](crate::ValuePolicy::Saturate) is 0 or the max value
+    pub fn set_channel_units(&mut self, channel: Channel, milli: i32) -> Result<(), Error<S, P>> {
+        let max = self.resolution.max_code();
+        let code = self.pipeline.units[channel].code(milli);
+        let code = match u16::try_from(code) {
+            Ok(code) if code <= max => code,
+            _ if self.saturates() => code.clamp(0, i64::from(max)) as u16,
+            _ => return Err(self.resolution.out_of_bounds()),
+        };
+        self.set_channel(channel, code)
+    }
+
+    /// Thousandths of a unit of the [last code](Self::last_code) of `channel`, e.g. for telemetry. This is the
+    /// code as written, after calibration, limits and inversion, so with those set it differs from the value
+    /// last given to [`set_channel_units`](Self::set_channel_units). `None` until the driver writes the channel,
+    /// or with a `code_per_unit` of 0
+    pub fn channel_units(&self, channel: Channel) -> Option<i32> {
+        self.pipeline.units[channel].milli_units(self.last_code(channel)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{scale::UnitScale, Ad5328, Ad5328Config, Channel, Error, ValuePolicy};
+    use embedded_hal_mock::eh0::spi;
+
+    #[test]
+    fn units_map_to_codes_and_back() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            // 4 mA at code 800, 20 mA at 4000
+            spi::Transaction::write(vec![0x03, 0x20]),
+            spi::Transaction::write(vec![0x0f, 0xa0]),
+            spi::Transaction::write(vec![0x08, 0x98]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default()).unwrap();
+        dac.set_unit_scale(Channel::A, 0, 200 * UnitScale::ONE);
+        assert_eq!(dac.channel_units(Channel::A), None);
+        dac.set_channel_units(Channel::A, 4_000).unwrap();
+        assert_eq!(dac.channel_units(Channel::A), Some(4_000));
+        dac.set_channel_units(Channel::A, 20_000).unwrap();
+        assert!(matches!(
+            dac.set_channel_units(Channel::A, 20_478),
+            Err(Error::OobMax(4095))
+        ));
+        assert!(matches!(
+            dac.set_channel_units(Channel::A, -3),
+            Err(Error::OobMax(4095))
+        ));
+        // 11 mA
+        dac.set_channel_units(Channel::A, 10_998).unwrap();
+        assert_eq!(dac.channel_units(Channel::A), Some(11_000));
+        assert_eq!(dac.unit_scale(Channel::A).code_per_unit, 200 << 16);
+        dac.clear_unit_scale(Channel::A);
+        assert_eq!(dac.channel_units(Channel::A), Some(2_200_000));
+        spi.done();
+    }
+
+    #[test]
+    fn saturating_units_stop_at_both_ends() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default())
+            .unwrap()
+            .with_value_policy(ValuePolicy::Saturate);
+        dac.set_unit_scale(Channel::A, 2048, i32::MAX);
+        dac.set_channel_units(Channel::A, i32::MIN).unwrap();
+        dac.set_channel_units(Channel::A, i32::MAX).unwrap();
+        dac.set_unit_scale(Channel::A, i32::MAX, i32::MIN);
+        dac.set_channel_units(Channel::A, 0).unwrap();
+        dac.set_channel_units(Channel::A, i32::MAX).unwrap();
+        spi.done();
+    }
+}
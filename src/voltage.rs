@@ -0,0 +1,233 @@
+//! Output voltages as `uom` quantities, enabled with the `uom` feature.
+//!
+//! The output is `vref * gain * code / 2^bits`, as for [`scale::millivolts_to_code`](crate::scale::millivolts_to_code),
+//! with `vref` being the supply voltage for a group using VDD as reference.
+
+use uom::si::{electric_potential::volt, f32::ElectricPotential};
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, Transport, GAIN};
+
+/// Error of [`Ad53xx::set_channel_voltage`]
+#[derive(Debug)]
+pub enum VoltageError<S, P> {
+    /// The potential is negative, NaN or over the output of the full-scale code, which is carried
+    OutOfRange(ElectricPotential),
+    /// Error of the driver
+    Dac(Error<S, P>),
+}
+
+impl<S, P> From<Error<S, P>> for VoltageError<S, P> {
+    fn from(error: Error<S, P>) -> Self {
+        VoltageError::Dac(error)
+    }
+}
+
+impl<S, P> core::fmt::Display for VoltageError<S, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            VoltageError::OutOfRange(max) => write!(
+                f,
+                "potential out of range, the maximum is {} V",
+                max.get::<volt>()
+            ),
+            VoltageError::Dac(error) => core::fmt::Display::fmt(error, f),
+        }
+    }
+}
+
+impl<S, P> core::error::Error for VoltageError<S, P>
+where
+    S: core::error::Error + 'static,
+    P: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            VoltageError::OutOfRange(_) => None,
+            VoltageError::Dac(error) => core::error::Error::source(error),
+        }
+    }
+}
+
+fn full_scale_volts(vref: ElectricPotential, gain: GAIN) -> f32 {
+    vref.get::<volt>()
+        * match gain {
+            GAIN::Gain0Vref => 1.0,
+            GAIN::Gain02Vref => 2.0,
+        }
+}
+
+/// Output of `code` on a `bits` wide part with a reference of `vref` and `gain`
+pub fn code_to_voltage(
+    code: u16,
+    vref: ElectricPotential,
+    gain: GAIN,
+    bits: u32,
+) -> ElectricPotential {
+    let lsb = full_scale_volts(vref, gain) / (1u32 << bits) as f32;
+    ElectricPotential::new::<volt>(f32::from(code) * lsb)
+}
+
+/// Code for an output of `v`, rounded to nearest, or the output of the full-scale code if `v` is out of range
+pub fn voltage_to_code(
+    v: ElectricPotential,
+    vref: ElectricPotential,
+    gain: GAIN,
+    bits: u32,
+) -> Result<u16, ElectricPotential> {
+    let max = (1u16 << bits) - 1;
+    let code = v.get::<volt>() * (1u32 << bits) as f32 / full_scale_volts(vref, gain);
+    // also false for NaN, e.g. with a reference of 0 V
+    if !(-0.5..f32::from(max) + 0.5).contains(&code) {
+        return Err(code_to_voltage(max, vref, gain, bits));
+    }
+    // no `f32::round` in `core`
+    Ok((code + 0.5) as u16)
+}
+
+impl<SPI, EN, R, L> Ad53xx<SPI, EN, R, L>
+where
+    R: Resolution,
+{
+    /// Output of `code` on `channel` with the group gain as last configured, e.g. for telemetry.
+    /// `vref` is the reference of the channel's group, its supply voltage if the group uses VDD as reference
+    pub fn code_to_voltage(
+        &self,
+        channel: Channel,
+        code: u16,
+        vref: ElectricPotential,
+    ) -> ElectricPotential {
+        code_to_voltage(code, vref, self.gain(channel), self.resolution.bits())
+    }
+}
+
+impl<SPI, EN, R, L, S, P> Ad53xx<SPI, EN, R, L>
+where
+    SPI: Transport<EN, SpiError = S, PinError = P>,
+    R: Resolution,
+{
+    /// Set a DAC channel to an output of `v`, rounded to nearest code, with the group gain as last configured,
+    /// always 1 with VDD as reference. `vref` is as for [`code_to_voltage`](Self::code_to_voltage).
+    /// A potential out of range fails with the largest output the channel can reach, unless it saturates with
+    /// [`ValuePolicy::Saturate`](crate::ValuePolicy::Saturate). NaN or a reference that isn't positive always fail
+    pub fn set_channel_voltage(
+        &mut self,
+        channel: Channel,
+        v: ElectricPotential,
+        vref: ElectricPotential,
+    ) -> Result<(), VoltageError<S, P>> {
+        let volts = v.get::<volt>();
+        let code = match voltage_to_code(v, vref, self.gain(channel), self.resolution.bits()) {
+            Err(_) if self.saturates() && !volts.is_nan() && vref.get::<volt>() > 0.0 => {
+                if volts < 0.0 {
+                    0
+                } else {
+                    self.resolution.max_code()
+                }
+            }
+            code => code.map_err(VoltageError::OutOfRange)?,
+        };
+        Ok(self.set_channel(channel, code)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ad5328, Ad5328Config, VDD};
+    use embedded_hal_mock::eh0::spi;
+    use uom::si::electric_potential::millivolt;
+
+    fn mv(mv: f32) -> ElectricPotential {
+        ElectricPotential::new::<millivolt>(mv)
+    }
+
+    fn assert_mv(v: ElectricPotential, mv: f32) {
+        assert!(
+            (v.get::<millivolt>() - mv).abs() < 0.01,
+            "{v:?} is not {mv} mV"
+        );
+    }
+
+    #[test]
+    fn voltages_round_to_nearest_code() {
+        let vref = mv(4096.0);
+        assert_eq!(voltage_to_code(mv(0.0), vref, GAIN::Gain0Vref, 12), Ok(0));
+        assert_eq!(voltage_to_code(mv(-0.4), vref, GAIN::Gain0Vref, 12), Ok(0));
+        assert_eq!(voltage_to_code(mv(1.6), vref, GAIN::Gain02Vref, 12), Ok(1));
+        assert_eq!(
+            voltage_to_code(mv(4095.4), vref, GAIN::Gain0Vref, 12),
+            Ok(4095)
+        );
+        assert_eq!(
+            voltage_to_code(mv(2048.0), vref, GAIN::Gain0Vref, 8),
+            Ok(128)
+        );
+    }
+
+    #[test]
+    fn out_of_range_carries_the_maximum() {
+        let vref = mv(2048.0);
+        assert_eq!(
+            voltage_to_code(mv(4095.0), vref, GAIN::Gain02Vref, 12),
+            Ok(4095)
+        );
+        for v in [mv(4096.0), mv(-1.0), mv(f32::NAN)] {
+            let max = voltage_to_code(v, vref, GAIN::Gain02Vref, 12).unwrap_err();
+            assert_mv(max, 4095.0);
+        }
+        assert_mv(code_to_voltage(4095, vref, GAIN::Gain02Vref, 12), 4095.0);
+    }
+
+    #[test]
+    fn set_channel_voltage_applies_the_group_gain() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x3d]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x08, 0x00]),
+            spi::Transaction::write(vec![0x44, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(
+            spi.clone(),
+            Ad5328Config {
+                gain: (GAIN::Gain02Vref, GAIN::Gain02Vref),
+                vdd: (VDD::VddAsRef, VDD::ExternalRef),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        dac.set_channel_voltage(Channel::A, mv(2500.0), mv(5000.0))
+            .unwrap();
+        dac.set_channel_voltage(Channel::E, mv(1250.0), mv(2500.0))
+            .unwrap();
+        match dac.set_channel_voltage(Channel::E, mv(5000.0), mv(2500.0)) {
+            Err(VoltageError::OutOfRange(max)) => assert_mv(max, 4998.779),
+            _ => panic!("5 V is over full scale"),
+        }
+        assert_mv(dac.code_to_voltage(Channel::A, 2048, mv(5000.0)), 2500.0);
+        spi.done();
+    }
+
+    #[test]
+    fn saturating_voltages_still_reject_nan() {
+        let mut spi = spi::Mock::new(&[
+            spi::Transaction::write(vec![0x80, 0x0c]),
+            spi::Transaction::write(vec![0xa0, 0x01]),
+            spi::Transaction::write(vec![0x0f, 0xff]),
+            spi::Transaction::write(vec![0x00, 0x00]),
+        ]);
+        let mut dac = Ad5328::init_without_enable(spi.clone(), Ad5328Config::default())
+            .unwrap()
+            .with_value_policy(crate::ValuePolicy::Saturate);
+        dac.set_channel_voltage(Channel::A, mv(5000.0), mv(2500.0))
+            .unwrap();
+        dac.set_channel_voltage(Channel::A, mv(-10.0), mv(2500.0))
+            .unwrap();
+        for (v, vref) in [(mv(f32::NAN), mv(2500.0)), (mv(1000.0), mv(0.0))] {
+            assert!(matches!(
+                dac.set_channel_voltage(Channel::A, v, vref),
+                Err(VoltageError::OutOfRange(_))
+            ));
+        }
+        spi.done();
+    }
+}
@@ -0,0 +1,168 @@
+//! Wavetable playback with a 32-bit phase accumulator, e.g. for an LFO ticked from a timer interrupt.
+//!
+//! A full turn of the phase is one pass over the table, so a [`WavetablePlayer`] ticked at `f_tick` with an
+//! increment of `inc` plays `f_tick * inc / 2^32` periods per second, with every tick writing one frame.
+
+use crate::{resolution::Resolution, Ad53xx, Channel, Error, Transport};
+
+/// One period of a waveform, the table's samples evenly spaced over a turn of the phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wavetable<'a>(&'a [u16]);
+
+impl<'a> Wavetable<'a> {
+    /// Table of `samples`, `None` if it is empty or a sample is over `max`, e.g. 4095 for the AD5328
+    pub fn new(samples: &'a [u16], max: u16) -> Option<Self> {
+        if samples.is_empty() || samples.iter().any(|&sample| sample > max) {
+            return None;
+        }
+        Some(Self(samples))
+    }
+
+    /// The samples of the table
+    pub fn samples(&self) -> &'a [u16] {
+        self.0
+    }
+
+    /// Value at `phase`, interpolated linearly between the two samples around it and rounded to nearest.
+    /// Past the last sample it interpolates towards the first, so the waveform wraps around seamlessly
+    pub fn sample(&self, phase: u32) -> u16 {
+        let position = u64::from(phase) * self.0.len() as u64;
+        let index = (position >> 32) as usize;
+        let fraction = (position & 0xffff_ffff) as i64;
+        let low = i64::from(self.0[index]);
+        let high = i64::from(self.0[(index + 1) % self.0.len()]);
+        (low + (((high - low) * fraction + (1 << 31)) >> 32)) as u16
+    }
+}
+
+/// Plays a [`Wavetable`] out of one channel, one interpolated sample per [`tick`](Self::tick)
+#[derive(Clone, Copy)]
+pub struct WavetablePlayer<'a> {
+    table: Wavetable<'a>,
+    channel: Channel,
+    phase: u32,
+    increment: u32,
+}
+
+impl<'a> WavetablePlayer<'a> {
+    /// Player of `table` on `channel` starting at phase 0, advancing the phase by `increment` every tick
+    pub fn new(channel: Channel, table: Wavetable<'a>, increment: u32) -> Self {
+        Self {
+            table,
+            channel,
+            phase: 0,
+            increment,
+        }
+    }
+
+    /// Change the phase increment per tick, taking effect from the next tick on. The phase carries on from
+    /// where it is, so the output doesn't jump
+    pub fn set_rate(&mut self, increment: u32) {
+        self.increment = increment;
+    }
+
+    /// The phase increment per tick
+    pub fn rate(&self) -> u32 {
+        self.increment
+    }
+
+    /// Phase of the next sample, a full turn is `2^32`
+    pub fn phase(&self) -> u32 {
+        self.phase
+    }
+
+    /// Move to `phase`, e.g. 0 to retrigger the waveform
+    pub fn set_phase(&mut self, phase: u32) {
+        self.phase = phase;
+    }
+
+    /// The channel the player writes
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// Write the sample at the current phase through [`Ad53xx::set_channel`], then advance the phase.
+    /// On an error the phase stays, so the next tick writes the same sample again
+    pub fn tick<SPI, EN, R, L, S, P>(
+        &mut self,
+        dac: &mut Ad53xx<SPI, EN, R, L>,
+    ) -> Result<(), Error<S, P>>
+    where
+        SPI: Transport<EN, SpiError = S, PinError = P>,
+        R: Resolution,
+    {
+        dac.set_channel(self.channel, self.table.sample(self.phase))?;
+        self.phase = self.phase.wrapping_add(self.increment);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Frames;
+    use crate::{Ad5328, Ad5328Config};
+
+    const SAW: [u16; 4] = [0, 1000, 2000, 3000];
+
+    fn play(player: &mut WavetablePlayer<'_>, ticks: usize) -> Vec<u16> {
+        let mut dac =
+            Ad5328::init_without_enable(Frames::default(), Ad5328Config::default()).unwrap();
+        for _ in 0..ticks {
+            player.tick(&mut dac).unwrap();
+        }
+        dac.release().0.values()
+    }
+
+    #[test]
+    fn tables_are_checked_when_created() {
+        assert!(Wavetable::new(&[], 4095).is_none());
+        assert!(Wavetable::new(&[0, 4096], 4095).is_none());
+        assert!(Wavetable::new(&[0, 256], 255).is_none());
+        assert!(Wavetable::new(&[4095], 4095).is_some());
+    }
+
+    #[test]
+    fn samples_are_interpolated_and_wrap_around() {
+        let table = Wavetable::new(&SAW, 4095).unwrap();
+        let mut player = WavetablePlayer::new(Channel::A, table, 1 << 28);
+        assert_eq!(
+            play(&mut player, 17),
+            [
+                0, 250, 500, 750, 1000, 1250, 1500, 1750, 2000, 2250, 2500, 2750, 3000, 2250, 1500,
+                750, 0
+            ]
+        );
+        assert_eq!(table.sample(u32::MAX), 0);
+        assert_eq!(table.sample(0x5555_5555), 1333);
+    }
+
+    #[test]
+    fn period_follows_the_increment() {
+        let table = Wavetable::new(&SAW, 4095).unwrap();
+        let mut player = WavetablePlayer::new(Channel::A, table, 1 << 26);
+        let values = play(&mut player, 256);
+        assert!((0..192).all(|n| values[n] == values[n + 64]));
+        assert!((1..64).all(|n| values[n] != values[0]));
+
+        // 48 ticks per period, rounded up so the 48th tick just wraps
+        let increment = (1u64 << 32).div_ceil(48) as u32;
+        let mut player = WavetablePlayer::new(Channel::A, table, increment);
+        play(&mut player, 47);
+        assert!(player.phase() > u32::MAX - increment);
+        play(&mut player, 1);
+        assert!(player.phase() < increment);
+    }
+
+    #[test]
+    fn rate_changes_keep_the_phase() {
+        let table = Wavetable::new(&SAW, 4095).unwrap();
+        let mut player = WavetablePlayer::new(Channel::A, table, 1 << 28);
+        assert_eq!(play(&mut player, 3), [0, 250, 500]);
+        player.set_rate(1 << 26);
+        assert_eq!(player.rate(), 1 << 26);
+        assert_eq!(play(&mut player, 3), [750, 813, 875]);
+        player.set_phase(0);
+        assert_eq!(play(&mut player, 1), [0]);
+    }
+}
@@ -0,0 +1,156 @@
+//! Streaming of channel values through `WriteIter`, enabled with the `write-iter` feature.
+
+use embedded_hal::{blocking::spi::WriteIter, digital::v2::OutputPin};
+
+use crate::{command, interface::framed, Ad5328, Channel, StreamError, Transport};
+
+impl<SPI, EN, S, P> Ad5328<SPI, EN>
+where
+    SPI: WriteIter<u8, Error = S> + Transport<EN, SpiError = S, PinError = P>,
+    EN: OutputPin<Error = P>,
+{
+    /// Set a sequence of channel values, handing each frame's bytes to the bus as an iterator
+    /// instead of through the command buffer. SYNC is still toggled around every frame.
+    ///
+    /// Each value goes through the value pipeline and is tracked as for [`update_channel`](Self::update_channel),
+    /// so limits, the channel map, inversion, mutes, mirrors, deduplication and rate limits apply as they do
+    /// there. A value that fails, e.g. one over 4095, stops the sequence before its frame is started, all
+    /// earlier frames have been written. The returned error carries the index of the offending item.
+    pub fn set_channels_iter(
+        &mut self,
+        values: impl IntoIterator<Item = (Channel, u16)>,
+    ) -> Result<(), StreamError<S, P>> {
+        let mut send = |dac: &mut Self, cmd: u16| {
+            framed(&mut dac.enable, || {
+                dac.spi.write_iter(command::to_bytes(cmd))
+            })
+        };
+        for (index, (channel, value)) in values.into_iter().enumerate() {
+            self.set_command(channel, value)
+                .and_then(|cmd| self.write_data_via(channel, Some(value), cmd, &mut send))
+                .map_err(|error| StreamError { index, error })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ad5328Config, Error};
+    use core::convert::Infallible;
+    use embedded_hal::blocking::spi::Write;
+
+    #[derive(Default)]
+    struct Bytes(Vec<u8>);
+
+    impl Write<u8> for Bytes {
+        type Error = Infallible;
+
+        fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+            self.0.extend_from_slice(words);
+            Ok(())
+        }
+    }
+
+    impl WriteIter<u8> for Bytes {
+        type Error = Infallible;
+
+        fn write_iter<WI: IntoIterator<Item = u8>>(&mut self, words: WI) -> Result<(), Infallible> {
+            self.0.extend(words);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn iter_writes_match_repeated_set_channel() {
+        let values: Vec<_> = (0..64u16)
+            .map(|n| (Channel::from((n % 8) as u8), n * 64))
+            .collect();
+
+        let mut dac =
+            Ad5328::init_without_enable(Bytes::default(), Ad5328Config::default()).unwrap();
+        for &(channel, value) in &values {
+            dac.set_channel(channel, value).unwrap();
+        }
+        let (expected, _) = dac.release();
+
+        let mut dac =
+            Ad5328::init_without_enable(Bytes::default(), Ad5328Config::default()).unwrap();
+        dac.set_channels_iter(values.iter().copied()).unwrap();
+        let (streamed, _) = dac.release();
+        assert_eq!(streamed.0, expected.0);
+    }
+
+    #[test]
+    fn streamed_codes_are_tracked_for_later_writes() {
+        let mut dac = Ad5328::init_without_enable(Bytes::default(), Ad5328Config::default())
+            .unwrap()
+            .with_dedup(true);
+        dac.set_channel(Channel::A, 100).unwrap();
+        dac.set_channels_iter([(Channel::A, 200), (Channel::A, 200)])
+            .unwrap();
+        assert_eq!(dac.last_code(Channel::A), Some(200));
+        dac.set_channel(Channel::A, 100).unwrap();
+        let (spi, _) = dac.release();
+        assert_eq!(spi.0[4..], [0x00, 0x64, 0x00, 0xc8, 0x00, 0x64]);
+    }
+
+    #[test]
+    fn streamed_values_go_through_the_channel_settings() {
+        use crate::LimitPolicy;
+        use Channel::*;
+
+        let mut dac =
+            Ad5328::init_without_enable(Bytes::default(), Ad5328Config::default()).unwrap();
+        dac.set_limits(A, 0, 1000).unwrap();
+        dac.set_limit_policy(LimitPolicy::Reject);
+        let result = dac.set_channels_iter([(B, 1), (A, 2000)]);
+        assert!(matches!(
+            result,
+            Err(StreamError {
+                index: 1,
+                error: Error::Limited(2000)
+            })
+        ));
+        dac.set_limit_policy(LimitPolicy::Clamp);
+        dac.set_inverted(C, true);
+        dac.set_channel_map([B, A, C, D, E, F, G, H]).unwrap();
+        dac.mute([D]).unwrap();
+        dac.set_channels_iter([(A, 2000), (C, 0), (D, 7)]).unwrap();
+        let (spi, _) = dac.release();
+        assert_eq!(
+            spi.0[4..],
+            [
+                0x10, 0x01, // limit rejected before its frame
+                0x30, 0x00, // mute
+                0x13, 0xe8, // clamped to 1000, on physical channel B
+                0x2f, 0xff, // inverted
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_bounds_item_stops_before_its_frame() {
+        use embedded_hal_mock::eh0::pin;
+
+        let toggle = [
+            pin::Transaction::set(pin::State::Low),
+            pin::Transaction::set(pin::State::High),
+        ];
+        let mut enable = pin::Mock::new(toggle.iter().cycle().take(2 * 3));
+        let mut dac =
+            Ad5328::init(Bytes::default(), enable.clone(), Ad5328Config::default()).unwrap();
+        let result = dac.set_channels_iter([(Channel::A, 1), (Channel::B, 4096), (Channel::C, 3)]);
+        assert!(matches!(
+            result,
+            Err(StreamError {
+                index: 1,
+                error: Error::OobMax(4095)
+            })
+        ));
+        let (spi, _) = dac.release();
+        assert_eq!(spi.0[4..], [0x00, 0x01]);
+        enable.done();
+    }
+}
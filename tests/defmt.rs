@@ -0,0 +1,94 @@
+//! Formats every public type through a minimal global logger, as firmware would over RTT.
+#![cfg(feature = "defmt")]
+
+use std::sync::Mutex;
+
+use ad5328::{
+    bank::BankError,
+    frames::{EncodeError, Op},
+    resolution::AnyResolution,
+    Ad5328Config, Channel, Error, StreamError, BUF, GAIN, LDAC, VDD,
+};
+
+static BYTES: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {}
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {}
+
+    unsafe fn write(bytes: &[u8]) {
+        BYTES.lock().unwrap().extend_from_slice(bytes);
+    }
+}
+
+defmt::timestamp!("{=u32}", 0);
+
+#[derive(defmt::Format)]
+struct BusError;
+
+struct OpaqueError;
+
+#[test]
+fn every_variant_formats() {
+    let errors: [Error<BusError, BusError>; 6] = [
+        Error::Spi(BusError),
+        Error::Pin(BusError),
+        Error::Conn,
+        Error::Address,
+        Error::Port,
+        Error::Oob,
+    ];
+    for error in errors {
+        defmt::println!("{}", error);
+    }
+    let opaque: Error<OpaqueError, OpaqueError> = Error::Spi(OpaqueError);
+    defmt::println!("{}", opaque.erase());
+    defmt::println!(
+        "{} {}",
+        StreamError {
+            index: 3,
+            error: Error::<(), ()>::Oob
+        },
+        BankError {
+            device: 1,
+            error: Error::<(), ()>::Address
+        }
+    );
+
+    for channel in 0..8u8 {
+        defmt::println!("{}", Channel::from(channel));
+    }
+    defmt::println!("{} {}", GAIN::Gain0Vref, GAIN::Gain02Vref);
+    defmt::println!("{} {}", BUF::Unbuffered, BUF::Buffered);
+    defmt::println!("{} {}", VDD::ExternalRef, VDD::VddAsRef);
+    defmt::println!(
+        "{} {} {}",
+        LDAC::LdacLow,
+        LDAC::LdacHigh,
+        LDAC::LdacSingleUpdate
+    );
+    defmt::println!("{}", Ad5328Config::default());
+    defmt::println!(
+        "{} {} {} {}",
+        AnyResolution::Bits8,
+        AnyResolution::Bits10,
+        AnyResolution::Bits12,
+        Error::<(), ()>::OobMax(1023)
+    );
+    defmt::println!("{} {}", EncodeError::BufferTooSmall, EncodeError::Oob);
+    defmt::println!(
+        "{} {} {} {}",
+        Op::SetChannel(Channel::A, 1),
+        Op::Configure(Ad5328Config::default()),
+        Op::PowerDown([false; 8]),
+        Op::Reset(true)
+    );
+
+    assert!(!BYTES.lock().unwrap().is_empty());
+}
@@ -0,0 +1,77 @@
+//! Calls the exported symbols through their C declarations, as C firmware would.
+#![cfg(feature = "ffi")]
+
+use ad5328::{
+    ffi::{AD5328_ERR_CHANNEL, AD5328_ERR_LDAC_MODE, AD5328_ERR_VALUE},
+    frames, Ad5328Config, Channel, BUF, GAIN, LDAC, VDD,
+};
+
+extern "C" {
+    fn ad5328_encode_set_channel(channel: u8, value: u16) -> i32;
+    fn ad5328_encode_config(
+        gain_ad: bool,
+        gain_eh: bool,
+        buf_ad: bool,
+        buf_eh: bool,
+        vdd_ad: bool,
+        vdd_eh: bool,
+    ) -> u16;
+    fn ad5328_encode_ldac(mode: u8) -> i32;
+    fn ad5328_encode_power_down(channels: u8) -> u16;
+    fn ad5328_encode_reset(full_reset: bool) -> u16;
+}
+
+fn word(frame: [u8; 2]) -> u16 {
+    u16::from_be_bytes(frame)
+}
+
+#[test]
+fn set_channel_matches_the_rust_encoder() {
+    let mut frame = [0; 2];
+    for channel in 0..8 {
+        for value in [0, 1, 0x123, 4095] {
+            frames::encode_set_channel(Channel::from(channel), value, &mut frame).unwrap();
+            let encoded = unsafe { ad5328_encode_set_channel(channel, value) };
+            assert_eq!(encoded, i32::from(word(frame)));
+        }
+    }
+    assert_eq!(
+        unsafe { ad5328_encode_set_channel(8, 0) },
+        AD5328_ERR_CHANNEL
+    );
+    assert_eq!(
+        unsafe { ad5328_encode_set_channel(0, 4096) },
+        AD5328_ERR_VALUE
+    );
+}
+
+#[test]
+fn control_words_match_the_rust_encoder() {
+    let config = Ad5328Config {
+        gain: (GAIN::Gain02Vref, GAIN::Gain0Vref),
+        buf: (BUF::Unbuffered, BUF::Buffered),
+        vdd: (VDD::ExternalRef, VDD::VddAsRef),
+        ldac: LDAC::LdacSingleUpdate,
+    };
+    let mut frames = [0; 4];
+    frames::encode_config(&config, &mut frames);
+    let control = unsafe { ad5328_encode_config(true, false, false, true, false, true) };
+    assert_eq!(control, word([frames[0], frames[1]]));
+    assert_eq!(
+        unsafe { ad5328_encode_ldac(2) },
+        i32::from(word([frames[2], frames[3]]))
+    );
+    assert_eq!(unsafe { ad5328_encode_ldac(3) }, AD5328_ERR_LDAC_MODE);
+
+    let mut frame = [0; 2];
+    frames::encode_power_down(
+        [true, false, false, true, false, false, false, true],
+        &mut frame,
+    );
+    assert_eq!(
+        unsafe { ad5328_encode_power_down(0b1000_1001) },
+        word(frame)
+    );
+    frames::encode_reset(true, &mut frame);
+    assert_eq!(unsafe { ad5328_encode_reset(true) }, word(frame));
+}
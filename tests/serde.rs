@@ -0,0 +1,103 @@
+#![cfg(feature = "serde")]
+
+use ad5328::{
+    measure::{ChannelReport, SelfTestReport},
+    scale::VoctCorrection,
+    snapshot::Snapshot,
+    Ad5328Config, Channel, ChannelMap, BUF, GAIN, LDAC, VDD,
+};
+
+fn config() -> Ad5328Config {
+    Ad5328Config {
+        gain: (GAIN::Gain02Vref, GAIN::Gain0Vref),
+        buf: (BUF::Unbuffered, BUF::Buffered),
+        vdd: (VDD::ExternalRef, VDD::VddAsRef),
+        ldac: LDAC::LdacSingleUpdate,
+    }
+}
+
+/// Compare through the JSON form, as the driver types don't implement `PartialEq`
+fn assert_same(a: &Ad5328Config, b: &Ad5328Config) {
+    assert_eq!(
+        serde_json::to_value(a).unwrap(),
+        serde_json::to_value(b).unwrap()
+    );
+}
+
+#[test]
+fn config_round_trips_through_postcard() {
+    let mut buf = [0; 16];
+    let bytes = postcard::to_slice(&config(), &mut buf).unwrap();
+    let decoded: Ad5328Config = postcard::from_bytes(bytes).unwrap();
+    assert_same(&decoded, &config());
+}
+
+#[test]
+fn config_round_trips_through_json() {
+    let json = serde_json::to_string(&config()).unwrap();
+    let decoded: Ad5328Config = serde_json::from_str(&json).unwrap();
+    assert_same(&decoded, &config());
+
+    let channel: Channel = serde_json::from_str(r#""H""#).unwrap();
+    assert_eq!(u8::from(channel), 7);
+}
+
+#[test]
+fn invalid_discriminants_are_rejected() {
+    let mut buf = [0; 16];
+    let bytes = postcard::to_slice(&config(), &mut buf).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] = 3;
+    assert!(postcard::from_bytes::<Ad5328Config>(bytes).is_err());
+
+    assert!(serde_json::from_str::<Channel>(r#""I""#).is_err());
+    assert!(serde_json::from_str::<LDAC>(r#""LdacMaybe""#).is_err());
+}
+
+#[test]
+fn voct_corrections_round_trip_and_are_checked() {
+    let mut corrections = ChannelMap::<Option<VoctCorrection>>::default();
+    corrections[Channel::C] = VoctCorrection::new(&[-3, 0, 5]);
+    let mut buf = [0; 128];
+    let bytes = postcard::to_slice(&corrections, &mut buf).unwrap();
+    let decoded: ChannelMap<Option<VoctCorrection>> = postcard::from_bytes(bytes).unwrap();
+    assert_eq!(decoded, corrections);
+
+    let json = serde_json::to_value(corrections[Channel::C]).unwrap();
+    assert_eq!(json["len"], 3);
+    let mut too_long = json.clone();
+    too_long["len"] = 12.into();
+    assert!(serde_json::from_value::<VoctCorrection>(too_long).is_err());
+}
+
+#[test]
+fn snapshots_round_trip() {
+    let snapshot = Snapshot([0, 1, 2, 3, 4095, 2048, 7, 9]);
+    let mut buf = [0; 32];
+    let bytes = postcard::to_slice(&snapshot, &mut buf).unwrap();
+    assert_eq!(postcard::from_bytes::<Snapshot>(bytes).unwrap(), snapshot);
+    let json = serde_json::to_string(&snapshot).unwrap();
+    assert_eq!(json, "[0,1,2,3,4095,2048,7,9]");
+    assert_eq!(serde_json::from_str::<Snapshot>(&json).unwrap(), snapshot);
+}
+
+#[test]
+fn self_test_reports_round_trip() {
+    let mut report = SelfTestReport::default();
+    report.channels[Channel::D] = ChannelReport {
+        passed: false,
+        worst_error_mv: 2499,
+    };
+    let mut buf = [0; 64];
+    let bytes = postcard::to_slice(&report, &mut buf).unwrap();
+    assert_eq!(
+        postcard::from_bytes::<SelfTestReport>(bytes).unwrap(),
+        report
+    );
+    let json = serde_json::to_value(report).unwrap();
+    assert_eq!(json["channels"][3]["worst_error_mv"], 2499);
+    assert_eq!(
+        serde_json::from_value::<SelfTestReport>(json).unwrap(),
+        report
+    );
+}
@@ -0,0 +1,33 @@
+use ad5328::{Ad5328, Ad5328Config, Channel};
+use embedded_hal::blocking::spi::Write;
+use embedded_hal_mock::eh0::{pin, spi};
+use shared_bus::BusManagerSimple;
+
+#[test]
+fn dac_and_second_owner_interleave_on_a_shared_bus() {
+    let mut spi = spi::Mock::new(&[
+        spi::Transaction::write(vec![0x80, 0x0c]),
+        spi::Transaction::write(vec![0xa0, 0x01]),
+        spi::Transaction::write(vec![0x01]),
+        spi::Transaction::write(vec![0x00, 0xab]),
+        spi::Transaction::write(vec![0x02]),
+        spi::Transaction::write(vec![0x7f, 0xff]),
+    ]);
+    let toggle = [
+        pin::Transaction::set(pin::State::Low),
+        pin::Transaction::set(pin::State::High),
+    ];
+    let mut enable = pin::Mock::new(toggle.iter().cycle().take(4 * 2));
+    let bus = BusManagerSimple::new(spi.clone());
+
+    let mut dac = Ad5328::init(bus.acquire_spi(), enable.clone(), Ad5328Config::default()).unwrap();
+    let mut other = bus.acquire_spi();
+
+    other.write(&[0x01]).unwrap();
+    dac.set_channel(Channel::A, 0x0ab).unwrap();
+    other.write(&[0x02]).unwrap();
+    dac.set_channel(Channel::H, 0xfff).unwrap();
+
+    spi.done();
+    enable.done();
+}
@@ -0,0 +1,44 @@
+//! Drives the DAC from application-style code against expectations built with `test_support`.
+#![cfg(feature = "test-support")]
+
+use ad5328::{
+    test_support::{expect_configure, expect_power_down, expect_reset, expect_set_channel},
+    Ad5328, Ad5328Config, Channel, Error, GAIN, LDAC, VDD,
+};
+use embedded_hal_mock::eh0::{pin, spi, MockError};
+
+/// Ramps `channel` up in three steps, then parks the outputs
+fn ramp_and_park(
+    dac: &mut Ad5328<spi::Mock, pin::Mock>,
+    channel: Channel,
+) -> Result<(), Error<MockError, MockError>> {
+    for step in 1..=3 {
+        dac.set_channel(channel, step * 1365)?;
+    }
+    dac.power_down([true; 8])?;
+    dac.reset(false)
+}
+
+#[test]
+fn init_ramp_and_park() {
+    let config = Ad5328Config {
+        gain: (GAIN::Gain02Vref, GAIN::Gain0Vref),
+        vdd: (VDD::VddAsRef, VDD::ExternalRef),
+        ldac: LDAC::LdacLow,
+        ..Default::default()
+    };
+    let expected = expect_configure(&config)
+        .then(expect_set_channel(Channel::F, 1365))
+        .then(expect_set_channel(Channel::F, 2730))
+        .then(expect_set_channel(Channel::F, 4095))
+        .then(expect_power_down([true; 8]))
+        .then(expect_reset(false));
+    let mut spi = spi::Mock::new(&expected.spi);
+    let mut enable = pin::Mock::new(&expected.enable);
+
+    let mut dac = Ad5328::init(spi.clone(), enable.clone(), config).unwrap();
+    ramp_and_park(&mut dac, Channel::F).unwrap();
+
+    spi.done();
+    enable.done();
+}